@@ -0,0 +1,39 @@
+use crate::dungeon;
+use crate::watch;
+use clap::Args;
+use core::converters;
+use core::formats;
+use std::process::exit;
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// The file containing the converted data,
+    /// its format is detected automatically, `-` reads it from stdin
+    #[clap(value_name = "FILE", index = 1)]
+    file: Option<String>,
+
+    /// draw the dungeon without colors
+    #[clap(long)]
+    no_color: bool,
+}
+
+pub fn run(args: &RunArgs, result: Option<String>, watch: bool) {
+    let file = args.file.clone().unwrap_or_default();
+    //the changes made while the file is loaded are not missed
+    let watcher = watch.then(|| watch::new(&file)).flatten();
+
+    //load the converted data from the file
+    let converted_data = match load_converted_data(&file) {
+        Ok(d) => d,
+        Err(err) => {
+            eprintln!("Error: failed to load converted data: {err}");
+            exit(1);
+        }
+    };
+
+    dungeon::run(converted_data, !args.no_color, result, watcher);
+}
+
+fn load_converted_data(file: &str) -> Result<converters::dungeon::Data, core::Error> {
+    formats::load(file)
+}
@@ -1,3 +1,4 @@
+pub mod dungeon;
 pub mod shmup;
 
 use clap::{Parser, Subcommand};
@@ -6,6 +7,8 @@ use clap::{Parser, Subcommand};
 enum Commands {
     /// Shmup will start the shmup game
     Shmup(shmup::RunArgs),
+    /// Dungeon will draw the dungeon in the terminal
+    Dungeon(dungeon::RunArgs),
 }
 
 /// `play` launches a selection of games or simulators
@@ -14,6 +17,14 @@ enum Commands {
 struct Cli {
     #[clap(subcommand)]
     command: Commands,
+
+    /// The file the result of the session is written to, as json
+    #[clap(long, value_name = "FILE", global = true)]
+    result: Option<String>,
+
+    /// reload the world every time the file changes
+    #[clap(long, global = true)]
+    watch: bool,
 }
 
 pub fn run() {
@@ -21,7 +32,10 @@ pub fn run() {
 
     match &cli.command {
         Commands::Shmup(args) => {
-            shmup::run(args);
+            shmup::run(args, cli.result.clone(), cli.watch);
+        }
+        Commands::Dungeon(args) => {
+            dungeon::run(args, cli.result.clone(), cli.watch);
         }
     }
 }
@@ -1,35 +1,35 @@
 use crate::shmup;
+use crate::watch;
 use clap::Args;
 use core::converters;
-use std::fs::File;
-use std::io::{Error, Read};
+use core::formats;
+use std::process::exit;
 
 #[derive(Args, Debug)]
 pub struct RunArgs {
-    /// The json file containing the converted data
+    /// The file containing the converted data,
+    /// its format is detected automatically, `-` reads it from stdin
     #[clap(value_name = "FILE", index = 1)]
     file: Option<String>,
 }
 
-pub fn run(args: &RunArgs) {
+pub fn run(args: &RunArgs, result: Option<String>, watch: bool) {
     let file = args.file.clone().unwrap_or_default();
+    //the changes made while the file is loaded are not missed
+    let watcher = watch.then(|| watch::new(&file)).flatten();
 
     //load the converted.json data from the file
     let converted_data = match load_converted_data(&file) {
         Ok(d) => d,
         Err(err) => {
-            println!("Error: failed to load converted data: {err}");
-            return;
+            eprintln!("Error: failed to load converted data: {err}");
+            exit(1);
         }
     };
 
-    shmup::run(converted_data);
+    shmup::run(converted_data, result, watcher);
 }
 
-fn load_converted_data(file: &str) -> Result<core::converters::shmup::Data, Error> {
-    let mut f = File::open(file)?;
-    let mut data = String::new();
-    f.read_to_string(&mut data)?;
-    let data: converters::shmup::Data = serde_json::from_str(&data)?;
-    Ok(data)
+fn load_converted_data(file: &str) -> Result<converters::shmup::Data, core::Error> {
+    formats::load(file)
 }
@@ -0,0 +1,159 @@
+use crate::watch;
+use core::converters::dungeon;
+use core::exec::{self, session};
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::exit;
+use std::thread;
+
+//the glyph of the player, drawn on the start tile
+const PLAYER_GLYPH: char = '@';
+//the color of the walls, the other tiles keep the terminal color
+const WALL_COLOR: &str = "#8b949e";
+const RESET: &str = "\x1b[0m";
+//clear the terminal and move the cursor to its top left corner
+const CLEAR: &str = "\x1b[2J\x1b[H";
+
+/// draw the dungeon in the terminal, colors are used when `color`
+/// is set and stdout is a terminal, or colors are forced.
+/// The dungeon is only drawn, it is not played: the `result` file records
+/// the start room as visited, and no score nor time played.
+///
+/// With a `watcher`, the dungeon is drawn again every time its
+/// file changes, until the player is interrupted
+pub fn run(
+    data: dungeon::Data,
+    color: bool,
+    result: Option<String>,
+    watcher: Option<watch::Watcher>,
+) {
+    let forced = env::var(exec::CLICOLOR_FORCE_ENV).is_ok_and(|v| v != "0");
+    let terminal = io::stdout().is_terminal() || forced;
+    let color = color && terminal;
+    draw(&data, color, &result);
+
+    let mut watcher = match watcher {
+        Some(w) => w,
+        None => return,
+    };
+    loop {
+        thread::sleep(watch::INTERVAL);
+        match watcher.reload::<dungeon::Data>() {
+            None => (),
+            Some(Ok(data)) => {
+                //the new dungeon replaces the previous one on screen
+                if terminal {
+                    print!("{CLEAR}");
+                }
+                draw(&data, color, &result);
+            }
+            Some(Err(err)) => eprintln!("Warning: failed to reload the dungeon: {err}"),
+        }
+    }
+}
+
+//draw the dungeon and write the session to the `result` file
+fn draw(data: &dungeon::Data, color: bool, result: &Option<String>) {
+    let mut out = io::stdout().lock();
+    if let Err(err) = out
+        .write_all(render(data, color).as_bytes())
+        .and_then(|_| out.flush())
+    {
+        eprintln!("Error: failed to draw the dungeon: {err}");
+        exit(1);
+    }
+
+    let path = match result {
+        Some(p) => p,
+        None => return,
+    };
+    let mut s = session::Session {
+        player: "dungeon".to_string(),
+        ..Default::default()
+    };
+    s.visit(&data.main_room);
+    if let Err(err) = s.save(path) {
+        eprintln!("Error: {err}");
+        exit(1);
+    }
+}
+
+/// returns the tile map of the dungeon with its placements,
+/// followed by a legend
+pub fn render(data: &dungeon::Data, color: bool) -> String {
+    //each cell holds the glyph drawn and its color
+    let mut cells: Vec<Vec<(char, &str)>> = data
+        .tiles
+        .iter()
+        .map(|row| {
+            row.chars()
+                .map(|c| match c {
+                    dungeon::TILE_WALL => (c, WALL_COLOR),
+                    _ => (c, ""),
+                })
+                .collect()
+        })
+        .collect();
+
+    for p in &data.placements {
+        let glyph = p.glyph.chars().next().unwrap_or('?');
+        set(&mut cells, p.x, p.y, (glyph, p.color.as_str()));
+    }
+    set(&mut cells, data.start.x, data.start.y, (PLAYER_GLYPH, ""));
+
+    let mut out = format!("{} @ {}\n", data.repo_name, short_commit(&data.commit));
+    for row in &cells {
+        let mut line = String::new();
+        for (glyph, hex) in row {
+            match (color, to_ansi(hex)) {
+                (true, Some(code)) => line.push_str(&format!("{code}{glyph}{RESET}")),
+                _ => line.push(*glyph),
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+
+    let monsters = data
+        .placements
+        .iter()
+        .filter(|p| p.kind == dungeon::PLACEMENT_MONSTER)
+        .count();
+    out.push_str(&format!(
+        "{} rooms, {monsters} monsters, {} items. {PLAYER_GLYPH} you, {} door, {} corridor\n",
+        data.rooms.len(),
+        data.placements.len() - monsters,
+        dungeon::TILE_DOOR,
+        dungeon::TILE_CORRIDOR,
+    ));
+    out
+}
+
+fn set<'a>(cells: &mut [Vec<(char, &'a str)>], x: u32, y: u32, cell: (char, &'a str)) {
+    if let Some(c) = cells
+        .get_mut(y as usize)
+        .and_then(|row| row.get_mut(x as usize))
+    {
+        *c = cell;
+    }
+}
+
+fn short_commit(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
+
+//returns the ANSI escape code of the `#rrggbb` color
+fn to_ansi(hex: &str) -> Option<String> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(format!(
+        "\x1b[38;2;{};{};{}m",
+        channel(0)?,
+        channel(2)?,
+        channel(4)?
+    ))
+}
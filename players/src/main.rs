@@ -1,5 +1,7 @@
 mod cmd;
+mod dungeon;
 mod shmup;
+mod watch;
 
 fn main() {
     cmd::run();
@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+use core::converters::shmup;
+
+/// Boss is an enemy fighting in multiple phases,
+/// it moves to its next phase as it loses its hp
+#[derive(Component, Debug, Default)]
+pub struct Boss {
+    pub hp: f32,
+    pub max_hp: f32,
+    //the index of the current phase
+    pub phase: usize,
+    pub phases: Vec<shmup::BossPhase>,
+    //the speed of the boss entity, a value between [0-1]
+    pub speed: f32,
+}
+
+/// BossHealthBar displays the hp left of its parent boss
+#[derive(Component, Debug, Default)]
+pub struct BossHealthBar {
+    pub width: f32,
+}
+
+/// Minion is an enemy spawned by a boss when it moves to its next phase
+#[derive(Component, Debug, Default)]
+pub struct Minion {}
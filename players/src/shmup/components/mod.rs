@@ -1,3 +1,4 @@
+pub mod boss;
 pub mod camera;
 pub mod enemy;
 pub mod enemy_bullets;
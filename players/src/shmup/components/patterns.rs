@@ -50,8 +50,7 @@ pub struct Pattern3 {
     pub dir_y: f32,
 }
 
-pub fn pattern3_random_dir(p: &mut Pattern3) {
-    let mut r = thread_rng();
+pub fn pattern3_random_dir(p: &mut Pattern3, r: &mut impl Rng) {
     let prob = r.gen_range(0..100);
 
     if (20..40).contains(&prob) {
@@ -69,8 +68,7 @@ pub fn pattern3_random_dir(p: &mut Pattern3) {
     }
 }
 
-pub fn pattern3_random_time(p: &mut Pattern3) {
-    let mut r = thread_rng();
+pub fn pattern3_random_time(p: &mut Pattern3, r: &mut impl Rng) {
     let prob = r.gen_range(0..100);
 
     let mut t = 1.;
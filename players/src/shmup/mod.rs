@@ -8,14 +8,39 @@ pub mod systems;
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::time::FixedTimestep;
 use bevy::{app::PluginGroupBuilder, prelude::*};
+use rand::{rngs::StdRng, SeedableRng};
 
+use crate::watch;
 use core::converters;
+use core::exec::session;
 
 const TIMESTEP_60_FPS: f64 = 1. / 60.;
 
 #[derive(Resource, Default, Debug)]
 pub struct WorldData(converters::shmup::Data);
 
+/// WorldRng is the random number generator of the world,
+/// seeded with the world data seed so that the same data
+/// always plays the same way
+#[derive(Resource)]
+pub struct WorldRng(pub StdRng);
+
+/// PlaySession records how the session goes, it is
+/// written to the `result` file when the game exits
+#[derive(Resource, Default, Debug)]
+pub struct PlaySession {
+    pub session: session::Session,
+    pub result: Option<String>,
+}
+
+/// WatchedFile is the file of the world data, the world
+/// is reloaded every time the file changes
+#[derive(Resource, Debug)]
+pub struct WatchedFile {
+    pub watcher: watch::Watcher,
+    pub timer: Timer,
+}
+
 fn default_plugins() -> PluginGroupBuilder {
     DefaultPlugins.set({
         WindowPlugin {
@@ -28,13 +53,28 @@ fn default_plugins() -> PluginGroupBuilder {
     })
 }
 
-pub fn run(data: converters::shmup::Data) {
-    App::new()
-        .add_plugins(default_plugins())
+pub fn run(data: converters::shmup::Data, result: Option<String>, watcher: Option<watch::Watcher>) {
+    let mut session = session::Session {
+        player: "shmup".to_string(),
+        ..Default::default()
+    };
+    //the game is played in the main scene
+    session.visit(&data.main_scene);
+
+    let mut app = App::new();
+    if let Some(watcher) = watcher {
+        app.insert_resource(WatchedFile {
+            watcher,
+            timer: Timer::new(watch::INTERVAL, TimerMode::Repeating),
+        });
+    }
+    app.add_plugins(default_plugins())
         .add_plugin(LogDiagnosticsPlugin::default())
         .add_plugin(FrameTimeDiagnosticsPlugin)
         .add_system_set(SystemSet::new().with_run_criteria(FixedTimestep::step(TIMESTEP_60_FPS)))
+        .insert_resource(WorldRng(StdRng::seed_from_u64(data.seed)))
         .insert_resource(WorldData(data))
+        .insert_resource(PlaySession { session, result })
         .add_plugin(plugin::ShmupPlugin)
         .run();
 }
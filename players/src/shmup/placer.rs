@@ -1,11 +1,15 @@
+use super::components::boss;
 use super::components::enemy;
 use super::components::guns;
+use super::components::patterns;
 use super::components::player;
 use super::config;
 use super::WorldData;
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
-use core::shapes;
+use bevy::{ecs::system::EntityCommands, prelude::*, sprite::MaterialMesh2dBundle};
+use core::converters::shmup;
+use core::{patterns as movement_patterns, shapes, weapons};
 use rand::prelude::*;
+use rand::rngs::StdRng;
 
 const BG_MAP_SIZE: u32 = 100;
 const BG_MAP_BLOCK_SIZE: u32 = 30;
@@ -16,6 +20,14 @@ const AREA_BLOCK_PADDING: f32 = 20.;
 const AREA_BLOCK_COL: i32 = 20;
 const AREA_BLOCK_ROW: i32 = 17;
 
+const MIN_ENTITY_SIZE: f32 = 30.;
+const MAX_ENTITY_SIZE: f32 = 80.;
+
+const BOSS_SIZE_FACTOR: f32 = 1.5;
+const BOSS_HEALTH_BAR_HEIGHT: f32 = 8.;
+const BOSS_HEALTH_BAR_COLOR: &str = "3fb950";
+const BOSS_HEALTH_BAR_BG_COLOR: &str = "30363d";
+
 type Area = Vec<Vec<usize>>;
 
 pub struct Placer {
@@ -25,9 +37,12 @@ pub struct Placer {
     pub area_block_padding: f32,
     pub area_block_col: i32,
     pub area_block_row: i32,
+    //the layout is generated from the world seed,
+    //so that the same world is always laid out the same way
+    pub rng: StdRng,
 }
 
-pub fn new() -> Placer {
+pub fn new(seed: u64) -> Placer {
     Placer {
         area: vec![vec![0; AREA_BLOCK_COL as usize]; AREA_BLOCK_ROW as usize],
         number_of_placed_entities: 0,
@@ -35,6 +50,7 @@ pub fn new() -> Placer {
         area_block_padding: AREA_BLOCK_PADDING,
         area_block_col: AREA_BLOCK_COL,
         area_block_row: AREA_BLOCK_ROW,
+        rng: StdRng::seed_from_u64(seed),
     }
 }
 
@@ -65,51 +81,47 @@ impl Placer {
             //get possible position
             let mut area_pos = self.random_position();
             let mut position = self.area_pos_to_world_pos(area_pos);
-            let mut size = random_size();
+            let mut size = entity_size(entity.size);
+            if entity.boss.is_some() {
+                size *= BOSS_SIZE_FACTOR;
+            }
             while self.is_occupied(area_pos) || self.mark_occupied_area(area_pos, position, size) {
                 area_pos = self.random_position();
                 position = self.area_pos_to_world_pos(area_pos);
-                size = random_size();
             }
 
             let color = entity.color.replace('#', "");
-            match entity.kind.as_str() {
-                shapes::CIRCLE => {
-                    self.spawn_circle(
-                        entity.name.to_string(),
-                        color,
-                        size,
-                        position,
-                        commands,
-                        meshes,
-                        materials,
-                    );
-                }
+            let id = match entity.kind.as_str() {
+                shapes::CIRCLE => self.spawn_circle(
+                    entity.name.to_string(),
+                    color,
+                    size,
+                    position,
+                    commands,
+                    meshes,
+                    materials,
+                ),
                 shapes::RECTANGLE => {
-                    self.spawn_rectangle(entity.name.to_string(), color, size, position, commands);
-                }
-                shapes::TRIANGLE => {
-                    self.spawn_triangle(
-                        entity.name.to_string(),
-                        color,
-                        size,
-                        position,
-                        commands,
-                        meshes,
-                        materials,
-                    );
-                }
-                shapes::HEXAGON => {
-                    self.spawn_hexagon(
-                        entity.name.to_string(),
-                        color,
-                        size,
-                        position,
-                        commands,
-                        meshes,
-                        materials,
-                    );
+                    self.spawn_rectangle(entity.name.to_string(), color, size, position, commands)
                 }
+                shapes::TRIANGLE => self.spawn_triangle(
+                    entity.name.to_string(),
+                    color,
+                    size,
+                    position,
+                    commands,
+                    meshes,
+                    materials,
+                ),
+                shapes::HEXAGON => self.spawn_hexagon(
+                    entity.name.to_string(),
+                    color,
+                    size,
+                    position,
+                    commands,
+                    meshes,
+                    materials,
+                ),
                 _ => {
                     //we will spawn an hexagon until we have defined
                     //a different shape for the rest of entity's kind
@@ -121,12 +133,70 @@ impl Placer {
                         commands,
                         meshes,
                         materials,
-                    );
+                    )
                 }
             };
+
+            //bosses fight with the weapons and the movement
+            //pattern of their first phase
+            if let Some(b) = &entity.boss {
+                self.spawn_boss(id, b, entity.speed, size, commands);
+                continue;
+            }
+
+            let mut entity_commands = commands.entity(id);
+            insert_gun(&mut entity_commands, entity.weapon.as_str());
+            insert_movement_pattern(
+                &mut entity_commands,
+                entity.movement_pattern.as_str(),
+                entity.speed,
+            );
         }
     }
 
+    fn spawn_boss(
+        &self,
+        id: Entity,
+        b: &shmup::Boss,
+        speed: f32,
+        size: f32,
+        commands: &mut Commands,
+    ) {
+        let width = size * 2.;
+        let bar = |color: &str, z: f32| SpriteBundle {
+            transform: Transform::from_translation(Vec3::new(0., size + 15., z)),
+            sprite: Sprite {
+                color: Color::hex(color).unwrap_or_default(),
+                custom_size: Some(Vec2::new(width, BOSS_HEALTH_BAR_HEIGHT)),
+                ..default()
+            },
+            ..default()
+        };
+
+        let mut entity_commands = commands.entity(id);
+        if let Some(phase) = b.phases.first() {
+            for weapon in &phase.weapons {
+                insert_gun(&mut entity_commands, weapon);
+            }
+            insert_movement_pattern(&mut entity_commands, &phase.movement_pattern, speed);
+        }
+
+        entity_commands
+            .insert(boss::Boss {
+                hp: b.hp,
+                max_hp: b.hp,
+                phase: 0,
+                phases: b.phases.to_owned(),
+                speed,
+            })
+            .with_children(|parent| {
+                parent.spawn(bar(BOSS_HEALTH_BAR_BG_COLOR, 0.1));
+                parent
+                    .spawn(bar(BOSS_HEALTH_BAR_COLOR, 0.2))
+                    .insert(boss::BossHealthBar { width });
+            });
+    }
+
     fn spawn_game_area(&self, commands: &mut Commands) {
         let color: Color = Color::hex("2d333b").unwrap_or_default();
         commands.spawn(SpriteBundle {
@@ -243,7 +313,7 @@ impl Placer {
         commands: &mut Commands,
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<ColorMaterial>>,
-    ) {
+    ) -> Entity {
         let col_sprite = SpriteBundle {
             transform: Transform::from_translation(Vec3::new(0., 0., -0.1)),
             sprite: Sprite {
@@ -254,15 +324,13 @@ impl Placer {
             ..default()
         };
 
-        let entity = &mut commands.spawn(MaterialMesh2dBundle {
-            mesh: meshes.add(shape::Circle::new(size).into()).into(),
-            material: materials.add(ColorMaterial::from(Color::hex(color).unwrap_or_default())),
-            transform: Transform::from_translation(position),
-            ..default()
-        });
-
-        entity
-            //                         .insert(patterns::MoveTowards::default())
+        commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: meshes.add(shape::Circle::new(size).into()).into(),
+                material: materials.add(ColorMaterial::from(Color::hex(color).unwrap_or_default())),
+                transform: Transform::from_translation(position),
+                ..default()
+            })
             .insert(Name::new(name.to_owned()))
             .insert(enemy::Enemy {
                 name,
@@ -272,13 +340,8 @@ impl Placer {
                 parent
                     .spawn(col_sprite)
                     .insert(enemy::EnemyCollider::default());
-            });
-
-        if size >= 60. {
-            entity.insert(guns::MultiDirectionCircleGun::default());
-        } else {
-            entity.insert(guns::FastGun::default());
-        }
+            })
+            .id()
     }
 
     fn spawn_hexagon(
@@ -290,7 +353,7 @@ impl Placer {
         commands: &mut Commands,
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<ColorMaterial>>,
-    ) {
+    ) -> Entity {
         let col_sprite = SpriteBundle {
             transform: Transform::from_translation(Vec3::new(0., 0., -0.1)),
             sprite: Sprite {
@@ -315,15 +378,12 @@ impl Placer {
                     .spawn(col_sprite)
                     .insert(enemy::EnemyCollider::default());
             })
-            //             .insert(patterns::Pattern3 {
-            //                 speed: 150.,
-            //                 ..default()
-            //})
             .insert(Name::new(name.to_owned()))
             .insert(enemy::Enemy {
                 name,
                 ..Default::default()
-            });
+            })
+            .id()
     }
 
     fn spawn_triangle(
@@ -335,7 +395,7 @@ impl Placer {
         commands: &mut Commands,
         meshes: &mut ResMut<Assets<Mesh>>,
         materials: &mut ResMut<Assets<ColorMaterial>>,
-    ) {
+    ) -> Entity {
         let col_sprite = SpriteBundle {
             transform: Transform::from_translation(Vec3::new(0., 0., -0.1)),
             sprite: Sprite {
@@ -360,16 +420,12 @@ impl Placer {
                     .spawn(col_sprite)
                     .insert(enemy::EnemyCollider::default());
             })
-            .insert(guns::SimpleGun::default())
-            //             .insert(patterns::Pattern3 {
-            //                 speed: 150.,
-            //                 ..default()
-            //             })
             .insert(Name::new(name.to_owned()))
             .insert(enemy::Enemy {
                 name,
                 ..Default::default()
-            });
+            })
+            .id()
     }
 
     fn spawn_rectangle(
@@ -379,7 +435,7 @@ impl Placer {
         size: f32,
         position: Vec3,
         commands: &mut Commands,
-    ) {
+    ) -> Entity {
         let padding = 20.;
         let col_sprite = SpriteBundle {
             transform: Transform::from_translation(Vec3::new(0., 0., -0.1)),
@@ -406,22 +462,17 @@ impl Placer {
                     .spawn(col_sprite)
                     .insert(enemy::EnemyCollider::default());
             })
-            .insert(guns::MultiDirectionRectangleGun::default()) // Debug
-            //             .insert(patterns::Pattern3 {
-            //                 speed: 100.,
-            //                 ..default()
-            //             })
             .insert(Name::new(name.to_owned()))
             .insert(enemy::Enemy {
                 name,
                 ..Default::default()
-            });
+            })
+            .id()
     }
 
-    fn random_position(&self) -> Vec2 {
-        let mut r = thread_rng();
-        let x = r.gen_range(0..(self.area_block_col));
-        let y = r.gen_range(0..(self.area_block_row));
+    fn random_position(&mut self) -> Vec2 {
+        let x = self.rng.gen_range(0..(self.area_block_col));
+        let y = self.rng.gen_range(0..(self.area_block_row));
 
         //if the there is something on that position, try again
         Vec2::new(x as f32, y as f32)
@@ -535,13 +586,50 @@ impl Placer {
     }
 }
 
-fn random_size() -> f32 {
-    match rand::thread_rng().gen_range(0..10) {
-        v if (0..1).contains(&v) => 40.,
-        v if (1..2).contains(&v) => 50.,
-        v if (2..3).contains(&v) => 60.,
-        v if (3..4).contains(&v) => 70.,
-        v if (4..5).contains(&v) => 80.,
-        _ => 30.,
-    }
+/// convert the entity size, a value between [0-1],
+/// to a size in the world
+fn entity_size(size: f32) -> f32 {
+    MIN_ENTITY_SIZE + size.clamp(0., 1.) * (MAX_ENTITY_SIZE - MIN_ENTITY_SIZE)
+}
+
+pub fn insert_gun(entity: &mut EntityCommands, weapon: &str) {
+    match weapon {
+        weapons::SIMPLE_GUN => entity.insert(guns::SimpleGun::default()),
+        weapons::FAST_GUN => entity.insert(guns::FastGun::default()),
+        weapons::MULTI_DIRECTION_RECTANGLE_GUN => {
+            entity.insert(guns::MultiDirectionRectangleGun::default())
+        }
+        weapons::MULTI_DIRECTION_CIRCLE_GUN => {
+            entity.insert(guns::MultiDirectionCircleGun::default())
+        }
+        _ => entity,
+    };
+}
+
+/// the speed is a value between [0-1]
+pub fn insert_movement_pattern(entity: &mut EntityCommands, pattern: &str, speed: f32) {
+    match pattern {
+        movement_patterns::WANDER => entity.insert(patterns::Pattern3 {
+            speed: 50. + speed * 100.,
+            ..default()
+        }),
+        movement_patterns::MOVE_TOWARDS => entity.insert(patterns::MoveTowards::default()),
+        _ => entity,
+    };
+}
+
+pub fn remove_guns(entity: &mut EntityCommands) {
+    entity
+        .remove::<guns::SimpleGun>()
+        .remove::<guns::FastGun>()
+        .remove::<guns::MultiDirectionRectangleGun>()
+        .remove::<guns::MultiDirectionCircleGun>();
+}
+
+pub fn remove_movement_patterns(entity: &mut EntityCommands) {
+    entity
+        .remove::<patterns::Pattern1>()
+        .remove::<patterns::Pattern2>()
+        .remove::<patterns::Pattern3>()
+        .remove::<patterns::MoveTowards>();
 }
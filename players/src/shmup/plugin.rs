@@ -1,12 +1,15 @@
 use super::components::camera;
 use super::debug;
 use super::placer;
+use super::systems::boss as boss_systems;
 use super::systems::camera as camera_system;
 use super::systems::enemy_bullets as enemy_bullets_systems;
 use super::systems::guns as guns_systems;
 use super::systems::movements;
 use super::systems::player as player_systems;
 use super::systems::player_bullet;
+use super::systems::reload;
+use super::systems::session;
 use super::WorldData;
 use bevy::{prelude::*, render::camera::ScalingMode};
 
@@ -39,10 +42,30 @@ impl Plugin for ShmupPlugin {
             .add_system(guns_systems::fast_gun)
             .add_system(guns_systems::multidirection_circle_gun)
             .add_system(guns_systems::multidirection_rectangle_gun)
-            .add_system(camera_system::follow_player);
+            .add_system(boss_systems::player_bullets_collisions)
+            .add_system(boss_systems::phase_transitions)
+            .add_system(boss_systems::health_bars)
+            .add_system(camera_system::follow_player)
+            .add_system(reload::reload_world)
+            .add_system(respawn.after(reload::reload_world))
+            .add_system_to_stage(CoreStage::Last, session::write_result);
     }
 }
 
+//spawn the world again when its data is reloaded
+fn respawn(
+    commands: Commands,
+    meshes: ResMut<Assets<Mesh>>,
+    world_data: Res<WorldData>,
+    windows: Res<Windows>,
+    materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !world_data.is_changed() || world_data.is_added() {
+        return;
+    }
+    setup(commands, meshes, world_data, windows, materials);
+}
+
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -64,7 +87,7 @@ fn setup(
         })
         .insert(camera::MainCamera);
 
-    let mut placer = placer::new();
+    let mut placer = placer::new(world_data.0.seed);
     placer.spawn_entities(
         windows,
         world_data,
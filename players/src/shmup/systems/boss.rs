@@ -0,0 +1,151 @@
+use super::super::components::boss;
+use super::super::components::enemy;
+use super::super::components::guns;
+use super::super::components::patterns;
+use super::super::components::player_bullet;
+use super::super::config;
+use super::super::placer;
+use super::super::PlaySession;
+use super::session;
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use std::f32::consts::PI;
+
+const MINION_SIZE: f32 = 25.;
+const MINION_COLOR: &str = "f85149";
+//distance from the boss at which the minions are spawned
+const WAVE_RADIUS: f32 = 120.;
+
+#[allow(clippy::type_complexity)]
+pub fn player_bullets_collisions(
+    player_bullets: Query<
+        (&Parent, &GlobalTransform, &Sprite),
+        With<player_bullet::BulletCollider>,
+    >,
+    bullets: Query<&player_bullet::Bullet>,
+    colliders: Query<
+        (&Parent, &GlobalTransform, &Sprite),
+        (
+            With<enemy::EnemyCollider>,
+            Without<player_bullet::BulletCollider>,
+        ),
+    >,
+    mut bosses: Query<&mut boss::Boss>,
+) {
+    for (collider_parent, collider_transform, collider_sprite) in colliders.iter() {
+        let mut boss = match bosses.get_mut(collider_parent.get()) {
+            Ok(boss) => boss,
+            Err(_) => continue,
+        };
+
+        for (player_bullet, player_bullet_transform, player_bullet_sprite) in player_bullets.iter()
+        {
+            //the bullet itself is despawned by the player systems
+            if collide(
+                player_bullet_transform.translation(),
+                player_bullet_sprite.custom_size.unwrap_or_default(),
+                collider_transform.translation(),
+                collider_sprite.custom_size.unwrap_or_default(),
+            )
+            .is_some()
+            {
+                let damage = match bullets.get(player_bullet.get()) {
+                    Ok(b) => b.damage,
+                    Err(_) => 1.,
+                };
+                boss.hp = (boss.hp - damage).max(0.);
+            }
+        }
+    }
+}
+
+/// move bosses to their next phase, when their hp drops
+/// below the phase threshold, and despawn the defeated ones
+pub fn phase_transitions(
+    mut commands: Commands,
+    mut play_session: ResMut<PlaySession>,
+    mut bosses: Query<(Entity, &mut boss::Boss, &Transform)>,
+) {
+    for (entity, mut boss, transform) in bosses.iter_mut() {
+        if boss.hp <= 0. {
+            commands.entity(entity).despawn_recursive();
+            play_session.session.score += session::BOSS_SCORE;
+            continue;
+        }
+
+        let next = boss.phase + 1;
+        let phase = match boss.phases.get(next) {
+            Some(phase) => phase.to_owned(),
+            None => continue,
+        };
+        if boss.hp / boss.max_hp > phase.hp_threshold {
+            continue;
+        }
+
+        boss.phase = next;
+        let mut entity_commands = commands.entity(entity);
+        placer::remove_guns(&mut entity_commands);
+        placer::remove_movement_patterns(&mut entity_commands);
+        for weapon in &phase.weapons {
+            placer::insert_gun(&mut entity_commands, weapon);
+        }
+        placer::insert_movement_pattern(&mut entity_commands, &phase.movement_pattern, boss.speed);
+
+        for i in 0..phase.spawn_wave {
+            let angle = 2. * PI * i as f32 / phase.spawn_wave as f32;
+            let position = transform.translation
+                + Vec3::new(angle.cos() * WAVE_RADIUS, angle.sin() * WAVE_RADIUS, 0.);
+            spawn_minion(&mut commands, position);
+        }
+    }
+}
+
+/// scale the health bars to the hp left of their boss
+pub fn health_bars(
+    bosses: Query<&boss::Boss>,
+    mut bars: Query<(&Parent, &mut Transform, &boss::BossHealthBar)>,
+) {
+    for (parent, mut transform, bar) in bars.iter_mut() {
+        if let Ok(boss) = bosses.get(parent.get()) {
+            let ratio = (boss.hp / boss.max_hp).clamp(0., 1.);
+            transform.scale.x = ratio;
+            //keep the bar aligned on its left side
+            transform.translation.x = -bar.width * (1. - ratio) / 2.;
+        }
+    }
+}
+
+fn spawn_minion(commands: &mut Commands, position: Vec3) {
+    let col_sprite = SpriteBundle {
+        transform: Transform::from_translation(Vec3::new(0., 0., -0.1)),
+        sprite: Sprite {
+            color: Color::hex(config::get_col_color()).unwrap_or_default(),
+            custom_size: Some(Vec2::new(MINION_SIZE, MINION_SIZE)),
+            ..default()
+        },
+        ..default()
+    };
+
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: Color::hex(MINION_COLOR).unwrap_or_default(),
+                custom_size: Some(Vec2::new(MINION_SIZE, MINION_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(position),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(col_sprite)
+                .insert(enemy::EnemyCollider::default());
+        })
+        .insert(Name::new("minion"))
+        .insert(enemy::Enemy {
+            name: "minion".to_string(),
+            ..Default::default()
+        })
+        .insert(boss::Minion::default())
+        .insert(guns::SimpleGun::default())
+        .insert(patterns::MoveTowards::default());
+}
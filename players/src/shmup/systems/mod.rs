@@ -1,6 +1,9 @@
+pub mod boss;
 pub mod camera;
 pub mod enemy_bullets;
 pub mod guns;
 pub mod movements;
 pub mod player;
 pub mod player_bullet;
+pub mod reload;
+pub mod session;
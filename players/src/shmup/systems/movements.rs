@@ -2,6 +2,7 @@ use std::time::Duration;
 
 use super::super::components::patterns;
 use super::super::components::player;
+use super::super::WorldRng;
 use bevy::prelude::*;
 
 pub fn movement_pattern_1(
@@ -25,12 +26,13 @@ pub fn movement_pattern_2(
 pub fn movement_pattern_3(
     mut query: Query<(&mut patterns::Pattern3, &mut Transform)>,
     time: Res<Time>,
+    mut rng: ResMut<WorldRng>,
 ) {
     for (mut p, mut t) in query.iter_mut() {
         p.timer.tick(Duration::from_secs_f32(time.delta_seconds()));
 
         if p.timer.finished() {
-            patterns::pattern3_random_dir(&mut p);
+            patterns::pattern3_random_dir(&mut p, &mut rng.0);
             //randomly determine the direction
             p.timer.reset();
         }
@@ -40,10 +42,10 @@ pub fn movement_pattern_3(
     }
 }
 
-pub fn pattern_3_init(mut query: Query<&mut patterns::Pattern3>) {
+pub fn pattern_3_init(mut query: Query<&mut patterns::Pattern3>, mut rng: ResMut<WorldRng>) {
     for mut p in query.iter_mut() {
-        patterns::pattern3_random_dir(&mut p);
-        patterns::pattern3_random_time(&mut p);
+        patterns::pattern3_random_dir(&mut p, &mut rng.0);
+        patterns::pattern3_random_time(&mut p, &mut rng.0);
     }
 }
 
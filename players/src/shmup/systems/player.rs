@@ -4,6 +4,8 @@ use super::super::components::enemy_bullets;
 use super::super::components::player;
 use super::super::components::player_bullet;
 use super::super::config;
+use super::super::PlaySession;
+use super::session;
 use bevy::prelude::*;
 use bevy::sprite::collide_aabb::collide;
 use std::time::Duration;
@@ -197,6 +199,7 @@ pub fn player_bullets_enemy_bullets_collisions(
 #[allow(clippy::type_complexity)]
 pub fn player_bullets_enemies_collisions(
     mut commands: Commands,
+    mut play_session: ResMut<PlaySession>,
     player_bullets: Query<
         (&Parent, &mut GlobalTransform, &mut Sprite),
         With<player_bullet::BulletCollider>,
@@ -225,6 +228,7 @@ pub fn player_bullets_enemies_collisions(
             {
                 //                 commands.entity(enemy.get()).despawn_recursive();
                 commands.entity(player_bullet.get()).despawn_recursive();
+                play_session.session.score += session::HIT_SCORE;
             }
         }
     }
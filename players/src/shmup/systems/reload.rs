@@ -0,0 +1,40 @@
+use super::super::{PlaySession, WatchedFile, WorldData, WorldRng};
+use bevy::prelude::*;
+use core::converters;
+use rand::{rngs::StdRng, SeedableRng};
+
+/// reload the world data when its file changes, the entities of the
+/// previous world are despawned and the new world is spawned by
+/// the `respawn` system
+pub fn reload_world(
+    mut commands: Commands,
+    time: Res<Time>,
+    watched: Option<ResMut<WatchedFile>>,
+    mut world_data: ResMut<WorldData>,
+    mut rng: ResMut<WorldRng>,
+    mut play_session: ResMut<PlaySession>,
+    entities: Query<Entity, (With<Transform>, Without<Parent>)>,
+) {
+    let mut watched = match watched {
+        Some(w) => w,
+        None => return,
+    };
+    if !watched.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let data: converters::shmup::Data = match watched.watcher.reload() {
+        Some(Ok(d)) => d,
+        Some(Err(err)) => {
+            eprintln!("Warning: failed to reload the world: {err}");
+            return;
+        }
+        None => return,
+    };
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    rng.0 = StdRng::seed_from_u64(data.seed);
+    play_session.session.visit(&data.main_scene);
+    world_data.0 = data;
+}
@@ -0,0 +1,29 @@
+use super::super::PlaySession;
+use bevy::{app::AppExit, prelude::*};
+use std::process::exit;
+
+//the points earned for each bullet hitting an enemy
+pub const HIT_SCORE: u64 = 10;
+//the points earned for defeating a boss
+pub const BOSS_SCORE: u64 = 1000;
+
+/// write the session to the result file when the game exits
+pub fn write_result(
+    mut exits: EventReader<AppExit>,
+    time: Res<Time>,
+    mut play_session: ResMut<PlaySession>,
+) {
+    if exits.iter().next().is_none() {
+        return;
+    }
+
+    play_session.session.time_played = time.elapsed_seconds_f64();
+    let path = match &play_session.result {
+        Some(p) => p,
+        None => return,
+    };
+    if let Err(err) = play_session.session.save(path) {
+        eprintln!("Error: {err}");
+        exit(1);
+    }
+}
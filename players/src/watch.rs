@@ -0,0 +1,45 @@
+use core::formats;
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+//the time between two checks of the watched file
+pub const INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watcher tells when the file of the converted data is written again,
+/// so that the players can reload the world
+#[derive(Debug)]
+pub struct Watcher {
+    path: String,
+    modified: Option<SystemTime>,
+}
+
+/// returns a watcher of the file `path`, the data read
+/// from stdin is never reloaded
+pub fn new(path: &str) -> Option<Watcher> {
+    if path == formats::STDIN {
+        return None;
+    }
+
+    Some(Watcher {
+        path: path.to_owned(),
+        modified: modified(path),
+    })
+}
+
+impl Watcher {
+    /// returns the data of the file when it changed since the last call,
+    /// a file that cannot be loaded is loaded again at the next change
+    pub fn reload<T: serde::de::DeserializeOwned>(&mut self) -> Option<Result<T, core::Error>> {
+        let m = modified(&self.path);
+        if m.is_none() || m == self.modified {
+            return None;
+        }
+
+        self.modified = m;
+        Some(formats::load(&self.path))
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,98 @@
+use clap::{Args, Subcommand};
+use core::config;
+use std::process::exit;
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// print the value of a setting, environment variables included
+    Get(GetArgs),
+    /// write a setting to the config file
+    Set(SetArgs),
+    /// print every setting, environment variables included
+    Show,
+}
+
+/// The settings are read from `~/.wake/config.toml`, or from the
+/// `config.toml` file of the `WAKE_HOME` folder when it is set.
+/// From the lowest to the highest precedence, a setting comes from:
+/// its default value, the config file, the `WAKE_<KEY>` environment
+/// variable, as `WAKE_CLONE_DEPTH`, and the command flags
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Args, Debug)]
+pub struct GetArgs {
+    /// the setting key
+    #[clap(value_name = "KEY", index = 1)]
+    key: String,
+}
+
+#[derive(Args, Debug)]
+pub struct SetArgs {
+    /// the setting key
+    #[clap(value_name = "KEY", index = 1)]
+    key: String,
+
+    /// the setting value, lists are separated with commas
+    #[clap(value_name = "VALUE", index = 2)]
+    value: String,
+}
+
+pub fn run(args: &RunArgs, conf: config::Config) {
+    match &args.command {
+        Commands::Get(args) => get(args, &conf),
+        Commands::Set(args) => set(args, &conf),
+        Commands::Show => show(&conf),
+    }
+}
+
+fn get(args: &GetArgs, conf: &config::Config) {
+    match conf.settings.get(&args.key) {
+        Ok(value) => println!("{value}"),
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    }
+}
+
+fn set(args: &SetArgs, conf: &config::Config) {
+    //only the settings of the file are written,
+    //not the ones overridden by the environment
+    let mut settings = match config::load_settings(&conf.config_path) {
+        Ok(s) => s,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+    if let Err(err) = settings.set(&args.key, &args.value) {
+        println!("Error: {err}");
+        exit(1);
+    }
+    if let Err(err) = settings.save(&conf.config_path) {
+        println!("Error: {err}");
+        exit(1);
+    }
+    println!("`{}` set in `{}`", args.key, conf.config_path);
+
+    let var = config::env_var_name(&args.key);
+    if std::env::var(&var).is_ok() {
+        println!("Warning: `{var}` is set, it overrides the config file");
+    }
+}
+
+fn show(conf: &config::Config) {
+    println!("# config file: {}", conf.config_path);
+    for key in config::KEYS {
+        let value = conf.settings.get(key).unwrap_or_default();
+        let source = match std::env::var(config::env_var_name(key)) {
+            Ok(_) => format!("  # from {}", config::env_var_name(key)),
+            Err(_) => String::new(),
+        };
+        println!("{key} = {value:?}{source}");
+    }
+}
@@ -0,0 +1,61 @@
+use clap::Args;
+use core::config;
+use core::converters::codealkemi;
+use core::formats;
+use std::fs;
+use std::process::exit;
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// the codealkemi converted file to upgrade to the current schema version
+    #[clap(value_name = "FILE", index = 1)]
+    file: String,
+
+    /// write the upgraded data to that file instead of overwriting FILE
+    #[clap(long, short, value_name = "OUTPUT")]
+    output: Option<String>,
+}
+
+pub fn run(args: &RunArgs, _conf: config::Config) {
+    let content = match fs::read(&args.file) {
+        Ok(c) => c,
+        Err(err) => {
+            println!("Error: failed to read `{}`: {err}", args.file);
+            exit(1);
+        }
+    };
+
+    //the upgraded file is written in the format of the original one
+    let format = match formats::detect(&content) {
+        Ok(f) => f,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+    let data = match formats::decode(&content).and_then(codealkemi::from_value) {
+        Ok(d) => d,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+
+    let output = args.output.clone().unwrap_or(args.file.clone());
+    let encoded_data = match formats::encode(&data, format) {
+        Ok(d) => d,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+    if let Err(err) = fs::write(&output, encoded_data) {
+        println!("Error: failed to write `{output}`: {err}");
+        exit(1);
+    }
+
+    println!(
+        "`{output}` upgraded to the codealkemi schema version {}",
+        codealkemi::VERSION
+    );
+}
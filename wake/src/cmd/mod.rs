@@ -1,18 +1,33 @@
+pub mod config;
+pub mod migrate;
 pub mod play;
+pub mod players;
 pub mod scan;
 pub mod serve;
+pub mod validate;
+pub mod watch;
 
 use clap::{Parser, Subcommand};
-use core::config;
+use core::config::Config;
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// scans a git repository and extracts world data
-    Scan(scan::RunArgs),
+    Scan(Box<scan::RunArgs>),
     /// play generate a playable/simulated world
     Play(play::RunArgs),
+    /// manage the players binary
+    Players(players::RunArgs),
     /// run an http server to serve world data
     Serve(serve::RunArgs),
+    /// upgrade a converted file to the current schema version
+    Migrate(migrate::RunArgs),
+    /// validate an extracted or converted file against its JSON schema
+    Validate(validate::RunArgs),
+    /// read and write the settings of the config file
+    Config(config::RunArgs),
+    /// convert the new commits of a local repository as they are made
+    Watch(watch::RunArgs),
 }
 
 /// `Wake` git repository world generator
@@ -23,7 +38,7 @@ struct Cli {
     command: Commands,
 }
 
-pub fn run(conf: config::Config) {
+pub fn run(conf: Config) {
     let cli = Cli::parse();
 
     match &cli.command {
@@ -33,8 +48,23 @@ pub fn run(conf: config::Config) {
         Commands::Play(args) => {
             play::run(args, conf);
         }
+        Commands::Players(args) => {
+            players::run(args, conf);
+        }
         Commands::Serve(args) => {
             serve::run(args, conf);
         }
+        Commands::Migrate(args) => {
+            migrate::run(args, conf);
+        }
+        Commands::Validate(args) => {
+            validate::run(args, conf);
+        }
+        Commands::Config(args) => {
+            config::run(args, conf);
+        }
+        Commands::Watch(args) => {
+            watch::run(args, conf);
+        }
     }
 }
@@ -1,74 +1,267 @@
 use clap::Args;
+use core::cache;
 use core::config;
 use core::converters;
-use core::extractor;
-use core::repo;
-use spinners::{Spinner, Spinners};
+use core::converters::external;
+use core::exec::{self, session};
+use core::formats;
+use core::pipeline::{self, Sink};
+use core::Error;
+use std::path::Path;
 use std::process::exit;
 
+use crate::progress;
+
 #[derive(Args, Debug)]
 pub struct RunArgs {
     /// the type of player
-    /// can be one of: [shmup, ...]
-    #[clap(value_name = "PLAYER", index = 1)]
+    /// can be one of: [shmup, dungeon]
+    #[clap(value_name = "PLAYER", index = 1, required_unless_present = "list")]
     player: Option<String>,
 
     /// the path to the repository we want to play
     #[clap(value_name = "REPOSITORY", index = 2)]
     repository: Option<String>,
+
+    /// the converter used to generate the world, the converter of
+    /// the player by default, or a registered external converter
+    #[clap(long, value_name = "CONVERTER")]
+    converter: Option<String>,
+
+    /// play the converted data of FILE, without cloning and converting
+    /// the repository again, `-` reads the data from stdin
+    #[clap(long, value_name = "FILE", conflicts_with_all = ["repository", "cached"])]
+    file: Option<String>,
+
+    /// reuse the last conversion of the repository
+    /// when its commit has not changed
+    #[clap(long)]
+    cached: bool,
+
+    /// list the available players
+    #[clap(long, exclusive = true)]
+    list: bool,
 }
 
 pub fn run(args: &RunArgs, conf: config::Config) {
-    let repo = args.repository.clone().unwrap_or_default();
-    let player = args.player.clone().unwrap_or_default();
+    if args.list {
+        for p in exec::PLAYERS {
+            println!(
+                "{:<10}{} (converter: {})",
+                p.name, p.description, p.converter
+            );
+        }
+        return;
+    }
 
-    let mut spin = Spinner::new(Spinners::Line, "Cloning repository...".to_string());
-    let mut git_repo = match repo::clone_repository(&repo, &conf) {
+    let player = match exec::find_player(&args.player.clone().unwrap_or_default()) {
+        Ok(p) => p,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+    let registry = match external::load_registry(&conf.converters_path) {
         Ok(r) => r,
         Err(err) => {
             println!("Error: {err}");
             exit(1);
         }
     };
-    spin.stop_with_message(format!(
+    let converter = match get_converter(&args.converter, player, &registry) {
+        Ok(c) => c,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+
+    // check if we are running the binary for integration tests
+    // because no players binary is installed while running tests
+    let test_mode = std::env::var("WAKE_TEST_MODE").is_ok();
+    let players_bin = match test_mode {
+        true => String::new(),
+        false => match find_players(player.name) {
+            Ok(b) => b,
+            Err(err) => {
+                println!("Error: {err}");
+                exit(1);
+            }
+        },
+    };
+
+    let converted_file_path = match &args.file {
+        Some(file) => {
+            if file != formats::STDIN && !Path::new(file).is_file() {
+                println!("Error: `{file}` file not found");
+                exit(1);
+            }
+            file.to_owned()
+        }
+        None => convert_repository(args, &converter, &conf, &registry),
+    };
+
+    println!("Running the player...");
+
+    // we don't want to open a window while running tests
+    if test_mode {
+        return;
+    }
+
+    let result_file = get_result_file(player.name, &converted_file_path, &conf);
+    let session = match exec::run_player(
+        &players_bin,
+        player.name,
+        &converted_file_path,
+        &result_file,
+        false,
+    ) {
+        Ok(s) => s,
+        Err(err) => {
+            println!("Error: failed to run the player: {err}");
+            exit(err.player_code());
+        }
+    };
+    report_session(&session, &result_file);
+}
+
+/// print the result of the player `session`
+pub fn report_session(session: &session::Session, result_file: &str) {
+    println!(
+        "Score: {}, time played: {:.0}s, scenes visited: {}",
+        session.score,
+        session.time_played,
+        session.scenes_visited.len()
+    );
+    println!("The session result was written to `{result_file}`");
+}
+
+/// returns the file the player writes the result of the session to,
+/// next to the converted file it plays
+pub fn get_result_file(player: &str, converted_file_path: &str, conf: &config::Config) -> String {
+    let dir = match Path::new(converted_file_path).parent() {
+        Some(d) if converted_file_path != formats::STDIN => d.to_string_lossy().into_owned(),
+        _ => conf.wake_path.to_owned(),
+    };
+    let dir = match dir.is_empty() {
+        true => ".".to_string(),
+        false => dir,
+    };
+    format!("{dir}/{player}-{}", config::RESULT_FILE_NAME_SUFFIX)
+}
+
+//clone, extract and convert the repository,
+//and returns the path of the converted file
+fn convert_repository(
+    args: &RunArgs,
+    converter: &str,
+    conf: &config::Config,
+    registry: &external::Registry,
+) -> String {
+    let repo = args.repository.clone().unwrap_or_default();
+    let mut progress = progress::new(false);
+    progress.start("Cloning repository...");
+    let mut scan = match pipeline::from_repository(&repo)
+        .with_converter(converter)
+        .with_registry(registry.to_owned())
+        .with_sink(Sink::File(None))
+        .open(conf)
+    {
+        Ok(s) => s,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+    progress.stop(&format!(
         "`{}` repository cloned successfully",
-        git_repo.folder_path
+        scan.repo.folder_path
     ));
 
-    let mut spin = Spinner::new(Spinners::Line, "Extracting data...".to_string());
-    let (extracted_data, _) = match extractor::extract(&mut git_repo) {
+    if args.cached {
+        if let Some(file) = find_cached(&scan, converter) {
+            println!("Reusing the `{file}` converted from the same commit");
+            return file;
+        }
+    }
+
+    progress.start("Extracting data...");
+    let extracted = match scan.extract() {
         Ok(d) => d,
         Err(err) => {
             println!("Error: failed to extract repository data: {err}");
             exit(1);
         }
     };
-    spin.stop_with_message(format!(
+    progress.stop(&format!(
         "Extraction completed checkout the `{}` generated.",
-        git_repo.extracted_file_path
+        scan.repo.extracted_file_path
     ));
 
-    let mut spin = Spinner::new(Spinners::Line, "Converting data...".to_string());
-    let conv = converters::shmup::new();
-    if let Err(err) = converters::convert(&mut git_repo, extracted_data, &conv) {
-        println!("Error: failed to convert extracted data: {err}");
-        exit(1);
+    progress.start("Converting data...");
+    let output = match scan.convert(extracted) {
+        Ok(o) => o,
+        Err(err) => {
+            println!("Error: failed to convert extracted data: {err}");
+            exit(1);
+        }
     };
-    spin.stop_with_message(format!(
+    progress.stop(&format!(
         "Convertion completed checkout the `{}` generated.",
-        git_repo.converted_file_path
+        output.repo.converted_file_path
     ));
 
-    println!("Running the player...");
+    output.repo.converted_file_path
+}
 
-    // check if we are running the binary for integration tests
-    // because we don't want to open a window while running tests
-    if std::env::var("WAKE_TEST_MODE").is_ok() {
-        return;
-    }
+//returns the file last converted by `converter`, if neither the
+//repository commit nor the options of the scan have changed since
+fn find_cached(scan: &pipeline::Scan, converter: &str) -> Option<String> {
+    let (_, commit) = scan.repo.ref_target().ok()?;
+    let c = match cache::load(&scan.repo.scanner_path) {
+        Ok(c) => c,
+        Err(err) => {
+            println!("Warning: {err}");
+            return None;
+        }
+    };
+    c.find(converter, &commit, &scan.fingerprint())
+        .map(|e| e.file.to_owned())
+}
 
-    if let Err(err) = core::exec::run_player(player, git_repo.converted_file_path) {
-        println!("Error: failed to run the player: {err}");
-        exit(1);
+/// returns the `converter` generating the world of `player`, the builtin
+/// converters of the other players generate data it cannot read
+pub fn get_converter(
+    converter: &Option<String>,
+    player: exec::Player,
+    registry: &external::Registry,
+) -> Result<String, String> {
+    let converter = match converter {
+        Some(c) => c.to_owned(),
+        None => return Ok(player.converter.to_owned()),
     };
+    //the builtin converters are run before the external ones of the same name
+    let builtin = converters::builtin_names().any(|n| n == converter);
+    if !builtin && registry.find(&converter).is_some() {
+        return Ok(converter);
+    }
+    if !converters::exists(&converter, registry) {
+        return Err(format!("unknown converter `{converter}`"));
+    }
+    if converter != player.converter {
+        return Err(format!(
+            "the `{}` player needs the `{}` converter, not `{converter}`",
+            player.name, player.converter
+        ));
+    }
+    Ok(converter)
+}
+
+/// find the players binary and check that it can
+/// read the data converted by this version of wake
+pub fn find_players(player: &str) -> Result<String, Error> {
+    let bin = exec::find_players_bin()?;
+    exec::check_players_version(&bin, env!("CARGO_PKG_VERSION"))?;
+    exec::check_player(&bin, player)?;
+    Ok(bin)
 }
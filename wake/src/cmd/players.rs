@@ -0,0 +1,50 @@
+use clap::{Args, Subcommand};
+use core::config;
+use core::exec;
+use std::process::exit;
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// copy or link the built players binary into `~/.wake/bin`
+    Install(InstallArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Args, Debug)]
+pub struct InstallArgs {
+    /// the players binary to install, found next to
+    /// the wake binary or in the `PATH` by default
+    #[clap(long, value_name = "PATH")]
+    from: Option<String>,
+
+    /// link the binary instead of copying it,
+    /// so that rebuilding the players updates it
+    #[clap(long)]
+    link: bool,
+}
+
+pub fn run(args: &RunArgs, _conf: config::Config) {
+    match &args.command {
+        Commands::Install(args) => install(args),
+    }
+}
+
+fn install(args: &InstallArgs) {
+    let bin = match exec::install_players(args.from.clone(), args.link) {
+        Ok(b) => b,
+        Err(err) => {
+            println!("Error: failed to install the players: {err}");
+            exit(1);
+        }
+    };
+
+    if let Err(err) = exec::check_players_version(&bin, env!("CARGO_PKG_VERSION")) {
+        println!("Warning: {err}");
+    }
+    println!("`{bin}` installed successfully");
+}
@@ -1,10 +1,31 @@
 use clap::Args;
 use core::config;
 use core::converters;
+use core::converters::external;
+use core::converters::rules;
 use core::extractor;
-use core::repo;
-use spinners::{Spinner, Spinners};
+use core::filters;
+use core::formats;
+use core::hash;
+use core::pipeline::{self, Sink};
+use core::Error;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Read};
 use std::process::exit;
+use std::time::Instant;
+
+use crate::progress;
+
+//the exit codes of the scan, telling the failing step apart,
+//2 is used by clap for invalid arguments
+pub const EXIT_INVALID: i32 = 1;
+pub const EXIT_CLONE: i32 = 3;
+pub const EXIT_EXTRACT: i32 = 4;
+pub const EXIT_CONVERT: i32 = 5;
+//some repositories of a batch could not be scanned
+pub const EXIT_BATCH: i32 = 6;
 
 #[derive(Args, Debug)]
 pub struct RunArgs {
@@ -12,72 +33,506 @@ pub struct RunArgs {
     /// examples:
     /// scan shmup <repository_url>
     /// scan codealkemi <repository_url>
+    /// scan graph <repository_url>
+    /// scan codecity <repository_url>
+    /// scan dungeon <repository_url>
+    /// scan midi <repository_url>
+    /// scan <registered_converter> <repository_url>
     #[clap(value_name = "CONVERTER", index = 1)]
     converter: Option<String>,
 
-    /// the path to the repository we want to scan
+    /// the url of the repository we want to scan,
+    /// or the path of a local repository or of a git bundle file
     #[clap(value_name = "REPOSITORY", index = 2)]
     repository: Option<String>,
+
+    /// the branch, tag or commit to scan instead of the default branch
+    #[clap(long = "ref", value_name = "REF")]
+    ref_name: Option<String>,
+
+    /// scan every repository listed in FILE, one url per line,
+    /// `-` reads the list from stdin. Empty lines and lines
+    /// starting with `#` are ignored
+    #[clap(long, value_name = "FILE", conflicts_with = "repository")]
+    from_file: Option<String>,
+
+    /// the number of repositories scanned at the same time with `--from-file`,
+    /// the `workers` setting by default, 0 for the number of cpus
+    #[clap(long, value_name = "N", requires = "from_file")]
+    workers: Option<usize>,
+
+    /// a yaml file of mapping rules applied on top of the converter defaults
+    #[clap(long, value_name = "RULES_FILE")]
+    rules: Option<String>,
+
+    /// build sub entities from the functions, classes, impls... declared in files
+    #[clap(long)]
+    sub_entities: bool,
+
+    /// the format of the extracted and converted files
+    /// can be one of: [json, json.gz, msgpack, cbor]
+    #[clap(long, value_name = "FORMAT", default_value = formats::JSON)]
+    format: String,
+
+    /// only keep the files whose path matches the glob, can be repeated
+    /// example: --include 'src/**/*.rs'
+    #[clap(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// drop the files and directories whose path matches the glob, can be repeated
+    /// example: --exclude 'tests/**' --exclude docs
+    #[clap(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// drop the directories deeper than DEPTH, 0 only keeps the root directory
+    #[clap(long, value_name = "DEPTH")]
+    max_depth: Option<u32>,
+
+    /// sample the files of each directory down to MAX files
+    #[clap(long, value_name = "MAX")]
+    max_entities_per_scene: Option<usize>,
+
+    /// an external converter executable, it receives the extracted data
+    /// on stdin and writes the converted data on stdout
+    /// examples:
+    /// scan --converter-cmd ./my-converter <repository_url>
+    /// scan my-world <repository_url> --converter-cmd ./my-converter --register
+    #[clap(long, value_name = "CMD")]
+    converter_cmd: Option<String>,
+
+    /// the JSON schema the external converter output must follow
+    #[clap(long, value_name = "SCHEMA_FILE", requires = "converter_cmd")]
+    converter_schema: Option<String>,
+
+    /// the time after which the external converter is killed, in seconds
+    #[clap(long, value_name = "SECONDS", requires = "converter_cmd")]
+    converter_timeout: Option<u64>,
+
+    /// register the external converter, so that it can be used by name
+    /// by the `scan`, `play` and `serve` commands
+    #[clap(long, requires = "converter_cmd")]
+    register: bool,
+
+    /// write the extracted and converted files to DIR,
+    /// instead of the scanner folder of the repository
+    #[clap(long, value_name = "DIR")]
+    output_dir: Option<String>,
+
+    /// print a json summary of the scan instead of the progress,
+    /// errors are also printed as json. With `--from-file`, the
+    /// summaries of every repository are printed as a json list
+    #[clap(long)]
+    json: bool,
+
+    /// only print the errors
+    #[clap(long)]
+    quiet: bool,
 }
 
-const SHUMP_CONVERTER: &str = "shmup";
-const CODEALKEMI_CONVERTER: &str = "codealkemi";
+/// Summary describes a successful scan, printed with `--json`
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    pub repository: String,
+    pub converter: String,
+    pub format: String,
+    //the ref scanned and its commit oid
+    pub ref_name: String,
+    pub commit: String,
+    pub folder_path: String,
+    pub extracted_file: String,
+    pub converted_file: String,
+    pub counts: Counts,
+    pub timings: Timings,
+}
+
+/// BatchResult is the result of the scan of a repository of a batch
+#[derive(Serialize, Debug)]
+pub struct BatchResult {
+    pub repository: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<Summary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub exit_code: i32,
+}
+
+/// Counts of the data scanned, after the filters are applied
+#[derive(Serialize, Debug, Default)]
+pub struct Counts {
+    pub commits: usize,
+    pub trees: usize,
+    pub blobs: usize,
+    //the files whose code was analysed
+    pub files: usize,
+}
+
+/// Timings of the scan steps, in milliseconds
+#[derive(Serialize, Debug, Default)]
+pub struct Timings {
+    pub clone_ms: u64,
+    pub extract_ms: u64,
+    pub convert_ms: u64,
+    pub total_ms: u64,
+}
+
+/// ScanError is the failure of a step of the scan
+#[derive(Debug)]
+pub struct ScanError {
+    pub code: i32,
+    pub message: String,
+}
+
+//the options of the scan, shared by every repository scanned
+struct Plan {
+    converter: String,
+    registry: external::Registry,
+    format: formats::Format,
+    filters: filters::Options,
+    sub_entities: bool,
+}
 
 pub fn run(args: &RunArgs, conf: config::Config) {
-    let converter = args.converter.clone().unwrap_or(SHUMP_CONVERTER.to_owned());
-    let repo = args.repository.clone().unwrap_or_default();
-    let mut spin = Spinner::new(Spinners::Line, "Cloning repository...".to_string());
-    let mut git_repo = match repo::clone_repository(&repo, &conf) {
-        Ok(r) => r,
+    let batch = args.from_file.is_some();
+    let mut progress = progress::new(args.quiet || args.json || batch);
+    let (plan, repo) = match prepare(args, &conf, &mut progress) {
+        Ok(p) => p,
+        Err(err) => fail(args, EXIT_INVALID, &err.to_string()),
+    };
+
+    if let Some(file) = &args.from_file {
+        run_batch(args, file, &plan, &conf);
+        return;
+    }
+
+    let summary = match scan_repository(args, &repo, &plan, &conf, &mut progress) {
+        Ok(s) => s,
         Err(err) => {
-            println!("Error: {err}");
-            exit(1);
+            progress.fail();
+            fail(args, err.code, &err.message)
+        }
+    };
+    if !args.json {
+        return;
+    }
+    match serde_json::to_string_pretty(&summary) {
+        Ok(s) => println!("{s}"),
+        Err(err) => fail(args, EXIT_INVALID, &err.to_string()),
+    }
+}
+
+//check the options of the scan, register the external converter,
+//and returns them with the repository to scan
+fn prepare(
+    args: &RunArgs,
+    conf: &config::Config,
+    progress: &mut progress::Progress,
+) -> Result<(Plan, String), Error> {
+    let (converter, repo) = match (&args.converter_cmd, &args.repository) {
+        //the repository is the only positional argument
+        //when an external converter is not named
+        (Some(cmd), None) if args.from_file.is_none() => (
+            external::new_spec(cmd).name,
+            args.converter.clone().unwrap_or_default(),
+        ),
+        (cmd, _) => {
+            let converter = match (&args.converter, cmd) {
+                (Some(c), _) => c.to_owned(),
+                //an external converter is named after its command when no name is given
+                (None, Some(cmd)) => external::new_spec(cmd).name,
+                (None, None) => conf.settings.default_converter.to_owned(),
+            };
+            (converter, args.repository.clone().unwrap_or_default())
+        }
+    };
+    let mut registry = external::load_registry(&conf.converters_path)?;
+    if let Some(cmd) = &args.converter_cmd {
+        //the builtin converter would be run instead of the command
+        if converters::builtin_names().any(|n| n == converter) {
+            return Err(Error::parse(format!(
+                "`{converter}` is the name of a builtin converter, pick another name \
+                 with `wake scan <CONVERTER> <REPOSITORY> --converter-cmd {cmd}`"
+            )));
+        }
+        let mut spec = external::new_spec(cmd);
+        spec.name = converter.to_owned();
+        spec.schema = args.converter_schema.to_owned();
+        if let Some(timeout) = args.converter_timeout {
+            spec.timeout = timeout;
         }
+        external::new(spec.to_owned())?;
+        registry.register(spec)?;
+
+        if args.register {
+            registry.save(&conf.converters_path)?;
+            progress.println(&format!(
+                "`{converter}` converter registered in `{}`",
+                conf.converters_path
+            ));
+        }
+    }
+    if !converters::exists(&converter, &registry) {
+        return Err(Error::convert(format!("unknown converter `{converter}`")));
+    }
+    //the rules are loaded again for each repository
+    if let Some(path) = &args.rules {
+        rules::load(path)?;
+    }
+    let format = formats::Format::from_name(&args.format)?;
+    //the globs given replace the ones of the settings
+    let filter_options = filters::Options {
+        include: match args.include.is_empty() {
+            true => conf.settings.include.to_owned(),
+            false => args.include.to_owned(),
+        },
+        exclude: match args.exclude.is_empty() {
+            true => conf.settings.exclude.to_owned(),
+            false => args.exclude.to_owned(),
+        },
+        max_depth: args.max_depth,
+        max_entities_per_scene: args.max_entities_per_scene,
+    };
+    filters::new(&filter_options)?;
+
+    let plan = Plan {
+        converter,
+        registry,
+        format,
+        filters: filter_options,
+        sub_entities: args.sub_entities,
+    };
+    Ok((plan, repo))
+}
+
+//clone, extract and convert the repository `repo`, the scans
+//of the same repository wait for each other
+fn scan_repository(
+    args: &RunArgs,
+    repo: &str,
+    plan: &Plan,
+    conf: &config::Config,
+    progress: &mut progress::Progress,
+) -> Result<Summary, ScanError> {
+    let err = |code, e: Error| ScanError {
+        code,
+        message: e.to_string(),
+    };
+    let started = Instant::now();
+    let rules = match &args.rules {
+        Some(path) => rules::load(path).map_err(|e| err(EXIT_INVALID, e))?,
+        None => rules::Rules::default(),
     };
-    spin.stop_with_message(format!(
+    let options = converters::Options {
+        rules,
+        sub_entities: plan.sub_entities,
+    };
+    let mut pipeline = pipeline::from_repository(repo)
+        .with_converter(&plan.converter)
+        .with_registry(plan.registry.to_owned())
+        .with_format(plan.format)
+        .with_filters(plan.filters.to_owned())
+        .with_options(options)
+        .with_sink(Sink::File(None));
+    if let Some(r) = &args.ref_name {
+        pipeline = pipeline.with_ref(r);
+    }
+
+    let mut timings = Timings::default();
+    let step = Instant::now();
+    progress.start("Cloning repository...");
+    let mut scan = pipeline.open(conf).map_err(|e| match e {
+        Error::InvalidUrl(_) | Error::Parse { .. } => err(EXIT_INVALID, e),
+        _ => err(EXIT_CLONE, e),
+    })?;
+    if let Some(dir) = &args.output_dir {
+        scan.repo.scanner_path = match args.from_file {
+            //each repository of a batch gets its own folder, the local
+            //repositories and the bundles of the same name are told apart
+            //by the hash of the repository listed
+            Some(_) => format!(
+                "{dir}/{}-{}",
+                scan.repo.folder_name,
+                &hash::new(repo.to_owned())[..8]
+            ),
+            None => dir.to_owned(),
+        };
+    }
+    timings.clone_ms = step.elapsed().as_millis() as u64;
+    progress.stop(&format!(
         "`{}` repository cloned successfully",
-        git_repo.folder_path
+        scan.repo.folder_path
     ));
 
-    let mut spin = Spinner::new(Spinners::Line, "Extracting data...".to_string());
-    let (extracted_data, _) = match extractor::extract(&mut git_repo) {
+    let step = Instant::now();
+    progress.start("Extracting data...");
+    let extracted = match scan.extract() {
         Ok(d) => d,
-        Err(err) => {
-            println!("Error: failed to extract repository data: {err}");
-            exit(1);
+        Err(e) => {
+            return Err(ScanError {
+                code: EXIT_EXTRACT,
+                message: format!("failed to extract repository data: {e}"),
+            })
         }
     };
-    spin.stop_with_message(format!(
+    timings.extract_ms = step.elapsed().as_millis() as u64;
+    progress.stop(&format!(
         "Extraction completed checkout the `{}` generated.",
-        git_repo.extracted_file_path
+        scan.repo.extracted_file_path
     ));
 
-    let mut spin = Spinner::new(Spinners::Line, "Converting data...".to_string());
+    let counts = count(&extracted.0);
+    let (ref_name, commit) = extracted.0.git.ref_target.to_owned();
 
-    match converter.as_str() {
-        SHUMP_CONVERTER => {
-            if let Err(err) =
-                converters::convert(&mut git_repo, extracted_data, &converters::shmup::new())
-            {
-                println!("Error: failed to convert extracted data: {err}");
-                exit(1);
-            }
-        }
-        CODEALKEMI_CONVERTER => {
-            if let Err(err) = converters::convert(
-                &mut git_repo,
-                extracted_data,
-                &converters::codealkemi::new(),
-            ) {
-                println!("Error: failed to convert extracted data: {err}");
-                exit(1);
-            }
+    let step = Instant::now();
+    progress.start("Converting data...");
+    let output = match scan.convert(extracted) {
+        Ok(o) => o,
+        Err(e) => {
+            return Err(ScanError {
+                code: EXIT_CONVERT,
+                message: format!("failed to convert extracted data: {e}"),
+            })
         }
-        _ => {}
     };
-
-    spin.stop_with_message(format!(
+    let git_repo = output.repo;
+    timings.convert_ms = step.elapsed().as_millis() as u64;
+    progress.stop(&format!(
         "Convertion completed checkout the `{}` generated.",
         git_repo.converted_file_path
     ));
+
+    timings.total_ms = started.elapsed().as_millis() as u64;
+    Ok(Summary {
+        repository: repo.to_owned(),
+        converter: plan.converter.to_owned(),
+        format: plan.format.extension().to_owned(),
+        ref_name,
+        commit,
+        folder_path: git_repo.folder_path.to_owned(),
+        extracted_file: git_repo.extracted_file_path.to_owned(),
+        converted_file: git_repo.converted_file_path.to_owned(),
+        counts,
+        timings,
+    })
+}
+
+//scan the repositories listed in `file` with a pool of workers,
+//a failure does not stop the scan of the other repositories
+fn run_batch(args: &RunArgs, file: &str, plan: &Plan, conf: &config::Config) {
+    let repos = match read_repositories(file) {
+        Ok(r) => r,
+        Err(err) => fail(args, EXIT_INVALID, &err.to_string()),
+    };
+    let workers = args.workers.unwrap_or(conf.settings.workers);
+    let pool = match rayon::ThreadPoolBuilder::new().num_threads(workers).build() {
+        Ok(p) => p,
+        Err(err) => fail(args, EXIT_INVALID, &err.to_string()),
+    };
+
+    let results: Vec<BatchResult> = pool.install(|| {
+        repos
+            .par_iter()
+            .map(|repo| {
+                let mut progress = progress::new(true);
+                let result = match scan_repository(args, repo, plan, conf, &mut progress) {
+                    Ok(s) => BatchResult {
+                        repository: repo.to_owned(),
+                        summary: Some(s),
+                        error: None,
+                        exit_code: 0,
+                    },
+                    Err(err) => BatchResult {
+                        repository: repo.to_owned(),
+                        summary: None,
+                        error: Some(err.message),
+                        exit_code: err.code,
+                    },
+                };
+                if !args.quiet && !args.json {
+                    match &result.error {
+                        None => eprintln!("ok      {repo}"),
+                        Some(err) => eprintln!("failed  {repo}: {err}"),
+                    }
+                }
+                result
+            })
+            .collect()
+    });
+
+    let failed: Vec<&BatchResult> = results.iter().filter(|r| r.exit_code != 0).collect();
+    if args.json {
+        match serde_json::to_string_pretty(&results) {
+            Ok(s) => println!("{s}"),
+            Err(err) => fail(args, EXIT_INVALID, &err.to_string()),
+        }
+    } else {
+        println!(
+            "Scanned {} repositories: {} succeeded, {} failed",
+            results.len(),
+            results.len() - failed.len(),
+            failed.len()
+        );
+        for r in &failed {
+            println!(
+                "  {}: {}",
+                r.repository,
+                r.error.to_owned().unwrap_or_default()
+            );
+        }
+    }
+
+    if !failed.is_empty() {
+        exit(EXIT_BATCH);
+    }
+}
+
+//returns the repositories listed in `file`, `-` reads them from stdin
+fn read_repositories(file: &str) -> Result<Vec<String>, String> {
+    let content = match file {
+        "-" => {
+            let mut content = String::new();
+            match io::stdin().read_to_string(&mut content) {
+                Ok(_) => content,
+                Err(err) => return Err(format!("failed to read stdin: {err}")),
+            }
+        }
+        _ => match fs::read_to_string(file) {
+            Ok(c) => c,
+            Err(err) => return Err(format!("failed to read `{file}`: {err}")),
+        },
+    };
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+//returns the counts of the objects of the extracted data
+fn count(data: &extractor::Data) -> Counts {
+    let mut counts = Counts {
+        commits: data.git.history.len(),
+        files: data.code.files_data.len(),
+        ..Default::default()
+    };
+    for obj in data.git.objects.values() {
+        match obj.kind {
+            extractor::git::ObjectKind::Tree => counts.trees += 1,
+            extractor::git::ObjectKind::Blob => counts.blobs += 1,
+            _ => (),
+        }
+    }
+    counts
+}
+
+//print the error on stderr, and on stdout as json with `--json`,
+//then exit with `code`
+fn fail(args: &RunArgs, code: i32, err: &str) -> ! {
+    eprintln!("Error: {err}");
+    if args.json {
+        let out = serde_json::json!({ "error": err, "exit_code": code });
+        println!("{out}");
+    }
+    exit(code)
 }
@@ -5,12 +5,16 @@ use core::server;
 #[derive(Args, Debug)]
 pub struct RunArgs {
     #[clap(short, long)]
-    /// specify the port you want the serve to listen on
+    /// specify the port you want the serve to listen on,
+    /// the `port` setting by default
     port: Option<String>,
 }
 
-pub fn run(args: &RunArgs, _conf: config::Config) {
-    let port = args.port.to_owned().unwrap_or_else(|| "8080".to_string());
+pub fn run(args: &RunArgs, conf: config::Config) {
+    let port = args
+        .port
+        .to_owned()
+        .unwrap_or_else(|| conf.settings.port.to_string());
 
-    server::run(port);
+    server::run(port, conf);
 }
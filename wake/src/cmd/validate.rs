@@ -0,0 +1,62 @@
+use clap::Args;
+use core::config;
+use core::formats;
+use core::schemas;
+use std::process::exit;
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// the extracted or converted file to validate,
+    /// in any of the formats supported by `scan --format`
+    #[clap(value_name = "FILE", index = 1)]
+    file: String,
+
+    /// the schema to validate the file against,
+    /// guessed from the file name by default
+    /// can be one of: [extracted, shmup, codealkemi, graph, codecity, dungeon, midi]
+    #[clap(long, value_name = "SCHEMA")]
+    schema: Option<String>,
+}
+
+pub fn run(args: &RunArgs, _conf: config::Config) {
+    let schema = match &args.schema {
+        Some(s) => s.to_owned(),
+        None => match schemas::name_from_path(&args.file) {
+            Ok(s) => s.to_owned(),
+            Err(err) => {
+                println!("Error: {err}, use --schema to pick one");
+                exit(1);
+            }
+        },
+    };
+
+    let document: serde_json::Value = match formats::load(&args.file) {
+        Ok(d) => d,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+
+    let violations = match schemas::validate(&schema, &document) {
+        Ok(v) => v,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
+
+    if !violations.is_empty() {
+        for violation in &violations {
+            println!("{violation}");
+        }
+        println!(
+            "Error: `{}` does not match the `{schema}` schema, {} violation(s) found",
+            args.file,
+            violations.len()
+        );
+        exit(1);
+    }
+
+    println!("`{}` matches the `{schema}` schema", args.file);
+}
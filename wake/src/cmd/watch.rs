@@ -0,0 +1,200 @@
+use clap::Args;
+use core::config;
+use core::converters::external;
+use core::exec;
+use core::extractor;
+use core::pipeline::{self, Sink};
+use core::repo;
+use std::process::exit;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::play;
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// the path to the local repository we want to watch
+    #[clap(value_name = "PATH", index = 1, default_value = ".")]
+    path: String,
+
+    /// the converter used to generate the world, the converter of the
+    /// player with `--play`, the `default_converter` setting otherwise
+    #[clap(long, value_name = "CONVERTER")]
+    converter: Option<String>,
+
+    /// the time between two checks for new commits, in milliseconds
+    #[clap(long, value_name = "MS", default_value_t = 1000)]
+    interval: u64,
+
+    /// run the player PLAYER, it reloads the world every time
+    /// a new commit is converted. Watching stops with the player
+    #[clap(long, value_name = "PLAYER")]
+    play: Option<String>,
+}
+
+pub fn run(args: &RunArgs, conf: config::Config) {
+    let player = match &args.play {
+        Some(name) => match exec::find_player(name) {
+            Ok(p) => Some(p),
+            Err(err) => {
+                eprintln!("Error: {err}");
+                exit(1);
+            }
+        },
+        None => None,
+    };
+    let registry = match external::load_registry(&conf.converters_path) {
+        Ok(r) => r,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            exit(1);
+        }
+    };
+    let converter = match player {
+        Some(p) => play::get_converter(&args.converter, p, &registry),
+        None => Ok(args
+            .converter
+            .clone()
+            .unwrap_or(conf.settings.default_converter.to_owned())),
+    };
+    let converter = match converter {
+        Ok(c) => c,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            exit(1);
+        }
+    };
+    let target = Target {
+        path: args.path.to_owned(),
+        converter,
+        registry,
+    };
+
+    // check if we are running the binary for integration tests
+    // because no players binary is installed while running tests
+    let test_mode = std::env::var("WAKE_TEST_MODE").is_ok();
+    let players_bin = match player {
+        Some(p) if !test_mode => match play::find_players(p.name) {
+            Ok(b) => b,
+            Err(err) => {
+                eprintln!("Error: {err}");
+                exit(1);
+            }
+        },
+        _ => String::new(),
+    };
+
+    let (git_repo, data) = match target.convert(None, &conf) {
+        Ok(c) => c,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            exit(1);
+        }
+    };
+    eprintln!(
+        "Conversion completed checkout the `{}` generated.",
+        git_repo.converted_file_path
+    );
+    eprintln!("Watching `{}` for new commits...", git_repo.folder_path);
+
+    let interval = Duration::from_millis(args.interval);
+    let player = match player {
+        Some(p) if !test_mode => p,
+        _ => return watch(git_repo, data, interval, &target, &conf),
+    };
+
+    //the commits are converted in the background while the player runs
+    let file = git_repo.converted_file_path.to_owned();
+    let result_file = play::get_result_file(player.name, &file, &conf);
+    thread::spawn(move || watch(git_repo, data, interval, &target, &conf));
+    match exec::run_player(&players_bin, player.name, &file, &result_file, true) {
+        Ok(session) => play::report_session(&session, &result_file),
+        Err(err) => {
+            eprintln!("Error: failed to run the player: {err}");
+            exit(err.player_code());
+        }
+    }
+}
+
+//Target is the repository watched and the converter it is converted with
+struct Target {
+    path: String,
+    converter: String,
+    registry: external::Registry,
+}
+
+impl Target {
+    //scan the repository, the data is updated from the `previous`
+    //data when set. returns the repository and the extracted data
+    fn convert(
+        &self,
+        previous: Option<&extractor::Data>,
+        conf: &config::Config,
+    ) -> Result<(repo::Repo, extractor::Data), String> {
+        let mut scan = pipeline::from_path(&self.path)
+            .with_converter(&self.converter)
+            .with_registry(self.registry.clone())
+            .with_sink(Sink::File(None))
+            .open(conf)
+            .map_err(|err| err.to_string())?;
+        let extracted = match previous {
+            Some(p) => scan.update(p),
+            None => scan.extract(),
+        };
+        let extracted = match extracted {
+            Ok(e) => e,
+            Err(err) => return Err(format!("failed to extract repository data: {err}")),
+        };
+
+        match scan.convert(extracted) {
+            Ok(output) => Ok((output.repo, output.extracted)),
+            Err(err) => Err(format!("failed to convert extracted data: {err}")),
+        }
+    }
+}
+
+//check for new commits every `interval`, and convert them. Only the
+//files changed since the last commit converted are analysed again
+fn watch(
+    mut git_repo: repo::Repo,
+    mut data: extractor::Data,
+    interval: Duration,
+    target: &Target,
+    conf: &config::Config,
+) {
+    let mut seen = data.git.ref_target.1.to_owned();
+    loop {
+        thread::sleep(interval);
+        let (_, commit) = match git_repo.ref_target() {
+            Ok(t) => t,
+            Err(err) => {
+                eprintln!("Warning: failed to read the repository HEAD: {err}");
+                continue;
+            }
+        };
+        //a commit that failed to convert is not converted again
+        if commit == seen {
+            continue;
+        }
+        seen = commit.to_owned();
+
+        eprintln!("Converting the `{}` commit...", short_commit(&commit));
+        let started = Instant::now();
+        match target.convert(Some(&data), conf) {
+            Ok((r, d)) => {
+                git_repo = r;
+                data = d;
+                eprintln!(
+                    "Conversion completed in {}ms checkout the `{}` generated.",
+                    started.elapsed().as_millis(),
+                    git_repo.converted_file_path
+                );
+            }
+            Err(err) => eprintln!("Error: {err}"),
+        }
+    }
+}
+
+fn short_commit(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
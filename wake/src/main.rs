@@ -1,7 +1,15 @@
 mod cmd;
+mod progress;
 use core::config;
+use std::process::exit;
 
 fn main() {
-    let conf = config::Config::new();
+    let conf = match config::load() {
+        Ok(c) => c,
+        Err(err) => {
+            println!("Error: {err}");
+            exit(1);
+        }
+    };
     cmd::run(conf);
 }
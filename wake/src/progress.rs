@@ -0,0 +1,65 @@
+use spinners::{Spinner, Spinners};
+use std::io::{self, IsTerminal};
+
+/// Progress reports the steps of a command on stderr, so that stdout
+/// only holds the command output. A spinner is drawn when stdout and
+/// stderr are terminals, plain lines are printed otherwise
+pub struct Progress {
+    mode: Mode,
+    spinner: Option<Spinner>,
+}
+
+#[derive(PartialEq)]
+enum Mode {
+    Quiet,
+    Lines,
+    Spinner,
+}
+
+/// returns a progress reporting nothing when `quiet` is set
+pub fn new(quiet: bool) -> Progress {
+    let terminal = io::stdout().is_terminal() && io::stderr().is_terminal();
+    let mode = match (quiet, terminal) {
+        (true, _) => Mode::Quiet,
+        (false, true) => Mode::Spinner,
+        (false, false) => Mode::Lines,
+    };
+    Progress {
+        mode,
+        spinner: None,
+    }
+}
+
+impl Progress {
+    /// start a step, described by `message`
+    pub fn start(&mut self, message: &str) {
+        match self.mode {
+            Mode::Quiet => (),
+            Mode::Lines => eprintln!("{message}"),
+            Mode::Spinner => self.spinner = Some(Spinner::new(Spinners::Line, message.to_owned())),
+        }
+    }
+
+    /// end the current step, with `message`
+    pub fn stop(&mut self, message: &str) {
+        match self.spinner.take() {
+            Some(mut s) => s.stop_with_message(message.to_owned()),
+            None if self.mode != Mode::Quiet => eprintln!("{message}"),
+            None => (),
+        }
+    }
+
+    /// print `message` between the steps
+    pub fn println(&mut self, message: &str) {
+        if self.mode != Mode::Quiet {
+            eprintln!("{message}");
+        }
+    }
+
+    /// end the current step without a message, before an error is printed
+    pub fn fail(&mut self) {
+        if let Some(mut s) = self.spinner.take() {
+            s.stop_with_newline();
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use assert_cmd::prelude::*;
+use core::utils::test;
+use core::utils::test::TMP_DIR;
+use std::fs;
+// Add methods on commands
+use predicates::prelude::*; // Used for writing assertions
+use std::process::Command; // Run programs
+
+#[test]
+fn get_set_and_show_settings() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("config").arg("get").arg("clone_depth");
+    cmd.assert().success().stdout("1\n");
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("config")
+        .arg("set")
+        .arg("exclude")
+        .arg("tests/**,docs");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("`exclude` set in"));
+    let content = fs::read_to_string(format!("{TMP_DIR}/.wake/config.toml"))?;
+    assert!(
+        content.contains("exclude = [\"tests/**\", \"docs\"]"),
+        "{content}"
+    );
+
+    //the environment overrides the config file
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.env("WAKE_EXCLUDE", "vendor").arg("config").arg("show");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "exclude = \"vendor\"  # from WAKE_EXCLUDE",
+        ))
+        .stdout(predicate::str::contains("port = \"8080\""));
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("config").arg("set").arg("port").arg("http");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("invalid `port` value `http`"));
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("config").arg("get").arg("colour");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("unknown setting `colour`"));
+
+    //the config file is read from the `WAKE_HOME` folder
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.env("WAKE_HOME", format!("{TMP_DIR}/home"))
+        .arg("config")
+        .arg("set")
+        .arg("fetch")
+        .arg("always");
+    cmd.assert().success();
+    assert!(fs::metadata(format!("{TMP_DIR}/home/config.toml"))?.is_file());
+
+    //an invalid config file is reported by every command
+    fs::write(format!("{TMP_DIR}/.wake/config.toml"), "colour = true\n")?;
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("config").arg("show");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("invalid config file"));
+
+    test::teardown();
+    Ok(())
+}
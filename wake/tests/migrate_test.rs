@@ -0,0 +1,46 @@
+use assert_cmd::prelude::*;
+use core::utils::test;
+use core::utils::test::TMP_DIR;
+use std::fs;
+// Add methods on commands
+use predicates::prelude::*; // Used for writing assertions
+use std::process::Command; // Run programs
+
+const LEGACY_DATA: &str = r#"{
+    "version": "v0",
+    "name": "codealkemi",
+    "repo_name": "ckp",
+    "url": "",
+    "commit": "abc",
+    "main_scene": "root",
+    "scenes": {"root": {"entities": {}, "sub_scenes": []}}
+}"#;
+
+#[test]
+fn upgrade_codealkemi_file() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    let file = format!("{TMP_DIR}/codealkemi-converted.json");
+    fs::write(&file, LEGACY_DATA)?;
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("migrate").arg(&file);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("upgraded"));
+
+    let content = fs::read_to_string(&file)?;
+    assert!(content.contains(r#""version":"1.0.0""#));
+
+    //files generated by a newer major version are rejected
+    fs::write(&file, LEGACY_DATA.replace("v0", "2.0.0"))?;
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("migrate").arg(&file);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("is not supported"));
+
+    test::teardown();
+    Ok(())
+}
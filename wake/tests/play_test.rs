@@ -21,3 +21,67 @@ fn should_work_correctly() -> Result<(), Box<dyn std::error::Error>> {
     test::teardown();
     Ok(())
 }
+
+#[test]
+fn should_play_a_converted_file() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    let file = format!(
+        "{}/../waking-git-core/schemas/golden/shmup-converted.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("play").arg("shmup").arg("--file").arg(&file);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Cloning").not())
+        .stdout(predicate::str::contains("Running the player..."));
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("play")
+        .arg("shmup")
+        .arg("--file")
+        .arg("missing.json");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("`missing.json` file not found"));
+
+    test::teardown();
+    Ok(())
+}
+
+#[test]
+fn should_list_and_check_players() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("play").arg("--list");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("shmup"))
+        .stdout(predicate::str::contains("dungeon"));
+
+    let url = "https://github.com/elhmn/ckp";
+
+    //the player is checked before the repository is cloned
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("play").arg("racing").arg(url);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("unknown player `racing`"))
+        .stdout(predicate::str::contains("Cloning").not());
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("play")
+        .arg("dungeon")
+        .arg(url)
+        .arg("--converter")
+        .arg("shmup");
+    cmd.assert().failure().stdout(predicate::str::contains(
+        "the `dungeon` player needs the `dungeon` converter",
+    ));
+
+    test::teardown();
+    Ok(())
+}
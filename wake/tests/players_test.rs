@@ -0,0 +1,44 @@
+//the fake players binary is a shell script
+#![cfg(unix)]
+
+use assert_cmd::prelude::*;
+use core::utils::test;
+use core::utils::test::TMP_DIR;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+// Add methods on commands
+use predicates::prelude::*; // Used for writing assertions
+use std::process::Command; // Run programs
+
+#[test]
+fn install_players() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    //a fake players binary, with the version of wake
+    let build = format!("{TMP_DIR}/build");
+    fs::create_dir_all(&build)?;
+    let source = format!("{build}/players");
+    let version = env!("CARGO_PKG_VERSION");
+    fs::write(&source, format!("#!/bin/sh\necho \"players {version}\"\n"))?;
+    fs::set_permissions(&source, fs::Permissions::from_mode(0o755))?;
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("players").arg("install").arg("--from").arg(&source);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("installed successfully"))
+        .stdout(predicate::str::contains("Warning").not());
+    assert!(fs::metadata(format!("{TMP_DIR}/.wake/bin/players"))?.is_file());
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("players")
+        .arg("install")
+        .arg("--from")
+        .arg(format!("{build}/missing"));
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("failed to install the players"));
+
+    test::teardown();
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use assert_cmd::{assert, prelude::*};
+use assert_cmd::prelude::*;
 use core::utils::test;
 use core::utils::test::TMP_DIR;
 use std::path::PathBuf;
@@ -13,32 +13,39 @@ fn fail_to_parse_wrong_url() -> Result<(), Box<dyn std::error::Error>> {
     struct Test<'a> {
         url: &'a str,
         exp: &'a str,
+        code: i32,
     }
 
     let tests = [
         Test {
             url: "./test/file/doesnt/exist",
             exp: "Failed to parse",
+            code: 1,
         },
         Test {
             url: "http://github.com",
             exp: "not a https url",
+            code: 1,
         },
         Test {
             url: "file://github.com",
             exp: "not a https url",
+            code: 1,
         },
         Test {
             url: "https://githubcom/elhmn/ckp",
             exp: "failed to resolve address for githubcom",
+            code: 3,
         },
         Test {
             url: "http://",
             exp: "Failed to parse",
+            code: 1,
         },
         Test {
             url: "",
             exp: "Failed to parse",
+            code: 1,
         },
     ];
 
@@ -47,7 +54,8 @@ fn fail_to_parse_wrong_url() -> Result<(), Box<dyn std::error::Error>> {
         cmd.arg("scan").arg("shmup").arg(t.url);
         cmd.assert()
             .failure()
-            .stdout(predicate::str::contains(t.exp));
+            .code(t.code)
+            .stderr(predicate::str::contains(t.exp));
     }
 
     test::teardown();
@@ -162,3 +170,301 @@ fn doesnt_fetch_repository_if_already_exists() -> Result<(), Box<dyn std::error:
     test::teardown();
     Ok(())
 }
+
+#[test]
+fn clone_repository_with_external_converter() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+    let url = "https://github.com/elhmn/ckp";
+
+    //the converter counts the bytes of the extracted data
+    let converter = format!("{TMP_DIR}/my-world.sh");
+    std::fs::write(&converter, "#!/bin/sh\necho \"{\\\"size\\\": $(wc -c)}\"\n")?;
+    Command::new("chmod")
+        .arg("+x")
+        .arg(&converter)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.current_dir(TMP_DIR)
+        .arg("scan")
+        .arg(url)
+        .arg("--converter-cmd")
+        .arg("./my-world.sh");
+    cmd.assert().success();
+
+    //the converted file is named after the converter
+    let expected_converted_file = format!(
+        "{}/{}/{}/{}",
+        TMP_DIR, ".wake/scanner", "github-com-elhmn-ckp", "my-world-converted.json"
+    );
+    assert!(std::path::Path::new(expected_converted_file.as_str()).exists());
+
+    test::teardown();
+    Ok(())
+}
+
+#[test]
+fn fail_on_unknown_converter() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("scan")
+        .arg("unknown")
+        .arg("https://github.com/elhmn/ckp");
+    cmd.assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("unknown converter `unknown`"));
+
+    Ok(())
+}
+
+#[test]
+fn print_errors_as_json() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("scan")
+        .arg("shmup")
+        .arg("http://github.com")
+        .arg("--json");
+    let output = cmd.output()?;
+    assert_eq!(output.status.code(), Some(1));
+    let out: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(out["exit_code"], 1);
+    assert!(out["error"]
+        .as_str()
+        .unwrap_or("")
+        .contains("not a https url"));
+
+    //nothing but the errors is printed in quiet mode
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("scan")
+        .arg("shmup")
+        .arg("http://github.com")
+        .arg("--quiet");
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("Cloning").not());
+
+    test::teardown();
+    Ok(())
+}
+
+#[test]
+fn scan_repositories_from_file() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    let list = format!("{TMP_DIR}/repos.txt");
+    std::fs::write(
+        &list,
+        "# repositories to scan\nhttp://github.com\n\nnot-a-url\n",
+    )?;
+
+    //a failure does not stop the other scans
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("scan")
+        .arg("shmup")
+        .arg("--from-file")
+        .arg(&list)
+        .arg("--workers")
+        .arg("2");
+    cmd.assert()
+        .code(6)
+        .stdout(predicate::str::contains(
+            "Scanned 2 repositories: 0 succeeded, 2 failed",
+        ))
+        .stderr(predicate::str::contains("failed  not-a-url"));
+
+    //the list is read from stdin with `-`
+    let mut cmd = assert_cmd::Command::cargo_bin("wake")?;
+    cmd.arg("scan")
+        .arg("shmup")
+        .arg("--from-file")
+        .arg("-")
+        .arg("--json");
+    let output = cmd.write_stdin("http://github.com\n").output()?;
+    assert_eq!(output.status.code(), Some(6));
+    let out: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(out[0]["repository"], "http://github.com");
+    assert_eq!(out[0]["exit_code"], 1);
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("scan")
+        .arg("shmup")
+        .arg("http://github.com")
+        .arg("--from-file")
+        .arg(&list);
+    cmd.assert().failure();
+
+    test::teardown();
+    Ok(())
+}
+
+#[test]
+fn scan_local_repository_and_bundle() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .current_dir(format!("{TMP_DIR}/app"))
+            .args(["-c", "user.name=wake", "-c", "user.email=wake@example.com"])
+            .args(args)
+            .assert()
+            .success();
+    };
+    std::fs::create_dir_all(format!("{TMP_DIR}/app"))?;
+    git(&["init", "--quiet"]);
+    std::fs::write(format!("{TMP_DIR}/app/a.rs"), "fn a() {}\n")?;
+    git(&["add", "a.rs"]);
+    git(&["commit", "--quiet", "-m", "a"]);
+    git(&["tag", "v1"]);
+    std::fs::write(format!("{TMP_DIR}/app/b.rs"), "fn b() {}\n")?;
+    git(&["add", "b.rs"]);
+    git(&["commit", "--quiet", "-m", "b"]);
+    git(&["bundle", "create", "--quiet", "../app.bundle", "--all"]);
+
+    //the local repository is scanned in place
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.current_dir(TMP_DIR)
+        .arg("scan")
+        .arg("shmup")
+        .arg("./app")
+        .arg("--output-dir")
+        .arg("out")
+        .arg("--json");
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let out: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(out["counts"]["files"], 2);
+    assert!(PathBuf::from(format!("{TMP_DIR}/out/shmup-converted.json")).exists());
+
+    //the bundle is cloned, then the ref is checked out
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.current_dir(TMP_DIR)
+        .arg("scan")
+        .arg("shmup")
+        .arg("./app.bundle")
+        .arg("--ref")
+        .arg("v1")
+        .arg("--json");
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let out: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(out["ref_name"], "refs/tags/v1");
+    assert_eq!(out["counts"]["files"], 1);
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.current_dir(TMP_DIR)
+        .arg("scan")
+        .arg("shmup")
+        .arg("./app.bundle")
+        .arg("--ref")
+        .arg("v2");
+    cmd.assert()
+        .failure()
+        .code(3)
+        .stderr(predicate::str::contains(
+            "failed to find the ref to traverse",
+        ));
+
+    test::teardown();
+    Ok(())
+}
+
+#[test]
+fn scan_repositories_from_file_with_an_external_converter() -> Result<(), Box<dyn std::error::Error>>
+{
+    test::setup();
+    let r = git2::Repository::init(format!("{TMP_DIR}/app"))?;
+    test::commit(&r, "a.rs", "fn a() {}\n");
+    std::fs::write(format!("{TMP_DIR}/repos.txt"), "./app\n")?;
+
+    let converter = format!("{TMP_DIR}/my-world.sh");
+    std::fs::write(&converter, "#!/bin/sh\necho \"{\\\"size\\\": $(wc -c)}\"\n")?;
+    Command::new("chmod")
+        .arg("+x")
+        .arg(&converter)
+        .assert()
+        .success();
+
+    //the external converter is named after its command, like the single scans
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.current_dir(TMP_DIR)
+        .arg("scan")
+        .arg("--from-file")
+        .arg("repos.txt")
+        .arg("--converter-cmd")
+        .arg("./my-world.sh");
+    cmd.assert().success();
+    let scanner = format!("{TMP_DIR}/.wake/scanner/local-app");
+    assert!(PathBuf::from(format!("{scanner}/my-world-converted.json")).exists());
+    assert!(!PathBuf::from(format!("{scanner}/shmup-converted.json")).exists());
+
+    test::teardown();
+    Ok(())
+}
+
+#[test]
+fn fail_on_external_converter_named_like_a_builtin() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    //the command is named after its file, like the builtin graph converter
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.current_dir(TMP_DIR)
+        .arg("scan")
+        .arg("https://github.com/elhmn/ckp")
+        .arg("--converter-cmd")
+        .arg("./graph")
+        .arg("--register");
+    cmd.assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "`graph` is the name of a builtin converter",
+        ));
+    assert!(!PathBuf::from(format!("{TMP_DIR}/.wake/converters.yaml")).exists());
+
+    test::teardown();
+    Ok(())
+}
+
+#[test]
+fn scan_repositories_of_the_same_name_from_file() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+    for dir in ["a", "b"] {
+        let r = git2::Repository::init(format!("{TMP_DIR}/{dir}/app"))?;
+        test::commit(&r, "a.rs", "fn a() {}\n");
+    }
+    //the same repository listed twice is scanned once at a time
+    std::fs::write(
+        format!("{TMP_DIR}/repos.txt"),
+        "./a/app\n./b/app\n./a/app\n",
+    )?;
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.current_dir(TMP_DIR)
+        .arg("scan")
+        .arg("shmup")
+        .arg("--from-file")
+        .arg("repos.txt")
+        .arg("--workers")
+        .arg("3")
+        .arg("--output-dir")
+        .arg("out")
+        .arg("--json");
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let out: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let files: Vec<&str> = (0..3)
+        .map(|i| out[i]["summary"]["converted_file"].as_str().unwrap_or(""))
+        .collect();
+    assert_ne!(files[0], files[1]);
+    assert_eq!(files[0], files[2]);
+    for file in files {
+        assert!(file.starts_with("out/local-app-"), "{file}");
+        assert!(PathBuf::from(format!("{TMP_DIR}/{file}")).exists());
+    }
+
+    test::teardown();
+    Ok(())
+}
@@ -50,6 +50,7 @@ fn get_scan_extracted() -> Result<(), Box<dyn std::error::Error>> {
         let body = server::ScanRequest {
             repo_url: Some("https://github.com/elhmn/ckp".to_string()),
             _ref_: Some("".to_string()),
+            ..Default::default()
         };
 
         let json_body = serde_json::to_string(&body).unwrap();
@@ -78,6 +79,7 @@ fn get_scan_extracted() -> Result<(), Box<dyn std::error::Error>> {
         let body = server::ScanRequest {
             repo_url: Some("https://wrong_url".to_string()),
             _ref_: Some("".to_string()),
+            ..Default::default()
         };
 
         let json_body = serde_json::to_string(&body).unwrap();
@@ -95,6 +97,7 @@ fn get_scan_extracted() -> Result<(), Box<dyn std::error::Error>> {
 
     //stop the server
     child.kill().unwrap();
+    child.wait().unwrap();
     test::teardown();
     Ok(())
 }
@@ -109,6 +112,7 @@ fn get_scan_converted() -> Result<(), Box<dyn std::error::Error>> {
         let body = server::ScanRequest {
             repo_url: Some("https://github.com/elhmn/ckp".to_string()),
             _ref_: Some("".to_string()),
+            ..Default::default()
         };
 
         let json_body = serde_json::to_string(&body).unwrap();
@@ -137,6 +141,7 @@ fn get_scan_converted() -> Result<(), Box<dyn std::error::Error>> {
         let body = server::ScanRequest {
             repo_url: Some("https://wrong_url".to_string()),
             _ref_: Some("".to_string()),
+            ..Default::default()
         };
 
         let json_body = serde_json::to_string(&body).unwrap();
@@ -154,6 +159,7 @@ fn get_scan_converted() -> Result<(), Box<dyn std::error::Error>> {
 
     //stop the server
     child.kill().unwrap();
+    child.wait().unwrap();
     test::teardown();
     Ok(())
 }
@@ -168,6 +174,7 @@ fn get_scan() -> Result<(), Box<dyn std::error::Error>> {
         let body = server::ScanRequest {
             repo_url: Some("https://github.com/elhmn/ckp".to_string()),
             _ref_: Some("".to_string()),
+            ..Default::default()
         };
 
         let json_body = serde_json::to_string(&body).unwrap();
@@ -198,6 +205,7 @@ fn get_scan() -> Result<(), Box<dyn std::error::Error>> {
         let body = server::ScanRequest {
             repo_url: Some("https://wrong_url".to_string()),
             _ref_: Some("".to_string()),
+            ..Default::default()
         };
 
         let json_body = serde_json::to_string(&body).unwrap();
@@ -215,6 +223,7 @@ fn get_scan() -> Result<(), Box<dyn std::error::Error>> {
 
     //stop the server
     child.kill().unwrap();
+    child.wait().unwrap();
     test::teardown();
     Ok(())
 }
@@ -0,0 +1,57 @@
+use assert_cmd::prelude::*;
+use core::utils::test;
+use core::utils::test::TMP_DIR;
+use std::fs;
+// Add methods on commands
+use predicates::prelude::*; // Used for writing assertions
+use std::process::Command; // Run programs
+
+const GOLDEN_DIR: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../waking-git-core/schemas/golden"
+);
+
+#[test]
+fn validate_golden_files() -> Result<(), Box<dyn std::error::Error>> {
+    for file in [
+        "extracted.json",
+        "shmup-converted.json",
+        "codealkemi-converted.json",
+    ] {
+        let mut cmd = Command::cargo_bin("wake")?;
+        cmd.arg("validate").arg(format!("{GOLDEN_DIR}/{file}"));
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("matches the"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn report_schema_violations() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    let content = fs::read_to_string(format!("{GOLDEN_DIR}/shmup-converted.json"))?;
+    let mut document: serde_json::Value = serde_json::from_str(&content)?;
+    document["main_scene"] = serde_json::json!(42);
+    let file = format!("{TMP_DIR}/shmup-converted.json");
+    fs::write(&file, document.to_string())?;
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("validate").arg(&file);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("/main_scene: "))
+        .stdout(predicate::str::contains("1 violation(s) found"));
+
+    //the schema can't be guessed from that file name
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("validate").arg(format!("{TMP_DIR}/data.json"));
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("use --schema"));
+
+    test::teardown();
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use assert_cmd::prelude::*;
+use core::utils::test;
+use core::utils::test::TMP_DIR;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+// Add methods on commands
+use predicates::prelude::*; // Used for writing assertions
+use std::process::{Command, Stdio}; // Run programs
+
+#[test]
+fn convert_new_commits() -> Result<(), Box<dyn std::error::Error>> {
+    test::setup();
+
+    let r = git2::Repository::init(format!("{TMP_DIR}/project"))?;
+    test::commit(&r, "main.rs", "fn main() {}\n");
+
+    let mut child = Command::cargo_bin("wake")?
+        .current_dir(TMP_DIR)
+        .arg("watch")
+        .arg("project")
+        .arg("--interval")
+        .arg("50")
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (tx, rx) = mpsc::channel();
+    let stderr = child.stderr.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            let _ = tx.send(line);
+        }
+    });
+    let wait_for = |text: &str| loop {
+        match rx.recv_timeout(Duration::from_secs(30)) {
+            Ok(line) if line.contains(text) => return true,
+            Ok(_) => continue,
+            Err(_) => return false,
+        }
+    };
+
+    assert!(wait_for("Watching"));
+    let converted = format!("{TMP_DIR}/.wake/scanner/local-project/shmup-converted.json");
+    let first = fs::read_to_string(&converted)?;
+
+    test::commit(&r, "lib.rs", "pub fn run() {}\n");
+    let found = wait_for("Conversion completed in");
+    child.kill()?;
+    child.wait()?;
+    assert!(found);
+    assert_ne!(fs::read_to_string(&converted)?, first);
+
+    test::teardown();
+    Ok(())
+}
+
+#[test]
+fn fail_to_watch_a_missing_repository() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("watch").arg("/wake-missing-repository");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to open"));
+
+    let mut cmd = Command::cargo_bin("wake")?;
+    cmd.arg("watch").arg(".").arg("--play").arg("tetris");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown player `tetris`"));
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use crate::config;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Cache records the commit and the options every converted file
+/// of a repository was generated from, so that a conversion can be
+/// reused as long as neither of them change
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
+pub struct Cache {
+    //the converted files, by converter name
+    #[serde(default)]
+    pub converted: BTreeMap<String, Entry>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    //the oid of the commit the file was converted from
+    pub commit: String,
+    //the fingerprint of the options the file was converted with
+    #[serde(default)]
+    pub options: String,
+    //the path of the converted file
+    pub file: String,
+}
+
+/// returns the path of the cache file of the scanner folder `scanner_path`
+pub fn get_path(scanner_path: &str) -> String {
+    format!("{scanner_path}/{}", config::CACHE_FILE_NAME)
+}
+
+/// load the cache of the scanner folder `scanner_path`,
+/// a missing file is an empty cache
+pub fn load(scanner_path: &str) -> Result<Cache, Error> {
+    let path = get_path(scanner_path);
+    if !Path::new(&path).exists() {
+        return Ok(Cache::default());
+    }
+
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(err) => {
+            return Err(Error::io(
+                format!("failed to read cache file `{path}`"),
+                err,
+            ))
+        }
+    };
+
+    match serde_yaml::from_str(&content) {
+        Ok(c) => Ok(c),
+        Err(err) => Err(Error::serialize(
+            format!("invalid cache file `{path}`"),
+            err,
+        )),
+    }
+}
+
+impl Cache {
+    /// returns the file converted by `converter` from `commit` with
+    /// the `options` fingerprint, if it is still on disk
+    pub fn find(&self, converter: &str, commit: &str, options: &str) -> Option<&Entry> {
+        self.converted
+            .get(converter)
+            .filter(|e| e.commit == commit && e.options == options && Path::new(&e.file).is_file())
+    }
+
+    /// record that `converter` converted `commit` to `file`
+    /// with the `options` fingerprint
+    pub fn record(&mut self, converter: &str, commit: &str, options: &str, file: &str) {
+        self.converted.insert(
+            converter.to_owned(),
+            Entry {
+                commit: commit.to_owned(),
+                options: options.to_owned(),
+                file: file.to_owned(),
+            },
+        );
+    }
+
+    /// write the cache to the scanner folder `scanner_path`
+    pub fn save(&self, scanner_path: &str) -> Result<(), Error> {
+        let content = match serde_yaml::to_string(self) {
+            Ok(c) => c,
+            Err(err) => return Err(Error::serialize("failed to serialize cache", err)),
+        };
+
+        if let Err(err) = fs::create_dir_all(scanner_path) {
+            return Err(Error::io(format!("failed to create `{scanner_path}`"), err));
+        }
+
+        let path = get_path(scanner_path);
+        match fs::write(&path, content) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::io(
+                format!("failed to write cache file `{path}`"),
+                err,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache;
+    use crate::utils::test;
+    use std::fs;
+
+    #[test]
+    fn record_and_find_converted_files() {
+        let dir = test::temp_dir();
+        let scanner_path = dir.path.to_string_lossy().into_owned();
+        assert_eq!(cache::load(&scanner_path).unwrap(), cache::Cache::default());
+
+        let file = format!("{scanner_path}/shmup-converted.json");
+        let mut c = cache::Cache::default();
+        c.record("shmup", "abc", "opts", &file);
+        c.save(&scanner_path).unwrap();
+
+        //the file must still exist to be reused
+        let c = cache::load(&scanner_path).unwrap();
+        assert!(c.find("shmup", "abc", "opts").is_none());
+        fs::write(&file, "{}").unwrap();
+        assert_eq!(c.find("shmup", "abc", "opts").unwrap().file, file);
+        assert!(c.find("shmup", "def", "opts").is_none());
+        assert!(c.find("shmup", "abc", "other").is_none());
+        assert!(c.find("dungeon", "abc", "opts").is_none());
+
+        fs::write(cache::get_path(&scanner_path), "converted: [").unwrap();
+        assert!(cache::load(&scanner_path).is_err());
+    }
+}
@@ -1,9 +1,46 @@
-use std::path::PathBuf;
+use crate::converters;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 pub const REPOS_FOLDER_NAME: &str = "repos";
 pub const SCANNER_FOLDER_NAME: &str = "scanner";
-pub const EXTRACTOR_FILE_NAME: &str = "extracted.json";
-pub const CONVERTER_FILE_NAME_PREFIX: &str = "converted.json";
+//the extracted and converted file names are suffixed
+//with the extension of the output format, as `extracted.json`
+pub const EXTRACTOR_FILE_NAME: &str = "extracted";
+pub const CONVERTER_FILE_NAME_PREFIX: &str = "converted";
+//the registry of the external converters
+pub const CONVERTERS_FILE_NAME: &str = "converters.yaml";
+//the record of the commits the converted files were generated from
+pub const CACHE_FILE_NAME: &str = "cache.yaml";
+//the file the players write the result of a session to, prefixed with the player name
+pub const RESULT_FILE_NAME_SUFFIX: &str = "result.json";
+//the settings of wake, stored in the wake folder
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+//the environment variable overriding the path to the wake folder
+pub const WAKE_HOME_ENV: &str = "WAKE_HOME";
+//the settings are overridden by the environment variables named after
+//them with this prefix, as `WAKE_CLONE_DEPTH` for `clone_depth`
+pub const ENV_PREFIX: &str = "WAKE_";
+
+pub const FETCH_NEVER: &str = "never";
+pub const FETCH_ALWAYS: &str = "always";
+
+/// The keys of the settings, as written in the config file
+pub const KEYS: [&str; 9] = [
+    "storage_path",
+    "scanner_path",
+    "default_converter",
+    "clone_depth",
+    "fetch",
+    "port",
+    "workers",
+    "include",
+    "exclude",
+];
 
 #[derive(Debug, Default)]
 pub struct Config {
@@ -13,22 +50,266 @@ pub struct Config {
 
     /// Path to the repository storage folder
     pub storage_path: String,
+
+    /// Path to the folder the extracted and converted files are written to
+    pub scanner_path: String,
+
+    /// Path to the registry of the external converters
+    pub converters_path: String,
+
+    /// Path to the config file
+    pub config_path: String,
+
+    /// The settings of the config file, overridden by the environment
+    pub settings: Settings,
 }
 
-pub const WAKE_FOLDER: &str = ".wake";
+/// FetchPolicy tells whether a repository already cloned
+/// is fetched again before it is scanned
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FetchPolicy {
+    #[default]
+    Never,
+    Always,
+}
+
+impl FetchPolicy {
+    pub fn from_name(name: &str) -> Result<FetchPolicy, Error> {
+        match name {
+            FETCH_NEVER => Ok(FetchPolicy::Never),
+            FETCH_ALWAYS => Ok(FetchPolicy::Always),
+            _ => Err(Error::parse(format!(
+                "unknown fetch policy `{name}`, expected one of: {FETCH_NEVER}, {FETCH_ALWAYS}"
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FetchPolicy::Never => FETCH_NEVER,
+            FetchPolicy::Always => FETCH_ALWAYS,
+        }
+    }
+}
+
+/// Settings of the config file.
+///
+/// They are overridden by the `WAKE_<KEY>` environment variables,
+/// which are overridden by the command flags
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    //the folder the repositories are cloned in,
+    //`<wake folder>/repos` when empty
+    pub storage_path: String,
+    //the folder the extracted and converted files are written to,
+    //`<wake folder>/scanner` when empty
+    pub scanner_path: String,
+    //the converter used when none is given
+    pub default_converter: String,
+    //the number of commits cloned, 0 clones the whole history
+    pub clone_depth: u32,
+    //whether the repositories already cloned are fetched again
+    pub fetch: FetchPolicy,
+    //the port the server listens on
+    pub port: u16,
+    //the number of repositories scanned at the same time,
+    //0 for the number of cpus
+    pub workers: usize,
+    //the globs of the file paths kept, when no glob is given
+    pub include: Vec<String>,
+    //the globs of the file paths dropped, when no glob is given
+    pub exclude: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            storage_path: String::new(),
+            scanner_path: String::new(),
+            default_converter: converters::shmup::CONVERTER_NAME.to_string(),
+            clone_depth: 1,
+            fetch: FetchPolicy::default(),
+            port: 8080,
+            workers: 0,
+            include: vec![],
+            exclude: vec![],
+        }
+    }
+}
+
+impl Settings {
+    /// returns the value of the setting `key`,
+    /// lists are joined with commas
+    pub fn get(&self, key: &str) -> Result<String, Error> {
+        let value = match key {
+            "storage_path" => self.storage_path.to_owned(),
+            "scanner_path" => self.scanner_path.to_owned(),
+            "default_converter" => self.default_converter.to_owned(),
+            "clone_depth" => self.clone_depth.to_string(),
+            "fetch" => self.fetch.name().to_owned(),
+            "port" => self.port.to_string(),
+            "workers" => self.workers.to_string(),
+            "include" => self.include.join(","),
+            "exclude" => self.exclude.join(","),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value)
+    }
+
+    /// set the setting `key` from its string `value`,
+    /// lists are split on commas
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        match key {
+            "storage_path" => self.storage_path = value.to_owned(),
+            "scanner_path" => self.scanner_path = value.to_owned(),
+            "default_converter" => self.default_converter = value.to_owned(),
+            "clone_depth" => self.clone_depth = parse(key, value)?,
+            "fetch" => self.fetch = FetchPolicy::from_name(value)?,
+            "port" => self.port = parse(key, value)?,
+            "workers" => self.workers = parse(key, value)?,
+            "include" => self.include = split_list(value),
+            "exclude" => self.exclude = split_list(value),
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    /// write the settings to the config file located at `path`
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let content = match toml::to_string(self) {
+            Ok(c) => c,
+            Err(err) => return Err(Error::serialize("failed to serialize settings", err)),
+        };
+
+        if let Some(dir) = Path::new(path).parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                return Err(Error::io(
+                    format!("failed to create `{}`", dir.display()),
+                    err,
+                ));
+            }
+        }
+
+        match fs::write(path, content) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::io(
+                format!("failed to write config file `{path}`"),
+                err,
+            )),
+        }
+    }
+}
+
+fn unknown_key(key: &str) -> Error {
+    Error::parse(format!(
+        "unknown setting `{key}`, expected one of: {}",
+        KEYS.join(", ")
+    ))
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, Error>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match value.trim().parse() {
+        Ok(v) => Ok(v),
+        Err(err) => Err(Error::Parse {
+            message: format!("invalid `{key}` value `{value}`"),
+            source: Some(Box::new(err)),
+        }),
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|v| v.trim().to_owned())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// returns the name of the environment variable overriding the setting `key`
+pub fn env_var_name(key: &str) -> String {
+    format!("{ENV_PREFIX}{}", key.to_uppercase())
+}
+
+/// load the settings of the config file located at `path`,
+/// a missing file holds the default settings
+pub fn load_settings(path: &str) -> Result<Settings, Error> {
+    if !Path::new(path).exists() {
+        return Ok(Settings::default());
+    }
+
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(err) => {
+            return Err(Error::io(
+                format!("failed to read config file `{path}`"),
+                err,
+            ))
+        }
+    };
+
+    match toml::from_str(&content) {
+        Ok(s) => Ok(s),
+        Err(err) => Err(Error::Parse {
+            message: format!("invalid config file `{path}`"),
+            source: Some(Box::new(err)),
+        }),
+    }
+}
+
+/// load the config, from the lowest to the highest precedence:
+/// - the default settings
+/// - the config file, `<wake folder>/config.toml`
+/// - the `WAKE_<KEY>` environment variables
+///
+/// The wake folder is `WAKE_HOME`, or `~/.wake` when it is not set
+pub fn load() -> Result<Config, Error> {
+    let mut conf = Config::new();
+    let mut settings = load_settings(&conf.config_path)?;
+    for key in KEYS {
+        if let Ok(value) = env::var(env_var_name(key)) {
+            settings.set(key, &value)?;
+        }
+    }
+
+    if !settings.storage_path.is_empty() {
+        conf.storage_path = settings.storage_path.to_owned();
+    }
+    if !settings.scanner_path.is_empty() {
+        conf.scanner_path = settings.scanner_path.to_owned();
+    }
+    conf.settings = settings;
+    Ok(conf)
+}
 
 impl Config {
+    /// returns the config with the default settings
     pub fn new() -> Config {
-        let dir = get_home_dir();
-        let wake_path = format!("{dir}/{WAKE_FOLDER}");
+        let wake_path = match env::var(WAKE_HOME_ENV) {
+            Ok(p) if !p.is_empty() => p,
+            _ => format!("{}/{WAKE_FOLDER}", get_home_dir()),
+        };
         let storage_path = format!("{wake_path}/{REPOS_FOLDER_NAME}");
+        let scanner_path = format!("{wake_path}/{SCANNER_FOLDER_NAME}");
+        let converters_path = format!("{wake_path}/{CONVERTERS_FILE_NAME}");
+        let config_path = format!("{wake_path}/{CONFIG_FILE_NAME}");
         Config {
             wake_path,
             storage_path,
+            scanner_path,
+            converters_path,
+            config_path,
+            settings: Settings::default(),
         }
     }
 }
 
+pub const WAKE_FOLDER: &str = ".wake";
+
 pub fn get_home_dir() -> String {
     // check if we are running the binary for integration tests
     let dir: PathBuf = if std::env::var("WAKE_TEST_MODE").is_ok() {
@@ -42,3 +323,41 @@ pub fn get_home_dir() -> String {
 
     dir.to_str().unwrap_or("").to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{self, FetchPolicy, Settings};
+    use crate::utils::test;
+    use std::fs;
+
+    #[test]
+    fn get_set_and_save_settings() {
+        let mut settings = Settings::default();
+        settings.set("clone_depth", "0").unwrap();
+        settings.set("fetch", "always").unwrap();
+        settings.set("exclude", "tests/**, docs").unwrap();
+        assert_eq!(settings.clone_depth, 0);
+        assert_eq!(settings.fetch, FetchPolicy::Always);
+        assert_eq!(settings.get("exclude").unwrap(), "tests/**,docs");
+
+        assert!(settings.set("port", "http").is_err());
+        assert!(settings.set("fetch", "sometimes").is_err());
+        assert!(settings.get("colour").is_err());
+
+        let dir = test::temp_dir();
+        let path = dir.path.join(config::CONFIG_FILE_NAME);
+        let path = path.to_string_lossy();
+        assert_eq!(config::load_settings(&path).unwrap(), Settings::default());
+        settings.save(&path).unwrap();
+        assert_eq!(config::load_settings(&path).unwrap(), settings);
+
+        //the missing settings keep their default value
+        fs::write(path.as_ref(), "port = 3000\n").unwrap();
+        let loaded = config::load_settings(&path).unwrap();
+        assert_eq!(loaded.port, 3000);
+        assert_eq!(loaded.default_converter, "shmup");
+
+        fs::write(path.as_ref(), "colour = true\n").unwrap();
+        assert!(config::load_settings(&path).is_err());
+    }
+}
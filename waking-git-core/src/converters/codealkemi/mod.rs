@@ -1,37 +1,58 @@
+use crate::converters::{metrics, rules, spaces};
+use crate::error::Error;
 use crate::{converters, extractor, languages};
 use rayon::prelude::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, Mutex};
 
-pub struct CodeAlkemiConverter {}
+pub struct CodeAlkemiConverter {
+    options: converters::Options,
+}
+
+pub const CONVERTER_NAME: &str = "codealkemi";
 
-const CONVERTER_NAME: &str = "codealkemi";
+/// The semver version of the codealkemi output schema.
+/// Minor versions only add fields, a major version breaks the schema.
+pub const VERSION: &str = "1.0.0";
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+//the version carried by the files generated before the schema was versioned
+const LEGACY_VERSION: &str = "v0";
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
 pub struct Data {
     pub version: String,
     pub name: String,
     pub repo_name: String,
+    //the url of the repository the data was generated from
     pub url: String,
+    //the ref scanned, as `refs/heads/main`
+    #[serde(default, rename = "ref")]
+    pub ref_name: String,
     pub commit: String,
     //The id of the main scene
     //it corresponds to the root tree of the git repository
     //and is stored as the sha256 of that path.
     pub main_scene: String,
-    pub scenes: HashMap<String, Scene>,
+    pub scenes: BTreeMap<String, Scene>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
 pub struct Scene {
+    //The id is the sha256 of the tree path
+    #[serde(default)]
+    pub id: String,
+    //stats aggregated over the scene and its sub scenes
+    #[serde(default)]
+    pub stats: metrics::SceneStats,
     // Entities, has the sha256 of the git blob as its key, and the entity
     // as value.
-    pub entities: HashMap<String, Entity>,
+    pub entities: BTreeMap<String, Entity>,
     pub sub_scenes: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
 pub struct Language {
     pub name: String,
     pub kind: String,
@@ -39,7 +60,7 @@ pub struct Language {
     pub extension: String,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
 pub struct Entity {
     //The id is the Sha256 of the the blob path
     pub id: String,
@@ -50,15 +71,37 @@ pub struct Entity {
     pub name: String,
     pub path: String,
     pub language: Language,
+    #[serde(default)]
+    pub metrics: metrics::Metrics,
+    //rooms are built from the functions, classes, impls...
+    //declared in the file, when sub entities are enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rooms: Vec<spaces::Space>,
+}
+
+impl metrics::StatsScene for Scene {
+    fn sub_scenes(&self) -> &Vec<String> {
+        &self.sub_scenes
+    }
+
+    fn stats_mut(&mut self) -> &mut metrics::SceneStats {
+        &mut self.stats
+    }
 }
 
 pub fn new() -> CodeAlkemiConverter {
-    CodeAlkemiConverter {}
+    CodeAlkemiConverter {
+        options: converters::Options::default(),
+    }
+}
+
+pub fn with_options(options: converters::Options) -> CodeAlkemiConverter {
+    CodeAlkemiConverter { options }
 }
 
 impl converters::Converter<Data> for CodeAlkemiConverter {
-    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, String> {
-        Ok(build_converter_data(extracted_data))
+    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, Error> {
+        build_converter_data(extracted_data, &self.options)
     }
 
     fn name(&self) -> String {
@@ -66,15 +109,21 @@ impl converters::Converter<Data> for CodeAlkemiConverter {
     }
 }
 
-fn build_converter_data(extracted_data: &extractor::Data) -> Data {
+fn build_converter_data(
+    extracted_data: &extractor::Data,
+    options: &converters::Options,
+) -> Result<Data, Error> {
     let commit_oid = &extracted_data.git.ref_target.1;
     let objs = &extracted_data.git.objects;
     let files = &extracted_data.code.files_data;
     let repo_name = &extracted_data.code.repo_name;
+    let distributions = metrics::new_distributions(extracted_data);
 
     let data = Data {
-        version: "v0".to_owned(),
+        version: VERSION.to_owned(),
         main_scene: get_main_scene(extracted_data),
+        url: extracted_data.git.url.to_owned(),
+        ref_name: extracted_data.git.ref_target.0.to_owned(),
         commit: commit_oid.to_string(),
         repo_name: repo_name.to_owned(),
         name: CONVERTER_NAME.to_owned(),
@@ -86,11 +135,20 @@ fn build_converter_data(extracted_data: &extractor::Data) -> Data {
     //Get the initial commit
     if let Some(commit) = &objs[commit_oid].commit {
         let trees_oid = vec![commit.tree.to_owned()];
-        add_scenes(&trees_oid, mut_data.to_owned(), objs, files);
+        add_scenes(
+            &trees_oid,
+            mut_data.to_owned(),
+            objs,
+            files,
+            &distributions,
+            options,
+        )?;
     }
 
-    let data = mut_data.lock().unwrap().to_owned();
-    data
+    let mut data = mut_data.lock().unwrap().to_owned();
+    let main_scene = data.main_scene.to_owned();
+    metrics::aggregate_stats(&main_scene, &mut data.scenes);
+    Ok(data)
 }
 
 // Gets sha256 path of the root directory.
@@ -115,10 +173,13 @@ fn add_scenes(
     data: Arc<Mutex<Data>>,
     objs: &HashMap<String, extractor::git::Object>,
     files: &HashMap<String, extractor::code::FileData>,
-) {
-    trees_oid.par_iter().for_each(|tree_oid| {
+    distributions: &metrics::Distributions,
+    options: &converters::Options,
+) -> Result<(), Error> {
+    trees_oid.par_iter().try_for_each(|tree_oid| {
         if let Some(tree) = &objs[tree_oid].tree {
             let mut scene = Scene {
+                id: tree.path_sha.to_owned(),
                 ..Default::default()
             };
             let mut sub_scene_oids: Vec<String> = vec![];
@@ -126,9 +187,12 @@ fn add_scenes(
             // Create entities
             for oid in &tree.objects {
                 if let Some(blob) = &objs[oid].blob {
-                    let mut entity = blob_to_entity(blob, files);
-                    entity.scene_id = oid.to_owned();
+                    let mut entity = blob_to_entity(blob, files, distributions, options)?;
+                    entity.scene_id = scene.id.to_owned();
                     scene.entities.insert(blob.path_sha.to_owned(), entity);
+
+                    let m = metrics::file_metrics(blob, files);
+                    scene.stats.add_file(blob, &m);
                 } else {
                     // Store sub_scenes path_sha
                     if let Some(tree) = &objs[oid].tree {
@@ -139,24 +203,38 @@ fn add_scenes(
                 }
             }
 
+            scene.sub_scenes.sort();
             if !sub_scene_oids.is_empty() {
-                add_scenes(&sub_scene_oids, data.to_owned(), objs, files);
+                add_scenes(
+                    &sub_scene_oids,
+                    data.to_owned(),
+                    objs,
+                    files,
+                    distributions,
+                    options,
+                )?;
             }
 
             data.lock()
                 .unwrap()
                 .scenes
-                .insert(tree.path_sha.to_owned(), scene);
+                .insert(scene.id.to_owned(), scene);
         }
-    });
+
+        Ok(())
+    })
 }
 
+/// build an entity from the blob,
+/// the rules are applied on top of the default mapping
 fn blob_to_entity(
     blob: &extractor::git::Blob,
-    _files: &HashMap<String, extractor::code::FileData>,
-) -> Entity {
+    files: &HashMap<String, extractor::code::FileData>,
+    distributions: &metrics::Distributions,
+    options: &converters::Options,
+) -> Result<Entity, Error> {
     let languages = languages::new();
-    let spec = get_language_spec(blob, &languages);
+    let spec = languages::spec_from_path(&languages, &blob.path);
     let language = Language {
         color: spec.color,
         kind: spec.kind,
@@ -164,27 +242,161 @@ fn blob_to_entity(
         ..Default::default()
     };
 
-    Entity {
+    let m = metrics::file_metrics(blob, files);
+    let mut entity = Entity {
         id: blob.path_sha.to_owned(),
         oid: blob.sha.to_owned(),
         name: blob.name.to_owned(),
         path: blob.path.to_owned(),
         language,
+        metrics: metrics::Metrics {
+            size: blob.size,
+            loc: m.loc,
+            complexity: m.complexity,
+            churn: m.churn,
+        },
         ..Default::default()
+    };
+
+    if options.sub_entities {
+        entity.rooms = spaces::from_blob(blob, files);
+    }
+
+    let rules = &options.rules;
+    if rules.is_empty() {
+        return Ok(entity);
     }
+
+    let ctx = rules::new_context(blob, files, distributions, &languages);
+    rules.apply(&ctx, entity)
 }
 
-fn get_language_spec(
-    blob: &extractor::git::Blob,
-    languages: &languages::Languages,
-) -> languages::Spec {
-    let p = Path::new(blob.path.as_str());
-    if let Some(ext) = p.extension() {
-        let converted_extension = format!(".{}", ext.to_string_lossy().into_owned());
-        return languages::spec_from_extension(languages, &converted_extension);
+/// parse codealkemi data generated by any version of wake.
+///
+/// Files generated with an older schema are upgraded to the current
+/// one, files generated with a newer major version are rejected.
+pub fn from_json(content: &str) -> Result<Data, Error> {
+    match serde_json::from_str(content) {
+        Ok(v) => from_value(v),
+        Err(err) => Err(Error::serialize("failed to parse codealkemi data", err)),
     }
+}
 
-    languages::Spec {
-        ..Default::default()
+/// same as `from_json`, for data already decoded
+pub fn from_value(value: serde_json::Value) -> Result<Data, Error> {
+    let value = upgrade(value)?;
+    match serde_json::from_value(value) {
+        Ok(d) => Ok(d),
+        Err(err) => Err(Error::serialize("failed to parse codealkemi data", err)),
+    }
+}
+
+/// upgrade a codealkemi json document to the current schema version
+pub fn upgrade(value: serde_json::Value) -> Result<serde_json::Value, Error> {
+    let version = value["version"].as_str().unwrap_or_default().to_owned();
+    if version == LEGACY_VERSION {
+        return upgrade_from_legacy(value);
+    }
+
+    let major = |v: &str| v.split('.').next().and_then(|m| m.parse::<u64>().ok());
+    let Some(file_major) = major(&version) else {
+        return Err(Error::parse(format!(
+            "unknown codealkemi schema version `{version}`, expected a semver version as `{VERSION}`"
+        )));
+    };
+
+    let current_major = major(VERSION).unwrap_or_default();
+    if file_major != current_major {
+        return Err(Error::parse(format!(
+            "codealkemi schema version `{version}` is not supported, this version of wake reads \
+             `{current_major}.x` files; upgrade wake or scan the repository again"
+        )));
+    }
+
+    Ok(value)
+}
+
+//legacy files lack the metrics, the scene ids and stats,
+//and their entities `scene_id` holds the entity oid
+fn upgrade_from_legacy(value: serde_json::Value) -> Result<serde_json::Value, Error> {
+    let mut data: Data = match serde_json::from_value(value) {
+        Ok(d) => d,
+        Err(err) => {
+            return Err(Error::serialize(
+                format!("failed to parse `{LEGACY_VERSION}` codealkemi data"),
+                err,
+            ))
+        }
+    };
+
+    for (id, scene) in data.scenes.iter_mut() {
+        scene.id = id.to_owned();
+        scene.stats.entities = scene.entities.len() as u32;
+        for entity in scene.entities.values_mut() {
+            entity.scene_id = id.to_owned();
+        }
+    }
+    let main_scene = data.main_scene.to_owned();
+    metrics::aggregate_stats(&main_scene, &mut data.scenes);
+    data.version = VERSION.to_owned();
+
+    match serde_json::to_value(data) {
+        Ok(v) => Ok(v),
+        Err(err) => Err(Error::serialize("failed to upgrade codealkemi data", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::codealkemi;
+
+    #[test]
+    fn upgrade_legacy_data() {
+        let legacy = r#"{
+            "version": "v0",
+            "name": "codealkemi",
+            "repo_name": "repo",
+            "url": "",
+            "commit": "abc",
+            "main_scene": "root",
+            "scenes": {
+                "root": {
+                    "entities": {
+                        "e1": {"id": "e1", "oid": "o1", "scene_id": "o1", "name": "main.rs",
+                               "path": "main.rs", "language": {"name": "Rust", "kind": "programming",
+                               "color": "", "extension": ""}}
+                    },
+                    "sub_scenes": ["src"]
+                },
+                "src": {"entities": {}, "sub_scenes": []}
+            }
+        }"#;
+
+        let data = codealkemi::from_json(legacy).unwrap();
+        assert_eq!(data.version, codealkemi::VERSION);
+        assert_eq!(data.scenes["root"].id, "root");
+        assert_eq!(data.scenes["root"].entities["e1"].scene_id, "root");
+        assert_eq!(data.scenes["root"].stats.entities, 1);
+        assert_eq!(data.scenes["root"].stats.scenes, 1);
+    }
+
+    #[test]
+    fn reject_unsupported_versions() {
+        let data = |version: &str| {
+            format!(
+                r#"{{"version": "{version}", "name": "codealkemi", "repo_name": "repo",
+                   "url": "", "commit": "abc", "main_scene": "", "scenes": {{}}}}"#
+            )
+        };
+
+        assert!(codealkemi::from_json(&data("1.2.0")).is_ok());
+        assert!(codealkemi::from_json(&data("2.0.0"))
+            .unwrap_err()
+            .to_string()
+            .contains("not supported"));
+        assert!(codealkemi::from_json(&data("latest"))
+            .unwrap_err()
+            .to_string()
+            .contains("unknown codealkemi schema version"));
     }
 }
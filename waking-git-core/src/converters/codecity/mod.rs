@@ -0,0 +1,300 @@
+use crate::converters::{metrics, rules};
+use crate::error::Error;
+use crate::{converters, extractor, languages};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+pub mod treemap;
+
+pub struct CodeCityConverter {
+    options: converters::Options,
+}
+
+pub const CONVERTER_NAME: &str = "codecity";
+
+//the space left between the border of a district and its content
+const DISTRICT_PADDING: f64 = 1.;
+//the share of its lot a building leaves empty on each side
+const BUILDING_MARGIN: f64 = 0.1;
+//the lines of code of the files that could not be analysed,
+//so that every building gets a lot
+const MIN_LOC: f64 = 1.;
+const MIN_HEIGHT: f64 = 1.;
+const MAX_HEIGHT: f64 = 50.;
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Data {
+    pub name: String,
+    pub repo_name: String,
+    pub commit: String,
+    //the id of the district of the root directory,
+    //its area covers the whole city
+    pub main_district: String,
+    pub districts: BTreeMap<String, District>,
+    pub buildings: BTreeMap<String, Building>,
+}
+
+/// Rect is an area of the city ground, in absolute coordinates.
+/// The origin is the top left corner of the city, `y` grows southward
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub length: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct District {
+    //The id is the sha256 of the tree path
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    //the id of the parent district, empty for the main district
+    pub parent: String,
+    //the depth of the district, 0 for the main district
+    pub depth: u32,
+    pub area: Rect,
+    pub sub_districts: Vec<String>,
+    //the ids of the buildings of the district
+    pub buildings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Language {
+    pub name: String,
+    pub kind: String,
+    pub color: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Building {
+    //The id is the sha256 of the blob path
+    pub id: String,
+    // the oid, is the git object id
+    pub oid: String,
+    pub district_id: String,
+    pub name: String,
+    pub path: String,
+    pub language: Language,
+    //the ground covered by the building, its area
+    //is proportional to the file lines of code
+    pub footprint: Rect,
+    //the building height, from the file complexity
+    pub height: f64,
+    pub metrics: metrics::Metrics,
+}
+
+pub fn new() -> CodeCityConverter {
+    CodeCityConverter {
+        options: converters::Options::default(),
+    }
+}
+
+pub fn with_options(options: converters::Options) -> CodeCityConverter {
+    CodeCityConverter { options }
+}
+
+impl converters::Converter<Data> for CodeCityConverter {
+    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, Error> {
+        build_converter_data(extracted_data, &self.options)
+    }
+
+    fn name(&self) -> String {
+        CONVERTER_NAME.to_owned()
+    }
+}
+
+struct Source<'a> {
+    objs: &'a HashMap<String, extractor::git::Object>,
+    files: &'a HashMap<String, extractor::code::FileData>,
+    distributions: metrics::Distributions,
+    languages: languages::Languages,
+    options: &'a converters::Options,
+}
+
+fn build_converter_data(
+    extracted_data: &extractor::Data,
+    options: &converters::Options,
+) -> Result<Data, Error> {
+    let commit_oid = &extracted_data.git.ref_target.1;
+    let src = Source {
+        objs: &extracted_data.git.objects,
+        files: &extracted_data.code.files_data,
+        distributions: metrics::new_distributions(extracted_data),
+        languages: languages::new(),
+        options,
+    };
+
+    let mut data = Data {
+        name: CONVERTER_NAME.to_owned(),
+        repo_name: extracted_data.code.repo_name.to_owned(),
+        commit: commit_oid.to_owned(),
+        ..Default::default()
+    };
+
+    let root_tree = match src.objs.get(commit_oid).and_then(|o| o.commit.as_ref()) {
+        Some(commit) => commit.tree.to_owned(),
+        None => return Ok(data),
+    };
+
+    let mut weights = HashMap::new();
+    let (main_district, weight) = add_districts(&root_tree, "", 0, &mut data, &mut weights, &src)?;
+    data.main_district = main_district.to_owned();
+
+    //the city is a square whose area is the sum of the lines of code
+    let side = weight.sqrt();
+    let area = Rect {
+        x: 0.,
+        y: 0.,
+        width: side,
+        length: side,
+    };
+    layout_district(&main_district, area, &mut data, &weights);
+    Ok(data)
+}
+
+//add the district of the tree `tree_oid`, its sub districts and buildings,
+//and returns its id and weight, the sum of the lines of code it holds
+fn add_districts(
+    tree_oid: &str,
+    parent: &str,
+    depth: u32,
+    data: &mut Data,
+    weights: &mut HashMap<String, f64>,
+    src: &Source,
+) -> Result<(String, f64), Error> {
+    let tree = match src.objs.get(tree_oid).and_then(|o| o.tree.as_ref()) {
+        Some(t) => t,
+        None => return Ok((String::new(), 0.)),
+    };
+
+    let mut district = District {
+        id: tree.path_sha.to_owned(),
+        name: tree.name.to_owned(),
+        path: tree.path.to_owned(),
+        parent: parent.to_owned(),
+        depth,
+        ..Default::default()
+    };
+    let mut weight = 0.;
+
+    for oid in &tree.objects {
+        let obj = match src.objs.get(oid) {
+            Some(o) => o,
+            None => continue,
+        };
+
+        if let Some(blob) = &obj.blob {
+            let mut building = blob_to_building(blob, src)?;
+            building.district_id = district.id.to_owned();
+            let w = building.metrics.loc.max(MIN_LOC);
+            weights.insert(building.id.to_owned(), w);
+            weight += w;
+            district.buildings.push(building.id.to_owned());
+            data.buildings.insert(building.id.to_owned(), building);
+        } else if obj.tree.is_some() {
+            let (id, w) = add_districts(oid, &district.id, depth + 1, data, weights, src)?;
+            //directories without any file are left out of the city
+            if w > 0. {
+                weight += w;
+                district.sub_districts.push(id);
+            }
+        }
+    }
+
+    district.buildings.sort();
+    district.sub_districts.sort();
+    let id = district.id.to_owned();
+    if weight > 0. || depth == 0 {
+        weights.insert(id.to_owned(), weight);
+        data.districts.insert(id.to_owned(), district);
+    }
+    Ok((id, weight))
+}
+
+/// build a building from a blob, its height is the normalized
+/// complexity of the file, its footprint is set by the layout.
+///
+/// The `rules` are applied on top of that default mapping.
+fn blob_to_building(blob: &extractor::git::Blob, src: &Source) -> Result<Building, Error> {
+    let spec = languages::spec_from_path(&src.languages, &blob.path);
+    let raw = metrics::file_metrics(blob, src.files);
+    let m = src.distributions.normalize(&raw);
+
+    let building = Building {
+        id: blob.path_sha.to_owned(),
+        oid: blob.sha.to_owned(),
+        name: blob.name.to_owned(),
+        path: blob.path.to_owned(),
+        language: Language {
+            name: spec.name,
+            kind: spec.kind,
+            color: spec.color,
+        },
+        height: MIN_HEIGHT + m.complexity * (MAX_HEIGHT - MIN_HEIGHT),
+        metrics: metrics::Metrics {
+            size: blob.size,
+            loc: raw.loc,
+            complexity: raw.complexity,
+            churn: raw.churn,
+        },
+        ..Default::default()
+    };
+
+    let rules = &src.options.rules;
+    if rules.is_empty() {
+        return Ok(building);
+    }
+
+    let ctx = rules::new_context(blob, src.files, &src.distributions, &src.languages);
+    rules.apply(&ctx, building)
+}
+
+//lay the content of the district out in its `area`,
+//with a squarified treemap
+fn layout_district(id: &str, area: Rect, data: &mut Data, weights: &HashMap<String, f64>) {
+    let (sub_districts, buildings) = match data.districts.get_mut(id) {
+        Some(d) => {
+            d.area = area;
+            (d.sub_districts.to_owned(), d.buildings.to_owned())
+        }
+        None => return,
+    };
+
+    let mut items: Vec<treemap::Item> = sub_districts
+        .iter()
+        .chain(buildings.iter())
+        .map(|id| treemap::Item {
+            id: id.to_owned(),
+            weight: weights.get(id).copied().unwrap_or_default(),
+        })
+        .collect();
+    //the biggest lots are laid out first, the id breaks ties
+    //so that the same repository always gives the same city
+    items.sort_by(|a, b| b.weight.total_cmp(&a.weight).then(a.id.cmp(&b.id)));
+
+    for (id, lot) in treemap::squarify(&items, inset(area, DISTRICT_PADDING)) {
+        if data.districts.contains_key(&id) {
+            layout_district(&id, lot, data, weights);
+        } else if let Some(building) = data.buildings.get_mut(&id) {
+            let margin = lot.width.min(lot.length) * BUILDING_MARGIN;
+            building.footprint = inset(lot, margin);
+        }
+    }
+}
+
+//shrink `rect` by `padding` on each side, as long as it leaves some room
+fn inset(rect: Rect, padding: f64) -> Rect {
+    if rect.width <= 4. * padding || rect.length <= 4. * padding {
+        return rect;
+    }
+
+    Rect {
+        x: rect.x + padding,
+        y: rect.y + padding,
+        width: rect.width - 2. * padding,
+        length: rect.length - 2. * padding,
+    }
+}
@@ -0,0 +1,193 @@
+use super::Rect;
+
+/// Item is a lot of the treemap, its area
+/// is proportional to its weight
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub id: String,
+    pub weight: f64,
+}
+
+/// lay the `items` out in `area`, using the squarified treemap algorithm
+/// from Bruls, Huizing and van Wijk, so that lots stay close to squares.
+///
+/// Items are laid out in the order they are given,
+/// they are expected to be sorted by descending weight
+pub fn squarify(items: &[Item], area: Rect) -> Vec<(String, Rect)> {
+    let total: f64 = items.iter().map(|i| i.weight.max(0.)).sum();
+    if total <= 0. || area.width <= 0. || area.length <= 0. {
+        return items
+            .iter()
+            .map(|i| (i.id.to_owned(), empty(area)))
+            .collect();
+    }
+
+    //scale the weights to areas
+    let scale = area.width * area.length / total;
+    let areas: Vec<(String, f64)> = items
+        .iter()
+        .map(|i| (i.id.to_owned(), i.weight.max(0.) * scale))
+        .collect();
+
+    let mut lots = vec![];
+    let mut free = area;
+    let mut row: Vec<(String, f64)> = vec![];
+    for item in areas {
+        let side = free.width.min(free.length);
+        let mut with_item = row.to_owned();
+        with_item.push(item.to_owned());
+        if row.is_empty() || worst(&with_item, side) <= worst(&row, side) {
+            row = with_item;
+            continue;
+        }
+
+        free = layout_row(&row, free, &mut lots);
+        row = vec![item];
+    }
+    if !row.is_empty() {
+        layout_row(&row, free, &mut lots);
+    }
+
+    lots
+}
+
+//returns the worst aspect ratio of the lots of `row`,
+//laid out along a side of length `side`
+fn worst(row: &[(String, f64)], side: f64) -> f64 {
+    let sum: f64 = row.iter().map(|(_, a)| a).sum();
+    if sum <= 0. || side <= 0. {
+        return f64::MAX;
+    }
+
+    row.iter()
+        .filter(|(_, a)| *a > 0.)
+        .map(|(_, a)| {
+            let ratio = side * side * a / (sum * sum);
+            ratio.max(1. / ratio)
+        })
+        .fold(1., f64::max)
+}
+
+//lay `row` out along the shortest side of `free`,
+//and returns the area left free
+fn layout_row(row: &[(String, f64)], free: Rect, lots: &mut Vec<(String, Rect)>) -> Rect {
+    let sum: f64 = row.iter().map(|(_, a)| a).sum();
+
+    if free.width >= free.length {
+        //a column on the west side
+        let width = if free.length > 0. {
+            sum / free.length
+        } else {
+            0.
+        };
+        let mut y = free.y;
+        for (id, a) in row {
+            let length = if width > 0. { a / width } else { 0. };
+            lots.push((
+                id.to_owned(),
+                Rect {
+                    x: free.x,
+                    y,
+                    width,
+                    length,
+                },
+            ));
+            y += length;
+        }
+
+        Rect {
+            x: free.x + width,
+            width: (free.width - width).max(0.),
+            ..free
+        }
+    } else {
+        //a row on the north side
+        let length = if free.width > 0. {
+            sum / free.width
+        } else {
+            0.
+        };
+        let mut x = free.x;
+        for (id, a) in row {
+            let width = if length > 0. { a / length } else { 0. };
+            lots.push((
+                id.to_owned(),
+                Rect {
+                    x,
+                    y: free.y,
+                    width,
+                    length,
+                },
+            ));
+            x += width;
+        }
+
+        Rect {
+            y: free.y + length,
+            length: (free.length - length).max(0.),
+            ..free
+        }
+    }
+}
+
+fn empty(area: Rect) -> Rect {
+    Rect {
+        x: area.x,
+        y: area.y,
+        width: 0.,
+        length: 0.,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::codecity::treemap::{self, Item};
+    use crate::converters::codecity::Rect;
+
+    #[test]
+    fn squarify_items() {
+        //the example of the squarified treemap paper
+        let items: Vec<Item> = [6., 6., 4., 3., 2., 2., 1.]
+            .iter()
+            .enumerate()
+            .map(|(i, w)| Item {
+                id: i.to_string(),
+                weight: *w,
+            })
+            .collect();
+        let area = Rect {
+            x: 0.,
+            y: 0.,
+            width: 6.,
+            length: 4.,
+        };
+
+        let lots = treemap::squarify(&items, area);
+        assert_eq!(lots.len(), 7);
+        //the first two items fill a 3x4 column on the west side
+        assert_eq!(
+            lots[0].1,
+            Rect {
+                x: 0.,
+                y: 0.,
+                width: 3.,
+                length: 2.
+            }
+        );
+        assert_eq!(lots[1].1.y, 2.);
+
+        let mut covered = 0.;
+        for (i, (id, lot)) in lots.iter().enumerate() {
+            assert_eq!(id, &items[i].id);
+            //every lot keeps its share of the area, inside the area
+            assert!((lot.width * lot.length - items[i].weight).abs() < 1e-9);
+            assert!(lot.x >= 0. && lot.x + lot.width <= 6. + 1e-9);
+            assert!(lot.y >= 0. && lot.y + lot.length <= 4. + 1e-9);
+            covered += lot.width * lot.length;
+        }
+        assert!((covered - 24.).abs() < 1e-9);
+
+        let lots = treemap::squarify(&[], area);
+        assert!(lots.is_empty());
+    }
+}
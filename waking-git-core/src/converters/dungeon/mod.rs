@@ -0,0 +1,482 @@
+use crate::converters::shmup;
+use crate::converters::Converter;
+use crate::error::Error;
+use crate::{converters, extractor, languages};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub struct DungeonConverter {
+    //the dungeon is built from the shmup world
+    shmup: shmup::ShmupConverter,
+}
+
+pub const CONVERTER_NAME: &str = "dungeon";
+
+pub const TILE_VOID: char = ' ';
+pub const TILE_WALL: char = '#';
+pub const TILE_FLOOR: char = '.';
+pub const TILE_DOOR: char = '+';
+pub const TILE_CORRIDOR: char = ':';
+
+pub const PLACEMENT_MONSTER: &str = "monster";
+pub const PLACEMENT_ITEM: &str = "item";
+
+//the language kind of the files that become monsters,
+//the others become items
+const MONSTER_LANGUAGE_KIND: &str = "programming";
+
+//the smallest room, walls excluded
+const MIN_ROOM_SIDE: u32 = 3;
+//the number of floor tiles of a room for each of its entities
+const TILES_PER_ENTITY: f64 = 2.;
+//the extra width a room can randomly get
+const MAX_ROOM_JITTER: u32 = 2;
+//the void between two columns of rooms, where the corridors run
+const CORRIDOR_GAP: u32 = 4;
+//the void between two rooms of the same column
+const ROOM_GAP: u32 = 1;
+//the void around the dungeon
+const MARGIN: u32 = 1;
+
+/// A grid-based dungeon, where the directories are rooms linked
+/// by corridors, and the files are monsters or items
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Data {
+    pub name: String,
+    pub repo_name: String,
+    pub commit: String,
+    //the seed the dungeon was generated from, derived from the commit sha
+    pub seed: u64,
+    pub width: u32,
+    pub height: u32,
+    //the rows of the tile map, one char per tile
+    pub tiles: Vec<String>,
+    //the id of the room of the root directory
+    pub main_room: String,
+    //the tile the player starts from, in the main room
+    pub start: Position,
+    pub rooms: Vec<Room>,
+    pub placements: Vec<Placement>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Room {
+    //the shmup scene id
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    //the id of the parent room, empty for the main room
+    pub parent: String,
+    pub depth: u32,
+    //the top left corner of the room, walls included
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub doors: Vec<Door>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Door {
+    pub x: u32,
+    pub y: u32,
+    //the id of the room the door leads to
+    pub room: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Placement {
+    //the shmup entity id
+    pub id: String,
+    //the id of the room the placement stands in
+    pub room: String,
+    //monster | item
+    pub kind: String,
+    pub name: String,
+    pub path: String,
+    //the char the placement is drawn with
+    pub glyph: String,
+    pub color: String,
+    pub language: String,
+    pub x: u32,
+    pub y: u32,
+    //the hp is a value between [0-1]
+    pub hp: f32,
+    //set when the entity is a shmup boss
+    pub boss: bool,
+}
+
+pub fn new() -> DungeonConverter {
+    DungeonConverter {
+        shmup: shmup::new(),
+    }
+}
+
+pub fn with_options(options: converters::Options) -> DungeonConverter {
+    DungeonConverter {
+        shmup: shmup::with_options(options),
+    }
+}
+
+impl Converter<Data> for DungeonConverter {
+    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, Error> {
+        let shmup_data = self.shmup.run(extracted_data)?;
+        Ok(build_dungeon(extracted_data, &shmup_data))
+    }
+
+    fn name(&self) -> String {
+        CONVERTER_NAME.to_owned()
+    }
+}
+
+//the rooms are laid out as a tree, from west to east:
+//each depth of the scene graph is a column of rooms, and the
+//sub rooms of a room are stacked in the rows it spans
+struct Layout<'a> {
+    scenes: &'a std::collections::BTreeMap<String, shmup::Scene>,
+    rng: StdRng,
+    //the room sizes, walls included
+    sizes: HashMap<String, (u32, u32)>,
+    //the height of the rows spanned by a room and its sub rooms
+    spans: HashMap<String, u32>,
+    //the west side and width of each column
+    columns: Vec<(u32, u32)>,
+    grid: Vec<Vec<char>>,
+    rooms: Vec<Room>,
+}
+
+/// build the dungeon of the scenes and entities of `shmup_data`
+fn build_dungeon(extracted_data: &extractor::Data, shmup_data: &shmup::Data) -> Data {
+    let commit = &extracted_data.git.ref_target.1;
+    let seed = converters::seed_from_commit(commit);
+    let mut data = Data {
+        name: CONVERTER_NAME.to_owned(),
+        repo_name: extracted_data.code.repo_name.to_owned(),
+        commit: commit.to_owned(),
+        seed,
+        main_room: shmup_data.main_scene.to_owned(),
+        ..Default::default()
+    };
+    if !shmup_data.scenes.contains_key(&shmup_data.main_scene) {
+        return data;
+    }
+
+    let mut layout = Layout {
+        scenes: &shmup_data.scenes,
+        rng: StdRng::seed_from_u64(seed),
+        sizes: HashMap::new(),
+        spans: HashMap::new(),
+        columns: vec![],
+        grid: vec![],
+        rooms: vec![],
+    };
+    let main_scene = &shmup_data.main_scene;
+    layout.measure(main_scene, 0);
+
+    let mut x = MARGIN;
+    for column in layout.columns.iter_mut() {
+        column.0 = x;
+        x += column.1 + CORRIDOR_GAP;
+    }
+    data.width = x - CORRIDOR_GAP + MARGIN;
+    data.height = layout.spans[main_scene] - ROOM_GAP + 2 * MARGIN;
+    layout.grid = vec![vec![TILE_VOID; data.width as usize]; data.height as usize];
+    layout.place(main_scene, "", 0, MARGIN);
+
+    let languages = languages::new();
+    let objs = &extracted_data.git.objects;
+    for i in 0..layout.rooms.len() {
+        let room = layout.rooms[i].to_owned();
+        let scene = &shmup_data.scenes[&room.id];
+
+        //every entity gets its own floor tile
+        let mut floor = vec![];
+        for y in room.y + 1..room.y + room.height - 1 {
+            for x in room.x + 1..room.x + room.width - 1 {
+                floor.push(Position { x, y });
+            }
+        }
+        floor.shuffle(&mut layout.rng);
+
+        for (entity, pos) in scene.entities.values().zip(floor) {
+            let path = objs
+                .get(&entity.oid)
+                .and_then(|o| o.blob.as_ref())
+                .map(|b| b.path.to_owned())
+                .unwrap_or_default();
+            let spec = languages::spec_from_path(&languages, &path);
+            data.placements
+                .push(new_placement(entity, &room, path, spec, pos));
+        }
+    }
+
+    let main = &layout.rooms[0];
+    data.start = Position {
+        x: main.x + main.width / 2,
+        y: main.y + main.height / 2,
+    };
+    data.tiles = layout.grid.iter().map(|row| row.iter().collect()).collect();
+    data.rooms = layout.rooms;
+    data
+}
+
+impl Layout<'_> {
+    //size the room of the scene `id` and its sub rooms,
+    //and returns the height of the rows they span
+    fn measure(&mut self, id: &str, depth: usize) -> u32 {
+        let scenes = self.scenes;
+        let scene = &scenes[id];
+        let entities = scene.entities.len() as f64;
+        let side = ((entities * TILES_PER_ENTITY).sqrt().ceil() as u32).max(MIN_ROOM_SIDE);
+        let width = side + self.rng.gen_range(0..=MAX_ROOM_JITTER) + 2;
+        let height = side + 2;
+        self.sizes.insert(id.to_owned(), (width, height));
+
+        if self.columns.len() <= depth {
+            self.columns.push((0, 0));
+        }
+        self.columns[depth].1 = self.columns[depth].1.max(width);
+
+        let mut sub_span = 0;
+        for sub_scene in self.sub_scenes(id) {
+            sub_span += self.measure(&sub_scene, depth + 1);
+        }
+
+        let span = height.max(sub_span) + ROOM_GAP;
+        self.spans.insert(id.to_owned(), span);
+        span
+    }
+
+    //dig the room of the scene `id` at the row `y`, then its sub rooms
+    //and the corridors leading to them. returns the room door, on its west wall
+    fn place(&mut self, id: &str, parent: &str, depth: usize, y: u32) -> Position {
+        let scenes = self.scenes;
+        let scene = &scenes[id];
+        let (width, height) = self.sizes[id];
+        let (column_x, column_width) = self.columns[depth];
+        let x = column_x + self.rng.gen_range(0..=column_width - width);
+
+        for ty in y..y + height {
+            for tx in x..x + width {
+                let border = ty == y || ty == y + height - 1 || tx == x || tx == x + width - 1;
+                self.set(tx, ty, if border { TILE_WALL } else { TILE_FLOOR });
+            }
+        }
+
+        let index = self.rooms.len();
+        self.rooms.push(Room {
+            id: id.to_owned(),
+            name: scene.name.to_owned(),
+            path: scene.path.to_owned(),
+            parent: parent.to_owned(),
+            depth: depth as u32,
+            x,
+            y,
+            width,
+            height,
+            doors: vec![],
+        });
+
+        let west_door = Position {
+            x,
+            y: self.rng.gen_range(y + 1..y + height - 1),
+        };
+        let sub_scenes = self.sub_scenes(id);
+        if sub_scenes.is_empty() {
+            return west_door;
+        }
+
+        //a single east door leads to a trunk corridor,
+        //running in the void between the columns
+        let east_door = Position {
+            x: x + width - 1,
+            y: self.rng.gen_range(y + 1..y + height - 1),
+        };
+        let trunk_x = column_x + column_width + CORRIDOR_GAP / 2;
+        self.dig(east_door.x + 1, trunk_x, east_door.y, true);
+
+        let mut sub_y = y;
+        for sub_scene in sub_scenes {
+            let door = self.place(&sub_scene, id, depth + 1, sub_y);
+            sub_y += self.spans[&sub_scene];
+
+            self.dig(trunk_x, door.x - 1, door.y, true);
+            self.dig(east_door.y, door.y, trunk_x, false);
+            self.set(door.x, door.y, TILE_DOOR);
+            self.rooms[index].doors.push(Door {
+                x: east_door.x,
+                y: east_door.y,
+                room: sub_scene.to_owned(),
+            });
+            let sub_room = self.rooms.iter_mut().find(|r| r.id == sub_scene);
+            if let Some(r) = sub_room {
+                r.doors.push(Door {
+                    x: door.x,
+                    y: door.y,
+                    room: id.to_owned(),
+                });
+            }
+        }
+        self.set(east_door.x, east_door.y, TILE_DOOR);
+
+        west_door
+    }
+
+    //the sub scenes of the scene `id` that are part of the world
+    fn sub_scenes(&self, id: &str) -> Vec<String> {
+        self.scenes[id]
+            .sub_scenes
+            .iter()
+            .filter(|s| self.scenes.contains_key(*s))
+            .cloned()
+            .collect()
+    }
+
+    //dig a corridor from `from` to `to`, along the row `at`
+    //when `horizontal` is set, along the column `at` otherwise
+    fn dig(&mut self, from: u32, to: u32, at: u32, horizontal: bool) {
+        for i in from.min(to)..=from.max(to) {
+            let (x, y) = if horizontal { (i, at) } else { (at, i) };
+            if self.grid[y as usize][x as usize] == TILE_VOID {
+                self.set(x, y, TILE_CORRIDOR);
+            }
+        }
+    }
+
+    fn set(&mut self, x: u32, y: u32, tile: char) {
+        self.grid[y as usize][x as usize] = tile;
+    }
+}
+
+/// build the placement of an entity, the files of programming
+/// languages are monsters, the others are items
+fn new_placement(
+    entity: &shmup::Entity,
+    room: &Room,
+    path: String,
+    spec: languages::Spec,
+    pos: Position,
+) -> Placement {
+    let boss = entity.boss.is_some();
+    let kind = match spec.kind.as_str() {
+        MONSTER_LANGUAGE_KIND => PLACEMENT_MONSTER,
+        _ => PLACEMENT_ITEM,
+    };
+    let glyph = match kind {
+        //monsters are drawn with the first letter of their language,
+        //in upper case for the bosses
+        PLACEMENT_MONSTER => {
+            let c = spec.name.chars().next().unwrap_or('m');
+            match boss {
+                true => c.to_ascii_uppercase(),
+                false => c.to_ascii_lowercase(),
+            }
+        }
+        _ => match spec.kind.as_str() {
+            "data" => '!',
+            "markup" | "prose" => '?',
+            _ => '*',
+        },
+    };
+
+    Placement {
+        id: entity.id.to_owned(),
+        room: room.id.to_owned(),
+        kind: kind.to_owned(),
+        name: entity.name.to_owned(),
+        path,
+        glyph: glyph.to_string(),
+        color: entity.color.to_owned(),
+        language: spec.name,
+        x: pos.x,
+        y: pos.y,
+        hp: entity.hp,
+        boss,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::dungeon;
+    use crate::converters::shmup;
+    use crate::extractor;
+    use std::collections::{HashSet, VecDeque};
+
+    fn scene(id: &str, entities: usize, sub_scenes: &[&str]) -> shmup::Scene {
+        let mut s = shmup::Scene {
+            id: id.to_string(),
+            name: id.to_string(),
+            sub_scenes: sub_scenes.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        for i in 0..entities {
+            let id = format!("{id}-{i}");
+            s.entities.insert(
+                id.to_owned(),
+                shmup::Entity {
+                    id,
+                    ..Default::default()
+                },
+            );
+        }
+        s
+    }
+
+    #[test]
+    fn build_connected_dungeon() {
+        let mut extracted = extractor::Data::default();
+        extracted.git.ref_target.1 = "3f786850e387550fdab836ed7e6dc881de23001b".to_string();
+        let mut shmup_data = shmup::Data {
+            main_scene: "root".to_string(),
+            ..Default::default()
+        };
+        for s in [
+            scene("root", 2, &["src", "docs"]),
+            scene("src", 40, &["cmd", "utils"]),
+            scene("cmd", 3, &[]),
+            scene("utils", 0, &[]),
+            scene("docs", 5, &[]),
+        ] {
+            shmup_data.scenes.insert(s.id.to_owned(), s);
+        }
+
+        let data = dungeon::build_dungeon(&extracted, &shmup_data);
+        assert_eq!(data.rooms.len(), 5);
+        assert_eq!(data.placements.len(), 50);
+        assert_eq!(data.tiles.len(), data.height as usize);
+        assert!(data.tiles.iter().all(|r| r.len() == data.width as usize));
+
+        //the same commit always gives the same dungeon
+        let again = dungeon::build_dungeon(&extracted, &shmup_data);
+        assert_eq!(data.tiles, again.tiles);
+
+        //every placement can be reached from the start
+        let tiles: Vec<Vec<char>> = data.tiles.iter().map(|r| r.chars().collect()).collect();
+        let mut seen = HashSet::from([(data.start.x, data.start.y)]);
+        let mut queue = VecDeque::from([(data.start.x, data.start.y)]);
+        while let Some((x, y)) = queue.pop_front() {
+            for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                let tile = tiles[ny as usize][nx as usize];
+                if tile != dungeon::TILE_WALL && tile != dungeon::TILE_VOID && seen.insert((nx, ny))
+                {
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        for p in &data.placements {
+            assert!(seen.contains(&(p.x, p.y)), "{} is unreachable", p.id);
+        }
+        let positions: HashSet<(u32, u32)> = data.placements.iter().map(|p| (p.x, p.y)).collect();
+        assert_eq!(positions.len(), data.placements.len());
+    }
+}
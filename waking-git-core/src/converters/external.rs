@@ -0,0 +1,400 @@
+use crate::converters::{self, Converter};
+use crate::error::Error;
+use crate::{extractor, formats, schemas};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+//the time an external converter is given to convert the data, in seconds
+pub const DEFAULT_TIMEOUT: u64 = 60;
+//the environment variable holding the format of the data sent on stdin
+pub const FORMAT_ENV_VAR: &str = "WAKE_CONVERTER_FORMAT";
+
+//the number of violations reported when the output does not match its schema
+const MAX_REPORTED_VIOLATIONS: usize = 5;
+
+/// Spec describes an external converter.
+///
+/// The converter receives the extracted data on its stdin, encoded in
+/// `format`, and writes the converted data on its stdout, in any of the
+/// formats supported by wake.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Spec {
+    //the name of the converter, used to name the converted file
+    pub name: String,
+    //the executable run to convert the data
+    pub cmd: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    //the time after which the converter is killed, in seconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    //the path to the JSON schema the converted data must follow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    //the format of the extracted data sent on stdin, json by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+}
+
+/// Registry lists the external converters that can be used by name.
+///
+/// Here is an example of a registry file:
+///
+/// ```yaml
+/// converters:
+///   - name: my-world
+///     cmd: /usr/local/bin/my-converter
+///     args: ["--pretty"]
+///     timeout: 30
+///     schema: /usr/local/share/my-world.schema.json
+/// ```
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
+pub struct Registry {
+    #[serde(default)]
+    pub converters: Vec<Spec>,
+}
+
+pub struct ExternalConverter {
+    spec: Spec,
+    format: formats::Format,
+    schema: Option<serde_json::Value>,
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT
+}
+
+/// returns the spec of an external converter run with `cmd`,
+/// named after the executable file name
+pub fn new_spec(cmd: &str) -> Spec {
+    let name = Path::new(cmd)
+        .file_stem()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    Spec {
+        name,
+        cmd: cmd.to_owned(),
+        args: vec![],
+        timeout: DEFAULT_TIMEOUT,
+        schema: None,
+        format: None,
+    }
+}
+
+/// build the converter described by `spec`,
+/// loading the schema of its output if one is declared
+pub fn new(spec: Spec) -> Result<ExternalConverter, Error> {
+    if spec.name.is_empty() {
+        return Err(Error::parse(format!(
+            "the converter `{}` has no name",
+            spec.cmd
+        )));
+    }
+
+    let format = match &spec.format {
+        Some(f) => formats::Format::from_name(f)?,
+        None => formats::Format::default(),
+    };
+
+    let schema = match &spec.schema {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(s) => Some(s),
+                Err(err) => {
+                    return Err(Error::Parse {
+                        message: format!("invalid schema file `{path}`"),
+                        source: Some(Box::new(err)),
+                    })
+                }
+            },
+            Err(err) => {
+                return Err(Error::io(
+                    format!("failed to read schema file `{path}`"),
+                    err,
+                ))
+            }
+        },
+        None => None,
+    };
+
+    Ok(ExternalConverter {
+        spec,
+        format,
+        schema,
+    })
+}
+
+/// load the registry file located at `path`,
+/// a missing file is an empty registry
+pub fn load_registry(path: &str) -> Result<Registry, Error> {
+    if !Path::new(path).exists() {
+        return Ok(Registry::default());
+    }
+
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(err) => {
+            return Err(Error::io(
+                format!("failed to read converters file `{path}`"),
+                err,
+            ))
+        }
+    };
+
+    match serde_yaml::from_str(&content) {
+        Ok(r) => Ok(r),
+        Err(err) => Err(Error::Parse {
+            message: format!("invalid converters file `{path}`"),
+            source: Some(Box::new(err)),
+        }),
+    }
+}
+
+impl Registry {
+    /// returns the spec of the converter named `name`
+    pub fn find(&self, name: &str) -> Option<&Spec> {
+        self.converters.iter().find(|c| c.name == name)
+    }
+
+    /// add `spec` to the registry, replacing the converter with the same name.
+    /// the builtin converters are run first, their names cannot be registered
+    pub fn register(&mut self, spec: Spec) -> Result<(), Error> {
+        if converters::builtin_names().any(|n| n == spec.name) {
+            return Err(Error::parse(format!(
+                "`{}` is the name of a builtin converter, pick another name for the external converter",
+                spec.name
+            )));
+        }
+        match self.converters.iter_mut().find(|c| c.name == spec.name) {
+            Some(c) => *c = spec,
+            None => self.converters.push(spec),
+        }
+        Ok(())
+    }
+
+    /// write the registry to the file located at `path`
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let content = match serde_yaml::to_string(self) {
+            Ok(c) => c,
+            Err(err) => return Err(Error::serialize("failed to serialize converters", err)),
+        };
+
+        if let Some(dir) = Path::new(path).parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                return Err(Error::io(
+                    format!("failed to create `{}`", dir.display()),
+                    err,
+                ));
+            }
+        }
+
+        match fs::write(path, content) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::io(
+                format!("failed to write converters file `{path}`"),
+                err,
+            )),
+        }
+    }
+}
+
+impl Converter<serde_json::Value> for ExternalConverter {
+    fn run(&self, extracted_data: &extractor::Data) -> Result<serde_json::Value, Error> {
+        let input = formats::encode(extracted_data, self.format)?;
+        let output = match self.exec(input) {
+            Ok(o) => o,
+            Err(err) => return Err(Error::convert(err)),
+        };
+
+        let data: serde_json::Value = match formats::decode(&output) {
+            Ok(d) => d,
+            Err(err) => {
+                return Err(Error::Convert {
+                    message: format!("`{}` converter wrote an invalid output", self.spec.name),
+                    source: Some(Box::new(err)),
+                })
+            }
+        };
+
+        if let Some(schema) = &self.schema {
+            let violations = schemas::validate_with(schema, &data)?;
+            if !violations.is_empty() {
+                let reported: Vec<String> = violations
+                    .iter()
+                    .take(MAX_REPORTED_VIOLATIONS)
+                    .map(|v| v.to_string())
+                    .collect();
+                return Err(Error::convert(format!(
+                    "`{}` converter output does not match its schema, {} violation(s): {}",
+                    self.spec.name,
+                    violations.len(),
+                    reported.join(", ")
+                )));
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn name(&self) -> String {
+        self.spec.name.to_owned()
+    }
+}
+
+impl ExternalConverter {
+    //run the converter command, sending `input` on its stdin,
+    //and return what it wrote on its stdout
+    fn exec(&self, input: Vec<u8>) -> Result<Vec<u8>, String> {
+        let name = &self.spec.name;
+        let mut child = match Command::new(&self.spec.cmd)
+            .args(&self.spec.args)
+            .env(FORMAT_ENV_VAR, self.format.extension())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(c) => c,
+            Err(err) => return Err(format!("failed to run `{}`: {err}", self.spec.cmd)),
+        };
+
+        //stdin and stdout are handled in their own threads, so that
+        //a converter writing before reading all its input can't block
+        let mut stdin = child.stdin.take();
+        let writer = thread::spawn(move || match stdin.as_mut() {
+            Some(s) => s.write_all(&input),
+            None => Ok(()),
+        });
+        let stdout = child.stdout.take();
+        let reader = thread::spawn(move || read_all(stdout));
+        let stderr = child.stderr.take();
+        let err_reader = thread::spawn(move || read_all(stderr));
+
+        let timeout = Duration::from_secs(self.spec.timeout);
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if started.elapsed() >= timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!(
+                        "`{name}` converter timed out after {}s",
+                        self.spec.timeout
+                    ));
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(err) => return Err(format!("failed to wait for `{name}` converter: {err}")),
+            }
+        };
+
+        let output = reader.join().unwrap_or_default();
+        let errors = err_reader.join().unwrap_or_default();
+        if !status.success() {
+            return Err(format!(
+                "`{name}` converter failed with {status}: {}",
+                String::from_utf8_lossy(&errors).trim()
+            ));
+        }
+
+        //the converter may exit without reading its whole input
+        if let Ok(Err(err)) = writer.join() {
+            if output.is_empty() {
+                return Err(format!("failed to send data to `{name}` converter: {err}"));
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+fn read_all(stream: Option<impl Read>) -> Vec<u8> {
+    let mut buf = vec![];
+    if let Some(mut s) = stream {
+        let _ = s.read_to_end(&mut buf);
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::external::{self, Registry};
+    use crate::converters::Converter;
+    use crate::extractor;
+    use crate::utils::test;
+
+    fn sh(script: &str) -> external::Spec {
+        external::Spec {
+            name: "test".to_string(),
+            cmd: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout: 5,
+            schema: None,
+            format: None,
+        }
+    }
+
+    #[test]
+    fn run_external_converter() {
+        let data = extractor::Data::default();
+
+        //the converter reads the extracted data and writes its world
+        let conv = external::new(sh("cat > /dev/null; echo '{\"world\": 42}'")).unwrap();
+        let converted = conv.run(&data).unwrap();
+        assert_eq!(converted["world"], 42);
+        assert_eq!(conv.name(), "test");
+
+        let conv = external::new(sh("echo 'boom' >&2; exit 3")).unwrap();
+        let err = conv.run(&data).unwrap_err();
+        assert!(err.to_string().contains("boom"), "{err}");
+
+        let conv = external::new(sh("echo 'not json'")).unwrap();
+        assert!(conv.run(&data).is_err());
+
+        let mut spec = sh("sleep 5");
+        spec.timeout = 0;
+        let conv = external::new(spec).unwrap();
+        let err = conv.run(&data).unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{err}");
+    }
+
+    #[test]
+    fn check_output_schema() {
+        let dir = test::temp_dir();
+        let schema_path = dir.path.join("schema.json");
+        std::fs::write(&schema_path, r#"{"type": "object", "required": ["world"]}"#).unwrap();
+
+        let mut spec = sh("echo '{\"map\": 1}'");
+        spec.schema = Some(schema_path.to_string_lossy().into_owned());
+        let conv = external::new(spec).unwrap();
+        let err = conv.run(&extractor::Data::default()).unwrap_err();
+        assert!(
+            err.to_string().contains("does not match its schema"),
+            "{err}"
+        );
+
+        //registered converters are replaced by name
+        let registry_path = dir.path.join("converters.yaml");
+        let registry_path = registry_path.to_str().unwrap();
+        let mut registry = external::load_registry(registry_path).unwrap();
+        registry.register(sh("true")).unwrap();
+        registry.register(sh("false")).unwrap();
+        registry.save(registry_path).unwrap();
+
+        //the builtin converters would be run instead
+        let mut spec = sh("true");
+        spec.name = "graph".to_string();
+        let err = registry.register(spec).unwrap_err();
+        assert!(err.to_string().contains("builtin converter"), "{err}");
+
+        let registry: Registry = external::load_registry(registry_path).unwrap();
+        assert_eq!(registry.converters.len(), 1);
+        assert_eq!(registry.find("test").unwrap().args[1], "false");
+    }
+}
@@ -0,0 +1,81 @@
+use super::{Data, Edge, Node, EDGE_COUPLING};
+use crate::shapes;
+use std::fmt::Write;
+
+pub const EXTENSION: &str = "dot";
+
+/// render the graph in the GraphViz DOT language
+pub fn render(data: &Data) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph {} {{", quote(&data.repo_name));
+    let _ = writeln!(out, "  graph [rankdir=LR];");
+    let _ = writeln!(out, "  node [style=filled];");
+
+    for node in &data.nodes {
+        let _ = writeln!(out, "  {} [{}];", quote(&node.id), node_attributes(node));
+    }
+
+    for edge in &data.edges {
+        let _ = writeln!(
+            out,
+            "  {} -> {}{};",
+            quote(&edge.source),
+            quote(&edge.target),
+            edge_attributes(edge)
+        );
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn node_attributes(node: &Node) -> String {
+    let attributes = [
+        ("label", quote(&node.label)),
+        ("kind", quote(&node.kind)),
+        ("path", quote(&node.path)),
+        ("shape", shape(&node.shape).to_owned()),
+        ("fillcolor", quote(&node.color)),
+        ("hp", node.hp.to_string()),
+        ("size", node.size.to_string()),
+        ("speed", node.speed.to_string()),
+        ("bytes", node.metrics.size.to_string()),
+        ("loc", node.metrics.loc.to_string()),
+        ("complexity", node.metrics.complexity.to_string()),
+        ("churn", node.metrics.churn.to_string()),
+    ];
+
+    attributes
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn edge_attributes(edge: &Edge) -> String {
+    match edge.kind.as_str() {
+        //coupling goes both ways
+        EDGE_COUPLING => format!(
+            " [kind={}, weight={}, dir=none, style=dashed]",
+            quote(&edge.kind),
+            edge.weight
+        ),
+        _ => "".to_owned(),
+    }
+}
+
+//map the shmup entity shapes to the GraphViz ones
+fn shape(shape: &str) -> &str {
+    match shape {
+        shapes::CIRCLE => "circle",
+        shapes::RECTANGLE => "box",
+        shapes::TRIANGLE => "triangle",
+        shapes::HEXAGON => "hexagon",
+        "" => "ellipse",
+        s => s,
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
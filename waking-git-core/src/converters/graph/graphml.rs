@@ -0,0 +1,102 @@
+use super::{Data, Edge, Node};
+use std::fmt::Write;
+
+pub const EXTENSION: &str = "graphml";
+
+//the attributes of the nodes and edges, as (id, type)
+const NODE_KEYS: [(&str, &str); 12] = [
+    ("kind", "string"),
+    ("label", "string"),
+    ("path", "string"),
+    ("shape", "string"),
+    ("color", "string"),
+    ("hp", "double"),
+    ("size", "double"),
+    ("speed", "double"),
+    ("bytes", "long"),
+    ("loc", "double"),
+    ("complexity", "double"),
+    ("churn", "double"),
+];
+const EDGE_KEYS: [(&str, &str); 2] = [("edge_kind", "string"), ("weight", "int")];
+
+/// render the graph in the GraphML format
+pub fn render(data: &Data) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    for (id, kind) in NODE_KEYS {
+        let _ = writeln!(
+            out,
+            "  <key id=\"{id}\" for=\"node\" attr.name=\"{id}\" attr.type=\"{kind}\"/>"
+        );
+    }
+    for (id, kind) in EDGE_KEYS {
+        let name = id.trim_start_matches("edge_");
+        let _ = writeln!(
+            out,
+            "  <key id=\"{id}\" for=\"edge\" attr.name=\"{name}\" attr.type=\"{kind}\"/>"
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        "  <graph id=\"{}\" edgedefault=\"directed\">",
+        escape(&data.repo_name)
+    );
+    for node in &data.nodes {
+        render_node(&mut out, node);
+    }
+    for (i, edge) in data.edges.iter().enumerate() {
+        render_edge(&mut out, i, edge);
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+fn render_node(out: &mut String, node: &Node) {
+    let values = [
+        node.kind.to_owned(),
+        node.label.to_owned(),
+        node.path.to_owned(),
+        node.shape.to_owned(),
+        node.color.to_owned(),
+        node.hp.to_string(),
+        node.size.to_string(),
+        node.speed.to_string(),
+        node.metrics.size.to_string(),
+        node.metrics.loc.to_string(),
+        node.metrics.complexity.to_string(),
+        node.metrics.churn.to_string(),
+    ];
+
+    let _ = writeln!(out, "    <node id=\"{}\">", escape(&node.id));
+    for ((key, _), value) in NODE_KEYS.iter().zip(values) {
+        let _ = writeln!(out, "      <data key=\"{key}\">{}</data>", escape(&value));
+    }
+    out.push_str("    </node>\n");
+}
+
+fn render_edge(out: &mut String, i: usize, edge: &Edge) {
+    let _ = writeln!(
+        out,
+        "    <edge id=\"e{i}\" source=\"{}\" target=\"{}\">",
+        escape(&edge.source),
+        escape(&edge.target)
+    );
+    let _ = writeln!(
+        out,
+        "      <data key=\"edge_kind\">{}</data>",
+        escape(&edge.kind)
+    );
+    let _ = writeln!(out, "      <data key=\"weight\">{}</data>", edge.weight);
+    out.push_str("    </edge>\n");
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
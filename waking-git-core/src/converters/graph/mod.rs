@@ -0,0 +1,286 @@
+use crate::converters::Converter;
+use crate::converters::{metrics, shmup};
+use crate::error::Error;
+use crate::{converters, extractor, hash, repo, utils};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub mod dot;
+pub mod graphml;
+
+pub struct GraphConverter {
+    //the graph is built from the shmup world
+    shmup: shmup::ShmupConverter,
+}
+
+pub const CONVERTER_NAME: &str = "graph";
+
+pub const NODE_SCENE: &str = "scene";
+pub const NODE_ENTITY: &str = "entity";
+//a scene contains its sub scenes and its entities
+pub const EDGE_CONTAINS: &str = "contains";
+//two entities are coupled when their files are often modified together
+pub const EDGE_COUPLING: &str = "coupling";
+
+//the shape and color of the scene nodes
+const SCENE_SHAPE: &str = "folder";
+const SCENE_COLOR: &str = "#8b949e";
+
+/// The scene graph of the repository, as built by the shmup converter
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Data {
+    pub name: String,
+    pub repo_name: String,
+    pub commit: String,
+    //the id of the main scene node
+    pub main_scene: String,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Node {
+    //the scene or entity id
+    pub id: String,
+    //scene | entity
+    pub kind: String,
+    pub label: String,
+    //the path of the directory or file relative to the root of the repository
+    pub path: String,
+    //the shmup entity shape, `folder` for scenes
+    pub shape: String,
+    pub color: String,
+    //the shmup entity attributes, values between [0-1], 0 for scenes
+    pub hp: f32,
+    pub size: f32,
+    pub speed: f32,
+    //the metrics of the file, aggregated over the
+    //sub scenes and entities for scenes
+    pub metrics: metrics::Metrics,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    //contains | coupling
+    pub kind: String,
+    //the number of commits shared by coupled entities, 1 otherwise
+    pub weight: u32,
+}
+
+pub fn new() -> GraphConverter {
+    GraphConverter {
+        shmup: shmup::new(),
+    }
+}
+
+pub fn with_options(options: converters::Options) -> GraphConverter {
+    GraphConverter {
+        shmup: shmup::with_options(options),
+    }
+}
+
+impl Converter<Data> for GraphConverter {
+    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, Error> {
+        let shmup_data = self.shmup.run(extracted_data)?;
+        Ok(build_graph(extracted_data, &shmup_data))
+    }
+
+    fn name(&self) -> String {
+        CONVERTER_NAME.to_owned()
+    }
+}
+
+/// build the graph of the scenes and entities of `shmup_data`
+fn build_graph(extracted_data: &extractor::Data, shmup_data: &shmup::Data) -> Data {
+    let objs = &extracted_data.git.objects;
+    let files = &extracted_data.code.files_data;
+    let mut data = Data {
+        name: CONVERTER_NAME.to_owned(),
+        repo_name: extracted_data.code.repo_name.to_owned(),
+        commit: extracted_data.git.ref_target.1.to_owned(),
+        main_scene: shmup_data.main_scene.to_owned(),
+        ..Default::default()
+    };
+
+    for scene in shmup_data.scenes.values() {
+        data.nodes.push(Node {
+            id: scene.id.to_owned(),
+            kind: NODE_SCENE.to_owned(),
+            label: match scene.name.as_str() {
+                "" => "/".to_owned(),
+                name => name.to_owned(),
+            },
+            path: scene.path.to_owned(),
+            shape: SCENE_SHAPE.to_owned(),
+            color: SCENE_COLOR.to_owned(),
+            metrics: metrics::Metrics {
+                size: scene.stats.size,
+                loc: scene.stats.loc,
+                complexity: scene.stats.complexity,
+                churn: scene.stats.churn,
+            },
+            ..Default::default()
+        });
+
+        for sub_scene in &scene.sub_scenes {
+            data.edges.push(contains(&scene.id, sub_scene));
+        }
+
+        for entity in scene.entities.values() {
+            let blob = objs.get(&entity.oid).and_then(|o| o.blob.as_ref());
+            let (path, metrics) = match blob {
+                Some(b) => {
+                    let m = metrics::file_metrics(b, files);
+                    let metrics = metrics::Metrics {
+                        size: b.size,
+                        loc: m.loc,
+                        complexity: m.complexity,
+                        churn: m.churn,
+                    };
+                    (b.path.to_owned(), metrics)
+                }
+                None => (String::new(), metrics::Metrics::default()),
+            };
+
+            data.nodes.push(Node {
+                id: entity.id.to_owned(),
+                kind: NODE_ENTITY.to_owned(),
+                label: entity.name.to_owned(),
+                path,
+                shape: entity.kind.to_owned(),
+                color: entity.color.to_owned(),
+                hp: entity.hp,
+                size: entity.size,
+                speed: entity.speed,
+                metrics,
+            });
+            data.edges.push(contains(&scene.id, &entity.id));
+        }
+    }
+
+    //only couple the entities that made it to the graph
+    let ids: std::collections::HashSet<&String> = data.nodes.iter().map(|n| &n.id).collect();
+    let mut coupling = vec![];
+    for c in &extracted_data.git.coupling {
+        let (a, b) = (hash::new(c.a.to_owned()), hash::new(c.b.to_owned()));
+        if ids.contains(&a) && ids.contains(&b) {
+            coupling.push(Edge {
+                source: a,
+                target: b,
+                kind: EDGE_COUPLING.to_owned(),
+                weight: c.commits,
+            });
+        }
+    }
+    data.edges.extend(coupling);
+    data
+}
+
+fn contains(source: &str, target: &str) -> Edge {
+    Edge {
+        source: source.to_owned(),
+        target: target.to_owned(),
+        kind: EDGE_CONTAINS.to_owned(),
+        weight: 1,
+    }
+}
+
+/// write the graph as DOT and GraphML files,
+/// next to the converted file of `git_repo`
+pub fn export(git_repo: &repo::Repo, data: &Data) -> Result<(), Error> {
+    let exports = [
+        (dot::EXTENSION, dot::render(data)),
+        (graphml::EXTENSION, graphml::render(data)),
+    ];
+
+    for (extension, content) in exports {
+        let dest_path = format!("{}/{CONVERTER_NAME}.{extension}", git_repo.scanner_path);
+        if let Err(err) = utils::store_data(
+            content.as_bytes(),
+            git_repo.scanner_path.to_owned(),
+            &dest_path,
+        ) {
+            return Err(Error::io(format!("failed to write `{dest_path}`"), err));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::graph::{self, dot, graphml};
+    use crate::converters::shmup;
+    use crate::extractor::{self, git};
+    use crate::hash;
+
+    #[test]
+    fn build_scene_graph() {
+        let mut extracted = extractor::Data::default();
+        extracted.git.coupling = vec![
+            git::Coupling {
+                a: "src/a.rs".to_string(),
+                b: "src/b.rs".to_string(),
+                commits: 3,
+            },
+            //files dropped from the world are not coupled
+            git::Coupling {
+                a: "src/a.rs".to_string(),
+                b: "src/c.rs".to_string(),
+                commits: 2,
+            },
+        ];
+
+        let mut shmup_data = shmup::Data {
+            main_scene: "root".to_string(),
+            ..Default::default()
+        };
+        let mut root = shmup::Scene {
+            id: "root".to_string(),
+            sub_scenes: vec!["src".to_string()],
+            ..Default::default()
+        };
+        let mut src = shmup::Scene {
+            id: "src".to_string(),
+            name: "src".to_string(),
+            path: "src".to_string(),
+            ..Default::default()
+        };
+        for path in ["src/a.rs", "src/b.rs"] {
+            let id = hash::new(path.to_string());
+            src.entities.insert(
+                id.to_owned(),
+                shmup::Entity {
+                    id,
+                    name: path.to_string(),
+                    kind: "circle".to_string(),
+                    color: "#dea584".to_string(),
+                    ..Default::default()
+                },
+            );
+        }
+        root.name = "".to_string();
+        shmup_data.scenes.insert("root".to_string(), root);
+        shmup_data.scenes.insert("src".to_string(), src);
+
+        let data = graph::build_graph(&extracted, &shmup_data);
+        assert_eq!(data.nodes.len(), 4);
+        assert_eq!(data.nodes[0].label, "/");
+        let kinds: Vec<&str> = data.edges.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["contains", "contains", "contains", "coupling"]);
+        assert_eq!(data.edges[3].weight, 3);
+
+        let dot = dot::render(&data);
+        assert!(dot.starts_with("digraph \"\" {"));
+        assert!(dot.contains("\"root\" -> \"src\";"));
+        assert!(dot.contains("shape=circle"));
+        assert!(dot.contains("dir=none"));
+
+        let graphml = graphml::render(&data);
+        assert!(graphml.contains("<node id=\"src\">"));
+        assert!(graphml.contains("<data key=\"color\">#dea584</data>"));
+        assert_eq!(graphml.matches("<edge ").count(), 4);
+    }
+}
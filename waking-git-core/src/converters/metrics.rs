@@ -0,0 +1,272 @@
+use crate::extractor;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Metrics of a single file, extracted from the git
+/// and code data of the repository
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct FileMetrics {
+    //sum of the cyclomatic complexity of every space of the file
+    pub complexity: f64,
+    //source lines of code
+    pub loc: f64,
+    //number of functions declared in the file
+    pub functions: f64,
+    //number of comment lines
+    pub comments: f64,
+    //number of commits that modified the file
+    pub churn: f64,
+}
+
+/// Metrics of the files an entity, a building or a node is built from
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone, PartialEq)]
+pub struct Metrics {
+    //size of the files in bytes
+    pub size: u64,
+    //source lines of code
+    pub loc: f64,
+    //cyclomatic complexity
+    pub complexity: f64,
+    //number of commits that modified the files
+    pub churn: f64,
+}
+
+/// Stats of a scene, aggregated over the scene and its sub scenes
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone, PartialEq)]
+pub struct SceneStats {
+    //number of entities of the scene and its sub scenes
+    pub entities: u32,
+    //number of scenes under the scene
+    pub scenes: u32,
+    //size of the files in bytes
+    #[serde(default)]
+    pub size: u64,
+    pub loc: f64,
+    pub complexity: f64,
+    pub churn: f64,
+}
+
+/// Scenes that hold stats aggregated over their sub scenes
+pub trait StatsScene {
+    fn sub_scenes(&self) -> &Vec<String>;
+    fn stats_mut(&mut self) -> &mut SceneStats;
+}
+
+/// Distribution holds the sorted values a metric takes
+/// across the repository
+#[derive(Default, Debug)]
+pub struct Distribution {
+    values: Vec<f64>,
+}
+
+/// Distributions of every file metric across the repository.
+/// They are used to normalize file metrics to [0-1].
+#[derive(Default, Debug)]
+pub struct Distributions {
+    pub complexity: Distribution,
+    pub loc: Distribution,
+    pub functions: Distribution,
+    pub comments: Distribution,
+    pub churn: Distribution,
+}
+
+impl Distribution {
+    pub fn new(mut values: Vec<f64>) -> Distribution {
+        values.sort_by(|a, b| a.total_cmp(b));
+        Distribution { values }
+    }
+
+    /// returns the percentile rank of `value` in the distribution,
+    /// a value between [0-1].
+    ///
+    /// Values equal to each others share the same rank, so that a
+    /// repository where every file has the same metric value
+    /// normalizes them all to 0.5
+    pub fn normalize(&self, value: f64) -> f32 {
+        if self.values.is_empty() {
+            return 0.;
+        }
+
+        let below = self.values.partition_point(|v| *v < value);
+        let below_or_equal = self.values.partition_point(|v| *v <= value);
+        ((below + below_or_equal) as f64 / (2. * self.values.len() as f64)) as f32
+    }
+}
+
+impl Distributions {
+    /// returns a copy of `metrics` where each metric is normalized
+    /// against its repository-wide distribution
+    pub fn normalize(&self, metrics: &FileMetrics) -> FileMetrics {
+        FileMetrics {
+            complexity: self.complexity.normalize(metrics.complexity) as f64,
+            loc: self.loc.normalize(metrics.loc) as f64,
+            functions: self.functions.normalize(metrics.functions) as f64,
+            comments: self.comments.normalize(metrics.comments) as f64,
+            churn: self.churn.normalize(metrics.churn) as f64,
+        }
+    }
+}
+
+impl SceneStats {
+    /// add the metrics of a file entity to the stats
+    pub fn add_file(&mut self, blob: &extractor::git::Blob, metrics: &FileMetrics) {
+        self.entities += 1;
+        self.size += blob.size;
+        self.loc += metrics.loc;
+        self.complexity += metrics.complexity;
+        self.churn += metrics.churn;
+    }
+
+    /// add the stats of a sub scene, counting the sub scene itself
+    pub fn add_sub_scene(&mut self, stats: &SceneStats) {
+        self.entities += stats.entities;
+        self.scenes += stats.scenes + 1;
+        self.size += stats.size;
+        self.loc += stats.loc;
+        self.complexity += stats.complexity;
+        self.churn += stats.churn;
+    }
+}
+
+/// add the stats of every sub scenes to the stats of their parent,
+/// and return the aggregated stats of `scene_id`
+pub fn aggregate_stats<S: StatsScene>(
+    scene_id: &str,
+    scenes: &mut BTreeMap<String, S>,
+) -> SceneStats {
+    let sub_scenes = match scenes.get(scene_id) {
+        Some(scene) => scene.sub_scenes().to_owned(),
+        None => return SceneStats::default(),
+    };
+
+    let mut sub_stats = SceneStats::default();
+    for id in &sub_scenes {
+        let stats = aggregate_stats(id, scenes);
+        sub_stats.add_sub_scene(&stats);
+    }
+
+    match scenes.get_mut(scene_id) {
+        Some(scene) => {
+            let stats = scene.stats_mut();
+            stats.entities += sub_stats.entities;
+            stats.scenes += sub_stats.scenes;
+            stats.size += sub_stats.size;
+            stats.loc += sub_stats.loc;
+            stats.complexity += sub_stats.complexity;
+            stats.churn += sub_stats.churn;
+            stats.to_owned()
+        }
+        None => SceneStats::default(),
+    }
+}
+
+/// returns the metrics of the file pointed by `blob`.
+/// Files that could not be analysed only carry git metrics
+pub fn file_metrics(
+    blob: &extractor::git::Blob,
+    files: &HashMap<String, extractor::code::FileData>,
+) -> FileMetrics {
+    let mut metrics = match files.get(&blob.path_sha) {
+        Some(file) => code_metrics(file),
+        None => FileMetrics::default(),
+    };
+    metrics.churn = blob.churn as f64;
+    metrics
+}
+
+fn code_metrics(file: &extractor::code::FileData) -> FileMetrics {
+    let m = &file.spaces.metrics;
+    FileMetrics {
+        complexity: m.cyclomatic.cyclomatic_sum(),
+        loc: m.loc.sloc(),
+        functions: m.nom.functions_sum(),
+        comments: m.loc.cloc(),
+        churn: 0.,
+    }
+}
+
+/// build the distributions of the repository file metrics.
+///
+/// Code metrics are only distributed over files that could be
+/// analysed, whereas churn is distributed over every blob.
+pub fn new_distributions(extracted_data: &extractor::Data) -> Distributions {
+    let files = &extracted_data.code.files_data;
+    let code: Vec<FileMetrics> = files.values().map(code_metrics).collect();
+    let churn = extracted_data
+        .git
+        .objects
+        .values()
+        .filter_map(|o| o.blob.as_ref())
+        .map(|b| b.churn as f64)
+        .collect();
+
+    Distributions {
+        complexity: Distribution::new(code.iter().map(|m| m.complexity).collect()),
+        loc: Distribution::new(code.iter().map(|m| m.loc).collect()),
+        functions: Distribution::new(code.iter().map(|m| m.functions).collect()),
+        comments: Distribution::new(code.iter().map(|m| m.comments).collect()),
+        churn: Distribution::new(churn),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::metrics::{self, Distribution, SceneStats, StatsScene};
+    use std::collections::BTreeMap;
+
+    struct Scene {
+        sub_scenes: Vec<String>,
+        stats: SceneStats,
+    }
+
+    impl StatsScene for Scene {
+        fn sub_scenes(&self) -> &Vec<String> {
+            &self.sub_scenes
+        }
+
+        fn stats_mut(&mut self) -> &mut SceneStats {
+            &mut self.stats
+        }
+    }
+
+    #[test]
+    fn normalize_against_distribution() {
+        let d = Distribution::new(vec![4., 1., 3., 2.]);
+        assert_eq!(d.normalize(1.), 0.125);
+        assert_eq!(d.normalize(4.), 0.875);
+        assert_eq!(d.normalize(0.), 0.);
+        assert_eq!(d.normalize(10.), 1.);
+
+        let d = Distribution::new(vec![2., 2., 2.]);
+        assert_eq!(d.normalize(2.), 0.5);
+
+        let d = Distribution::new(vec![]);
+        assert_eq!(d.normalize(2.), 0.);
+    }
+
+    #[test]
+    fn aggregate_stats_over_sub_scenes() {
+        let mut scenes = BTreeMap::new();
+        let scene = |sub_scenes: Vec<&str>, entities: u32, loc: f64| Scene {
+            sub_scenes: sub_scenes.iter().map(|s| s.to_string()).collect(),
+            stats: SceneStats {
+                entities,
+                loc,
+                ..Default::default()
+            },
+        };
+        scenes.insert("root".to_string(), scene(vec!["a", "b"], 1, 10.));
+        scenes.insert("a".to_string(), scene(vec!["c"], 2, 20.));
+        scenes.insert("b".to_string(), scene(vec![], 3, 30.));
+        scenes.insert("c".to_string(), scene(vec![], 4, 40.));
+
+        let stats = metrics::aggregate_stats("root", &mut scenes);
+        assert_eq!(stats.entities, 10);
+        assert_eq!(stats.scenes, 3);
+        assert_eq!(stats.loc, 100.);
+        assert_eq!(scenes["a"].stats.entities, 6);
+        assert_eq!(scenes["a"].stats.scenes, 1);
+        assert_eq!(scenes["b"].stats.scenes, 0);
+    }
+}
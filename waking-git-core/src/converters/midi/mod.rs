@@ -0,0 +1,448 @@
+use crate::converters::metrics;
+use crate::converters::Converter;
+use crate::error::Error;
+use crate::{extractor, hash, languages, repo, utils};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+pub mod smf;
+
+pub struct MidiConverter {}
+
+pub const CONVERTER_NAME: &str = "midi";
+
+//the number of ticks in a beat, a quarter note
+pub const TICKS_PER_BEAT: u16 = 480;
+//the tempo of the song in microseconds per beat, 120 bpm
+pub const TEMPO: u32 = 500_000;
+
+//a MIDI file has 16 channels, the 10th is kept for the drums
+const CHANNELS: [u8; 15] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 12, 13, 14, 15];
+//the track of the languages left without a channel, and of
+//the files of unknown languages
+const OTHER_LANGUAGE: &str = "Other";
+
+//the General MIDI programs of the most common languages,
+//the other languages get one of the `PALETTE` programs
+const INSTRUMENTS: [(&str, u8); 16] = [
+    ("Rust", 0),        //acoustic grand piano
+    ("Go", 11),         //vibraphone
+    ("Python", 73),     //flute
+    ("JavaScript", 25), //acoustic guitar
+    ("TypeScript", 26), //jazz guitar
+    ("Java", 40),       //violin
+    ("C", 32),          //acoustic bass
+    ("C++", 42),        //cello
+    ("C#", 41),         //viola
+    ("Ruby", 71),       //clarinet
+    ("Shell", 46),      //harp
+    ("Markdown", 52),   //choir aahs
+    ("JSON", 12),       //marimba
+    ("YAML", 13),       //xylophone
+    ("HTML", 56),       //trumpet
+    ("CSS", 57),        //trombone
+];
+const PALETTE: [u8; 8] = [4, 8, 10, 24, 45, 68, 79, 88];
+const OTHER_PROGRAM: u8 = 4; //electric piano
+
+//pitches are picked on a major pentatonic scale, so that
+//any set of notes played together sounds consonant
+const SCALE: [u8; 5] = [0, 2, 4, 7, 9];
+//the lowest note, C3, and the number of octaves spanned by the files
+const BASE_KEY: u8 = 48;
+const OCTAVES: u8 = 3;
+const MIN_VELOCITY: f64 = 40.;
+const MAX_VELOCITY: f64 = 127.;
+
+//commits modifying up to that many files are played as chords,
+//the others are played as arpeggios
+const MAX_CHORD_NOTES: usize = 4;
+//the notes played for a commit, larger commits are sampled
+const MAX_COMMIT_NOTES: usize = 64;
+//the longest rest between two commits, in eighth notes
+const MAX_REST: u32 = 8;
+
+/// A song generated from the commit history, one track per language
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Data {
+    pub name: String,
+    pub repo_name: String,
+    pub commit: String,
+    pub ticks_per_beat: u16,
+    //microseconds per beat
+    pub tempo: u32,
+    pub tracks: Vec<Track>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Track {
+    pub language: String,
+    //the General MIDI program the track is played with
+    pub program: u8,
+    pub channel: u8,
+    //the notes sorted by tick
+    pub notes: Vec<Note>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Note {
+    //the start of the note in ticks, from the commit time
+    pub tick: u32,
+    pub duration: u32,
+    //the MIDI key, from the file complexity
+    pub key: u8,
+    //the MIDI velocity, from the file churn
+    pub velocity: u8,
+    //the file played
+    pub path: String,
+    //the commit that modified the file
+    pub commit: String,
+}
+
+pub fn new() -> MidiConverter {
+    MidiConverter {}
+}
+
+impl Converter<Data> for MidiConverter {
+    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, Error> {
+        Ok(build_song(extracted_data))
+    }
+
+    fn name(&self) -> String {
+        CONVERTER_NAME.to_owned()
+    }
+}
+
+/// build the song of the commits of `extracted_data`,
+/// each commit plays the files it modified.
+///
+/// The tree is played when the history is missing
+fn build_song(extracted_data: &extractor::Data) -> Data {
+    let git = &extracted_data.git;
+    let mut data = Data {
+        name: CONVERTER_NAME.to_owned(),
+        repo_name: extracted_data.code.repo_name.to_owned(),
+        commit: git.ref_target.1.to_owned(),
+        ticks_per_beat: TICKS_PER_BEAT,
+        tempo: TEMPO,
+        ..Default::default()
+    };
+
+    let blobs: HashMap<&str, &extractor::git::Blob> = git
+        .objects
+        .values()
+        .filter_map(|o| o.blob.as_ref())
+        .map(|b| (b.path.as_str(), b))
+        .collect();
+    let history = match git.history.is_empty() {
+        true => {
+            let mut files: Vec<String> = blobs.keys().map(|p| p.to_string()).collect();
+            files.sort();
+            vec![extractor::git::Revision {
+                sha: data.commit.to_owned(),
+                time: 0,
+                files,
+            }]
+        }
+        false => git.history.to_owned(),
+    };
+
+    let distributions = metrics::new_distributions(extracted_data);
+    let languages = languages::new();
+    let files = &extracted_data.code.files_data;
+    let beat = TICKS_PER_BEAT as u32;
+    let sixteenth = beat / 4;
+
+    //the notes of each language
+    let mut notes: BTreeMap<String, Vec<Note>> = BTreeMap::new();
+    let mut tick = 0;
+    let mut previous_time = None;
+    for revision in &history {
+        //the files deleted since the commit are not played
+        let played: Vec<&extractor::git::Blob> = revision
+            .files
+            .iter()
+            .filter_map(|f| blobs.get(f.as_str()).copied())
+            .collect();
+        if played.is_empty() {
+            continue;
+        }
+
+        if let Some(time) = previous_time {
+            tick += rest(revision.time - time);
+        }
+        previous_time = Some(revision.time);
+
+        let chord = played.len() <= MAX_CHORD_NOTES;
+        for (i, blob) in sample(&played, MAX_COMMIT_NOTES).iter().enumerate() {
+            let m = distributions.normalize(&metrics::file_metrics(blob, files));
+            let (start, duration) = match chord {
+                true => (tick, beat),
+                false => (tick + i as u32 * sixteenth, sixteenth),
+            };
+            let language = get_language_name(&blob.path, &languages);
+            notes.entry(language).or_default().push(Note {
+                tick: start,
+                duration,
+                key: to_key(m.complexity),
+                velocity: (MIN_VELOCITY + m.churn * (MAX_VELOCITY - MIN_VELOCITY)).round() as u8,
+                path: blob.path.to_owned(),
+                commit: revision.sha.to_owned(),
+            });
+        }
+        tick += match chord {
+            true => beat,
+            false => played.len().min(MAX_COMMIT_NOTES) as u32 * sixteenth,
+        };
+    }
+
+    data.tracks = build_tracks(notes);
+    data
+}
+
+//returns the rest between two commits `gap` seconds apart, in ticks.
+//the rest grows with the logarithm of the gap: commits made a few
+//minutes apart follow each other, commits made days apart are
+//separated by a whole bar
+fn rest(gap: i64) -> u32 {
+    let minutes = (gap.max(0) / 60) as u64;
+    let eighths = (1 + minutes).ilog2().min(MAX_REST);
+    eighths * TICKS_PER_BEAT as u32 / 2
+}
+
+//returns the key of a file of normalized complexity `complexity`
+fn to_key(complexity: f64) -> u8 {
+    let steps = SCALE.len() as u8 * OCTAVES;
+    let step = (complexity.clamp(0., 1.) * (steps - 1) as f64).round() as u8;
+    BASE_KEY + 12 * (step / SCALE.len() as u8) + SCALE[(step % SCALE.len() as u8) as usize]
+}
+
+//returns at most `max` evenly spaced items of `items`
+fn sample<T: Copy>(items: &[T], max: usize) -> Vec<T> {
+    if items.len() <= max {
+        return items.to_vec();
+    }
+    (0..max).map(|i| items[i * items.len() / max]).collect()
+}
+
+//give a channel and an instrument to each language, the languages with
+//the most notes first. the languages left without a channel share the
+//`OTHER_LANGUAGE` track
+fn build_tracks(notes: BTreeMap<String, Vec<Note>>) -> Vec<Track> {
+    let mut languages: Vec<(String, Vec<Note>)> = notes.into_iter().collect();
+    languages.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.0.cmp(&b.0)));
+
+    let mut other = vec![];
+    let mut tracks = vec![];
+    for (language, notes) in languages {
+        if language == OTHER_LANGUAGE || tracks.len() == CHANNELS.len() - 1 {
+            other.extend(notes);
+            continue;
+        }
+        tracks.push(Track {
+            program: get_program(&language),
+            channel: CHANNELS[tracks.len()],
+            language,
+            notes,
+        });
+    }
+
+    if !other.is_empty() {
+        other.sort_by(|a, b| a.tick.cmp(&b.tick).then(a.path.cmp(&b.path)));
+        tracks.push(Track {
+            language: OTHER_LANGUAGE.to_owned(),
+            program: OTHER_PROGRAM,
+            channel: CHANNELS[tracks.len()],
+            notes: other,
+        });
+    }
+    tracks
+}
+
+fn get_program(language: &str) -> u8 {
+    match INSTRUMENTS.iter().find(|(name, _)| *name == language) {
+        Some((_, program)) => *program,
+        None => {
+            let h = hash::new(language.to_owned());
+            let i = usize::from_str_radix(h.get(..2).unwrap_or_default(), 16).unwrap_or_default();
+            PALETTE[i % PALETTE.len()]
+        }
+    }
+}
+
+fn get_language_name(path: &str, languages: &languages::Languages) -> String {
+    let name = languages::spec_from_path(languages, path).name;
+    match name.is_empty() {
+        true => OTHER_LANGUAGE.to_owned(),
+        false => name,
+    }
+}
+
+/// returns the Standard MIDI File of the song,
+/// with a first track holding the tempo
+pub fn render(data: &Data) -> Vec<u8> {
+    let mut tracks = vec![vec![
+        event(0, smf::EventKind::TrackName(data.repo_name.to_owned())),
+        event(0, smf::EventKind::Tempo(data.tempo)),
+        event(0, smf::EventKind::EndOfTrack),
+    ]];
+
+    for track in &data.tracks {
+        let channel = track.channel;
+        //the events at their absolute tick, the notes
+        //ending at a tick are stopped before the new ones start
+        let mut timed = vec![];
+        for n in &track.notes {
+            let (key, velocity) = (n.key, n.velocity);
+            timed.push((
+                n.tick,
+                1,
+                smf::EventKind::NoteOn {
+                    channel,
+                    key,
+                    velocity,
+                },
+            ));
+            let off = smf::EventKind::NoteOff {
+                channel,
+                key,
+                velocity: 0,
+            };
+            timed.push((n.tick + n.duration, 0, off));
+        }
+        timed.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut events = vec![
+            event(0, smf::EventKind::TrackName(track.language.to_owned())),
+            event(
+                0,
+                smf::EventKind::ProgramChange {
+                    channel,
+                    program: track.program,
+                },
+            ),
+        ];
+        let mut last = 0;
+        for (tick, _, kind) in timed {
+            events.push(event(tick - last, kind));
+            last = tick;
+        }
+        events.push(event(0, smf::EventKind::EndOfTrack));
+        tracks.push(events);
+    }
+
+    smf::encode(&smf::Smf {
+        format: 1,
+        ticks_per_beat: data.ticks_per_beat,
+        tracks,
+    })
+}
+
+fn event(delta: u32, kind: smf::EventKind) -> smf::Event {
+    smf::Event { delta, kind }
+}
+
+/// write the song as a Standard MIDI File,
+/// next to the converted file of `git_repo`
+pub fn export(git_repo: &repo::Repo, data: &Data) -> Result<(), Error> {
+    let dest_path = format!(
+        "{}/{CONVERTER_NAME}.{}",
+        git_repo.scanner_path,
+        smf::EXTENSION
+    );
+    match utils::store_data(&render(data), git_repo.scanner_path.to_owned(), &dest_path) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::io(format!("failed to write `{dest_path}`"), err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::midi::{self, smf};
+    use crate::converters::Converter;
+    use crate::extractor::{self, git};
+    use crate::hash;
+
+    fn blob(path: &str, churn: u32) -> git::Object {
+        git::Object {
+            kind: git::ObjectKind::Blob,
+            blob: Some(git::Blob {
+                path: path.to_string(),
+                path_sha: hash::new(path.to_string()),
+                sha: path.to_string(),
+                churn,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn revision(sha: &str, time: i64, files: &[&str]) -> git::Revision {
+        git::Revision {
+            sha: sha.to_string(),
+            time,
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn build_song_from_history() {
+        let mut extracted = extractor::Data::default();
+        for (path, churn) in [("src/main.rs", 1), ("src/lib.rs", 3), ("docs/index.md", 2)] {
+            extracted
+                .git
+                .objects
+                .insert(path.to_string(), blob(path, churn));
+        }
+        extracted.git.history = vec![
+            revision("a", 0, &["src/main.rs", "src/lib.rs", "docs/index.md"]),
+            //deleted files are not played
+            revision("b", 60, &["src/lib.rs", "src/gone.rs"]),
+            revision("c", 86400 * 3, &["src/lib.rs"]),
+        ];
+
+        let data = midi::new().run(&extracted).unwrap();
+        let notes: usize = data.tracks.iter().map(|t| t.notes.len()).sum();
+        assert_eq!(notes, 5);
+        let rust = data.tracks.iter().find(|t| t.language == "Rust").unwrap();
+        assert_eq!(rust.program, 0);
+        let ticks: Vec<u32> = rust.notes.iter().map(|n| n.tick).collect();
+        //a beat per chord, an eighth rest after a minute, a bar after days
+        assert_eq!(ticks, vec![0, 0, 720, 3120]);
+        //the files modified the most are played the loudest
+        let lib = rust.notes.iter().find(|n| n.path == "src/lib.rs").unwrap();
+        let main = rust.notes.iter().find(|n| n.path == "src/main.rs").unwrap();
+        assert!(lib.velocity > main.velocity);
+
+        //the rendered file is parsed back
+        let song = smf::decode(&midi::render(&data)).unwrap();
+        assert_eq!(song.format, 1);
+        assert_eq!(song.ticks_per_beat, midi::TICKS_PER_BEAT);
+        assert_eq!(song.tracks.len(), data.tracks.len() + 1);
+        assert!(song.tracks[0].contains(&midi::event(0, smf::EventKind::Tempo(midi::TEMPO))));
+        let note_on = |e: &smf::Event| matches!(e.kind, smf::EventKind::NoteOn { .. });
+        let played = song.tracks.iter().flatten().filter(|e| note_on(e)).count();
+        assert_eq!(played, notes);
+        let end: u32 = song.tracks[1].iter().map(|e| e.delta).sum();
+        assert_eq!(end, 3120 + midi::TICKS_PER_BEAT as u32);
+    }
+
+    #[test]
+    fn play_the_tree_without_history() {
+        let mut extracted = extractor::Data::default();
+        for i in 0..10 {
+            let path = format!("src/{i}.rs");
+            extracted
+                .git
+                .objects
+                .insert(path.to_owned(), blob(&path, 1));
+        }
+
+        //large commits are played as arpeggios
+        let data = midi::new().run(&extracted).unwrap();
+        assert_eq!(data.tracks.len(), 1);
+        let ticks: Vec<u32> = data.tracks[0].notes.iter().map(|n| n.tick).collect();
+        assert_eq!(ticks, (0..10).map(|i| i * 120).collect::<Vec<u32>>());
+    }
+}
@@ -0,0 +1,335 @@
+use crate::error::Error;
+
+//a minimal reader and writer of Standard MIDI Files,
+//supporting the events written by the midi converter
+
+pub const EXTENSION: &str = "mid";
+
+const HEADER_CHUNK: &[u8; 4] = b"MThd";
+const TRACK_CHUNK: &[u8; 4] = b"MTrk";
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const PROGRAM_CHANGE: u8 = 0xC0;
+const META: u8 = 0xFF;
+const META_TRACK_NAME: u8 = 0x03;
+const META_END_OF_TRACK: u8 = 0x2F;
+const META_TEMPO: u8 = 0x51;
+
+/// Smf is a Standard MIDI File
+#[derive(Debug, Clone, PartialEq)]
+pub struct Smf {
+    //0 for a single track, 1 for simultaneous tracks
+    pub format: u16,
+    //the number of ticks in a quarter note
+    pub ticks_per_beat: u16,
+    pub tracks: Vec<Vec<Event>>,
+}
+
+/// Event is a track event, `delta` ticks after the previous one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub delta: u32,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    NoteOn { channel: u8, key: u8, velocity: u8 },
+    NoteOff { channel: u8, key: u8, velocity: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    TrackName(String),
+    //microseconds per quarter note
+    Tempo(u32),
+    EndOfTrack,
+}
+
+/// encode `smf` in the Standard MIDI File format
+pub fn encode(smf: &Smf) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend_from_slice(HEADER_CHUNK);
+    out.extend_from_slice(&6u32.to_be_bytes());
+    out.extend_from_slice(&smf.format.to_be_bytes());
+    out.extend_from_slice(&(smf.tracks.len() as u16).to_be_bytes());
+    out.extend_from_slice(&smf.ticks_per_beat.to_be_bytes());
+
+    for track in &smf.tracks {
+        let mut chunk = vec![];
+        for event in track {
+            write_vlq(&mut chunk, event.delta);
+            write_event(&mut chunk, &event.kind);
+        }
+        out.extend_from_slice(TRACK_CHUNK);
+        out.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        out.extend_from_slice(&chunk);
+    }
+    out
+}
+
+fn write_event(out: &mut Vec<u8>, kind: &EventKind) {
+    match kind {
+        EventKind::NoteOn {
+            channel,
+            key,
+            velocity,
+        } => out.extend_from_slice(&[NOTE_ON | channel, *key, *velocity]),
+        EventKind::NoteOff {
+            channel,
+            key,
+            velocity,
+        } => out.extend_from_slice(&[NOTE_OFF | channel, *key, *velocity]),
+        EventKind::ProgramChange { channel, program } => {
+            out.extend_from_slice(&[PROGRAM_CHANGE | channel, *program])
+        }
+        EventKind::TrackName(name) => {
+            out.extend_from_slice(&[META, META_TRACK_NAME]);
+            write_vlq(out, name.len() as u32);
+            out.extend_from_slice(name.as_bytes());
+        }
+        EventKind::Tempo(tempo) => {
+            out.extend_from_slice(&[META, META_TEMPO, 3]);
+            out.extend_from_slice(&tempo.to_be_bytes()[1..]);
+        }
+        EventKind::EndOfTrack => out.extend_from_slice(&[META, META_END_OF_TRACK, 0]),
+    }
+}
+
+//write `value` as a variable length quantity,
+//7 bits per byte, most significant bytes first
+fn write_vlq(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut v = value >> 7;
+    while v > 0 {
+        bytes.push((v & 0x7F) as u8 | 0x80);
+        v >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+/// decode a Standard MIDI File, the events
+/// not written by `encode` are skipped
+pub fn decode(bytes: &[u8]) -> Result<Smf, Error> {
+    match decode_smf(bytes) {
+        Ok(smf) => Ok(smf),
+        Err(err) => Err(Error::parse(err)),
+    }
+}
+
+fn decode_smf(bytes: &[u8]) -> Result<Smf, String> {
+    let mut r = Reader { bytes, pos: 0 };
+    if r.take(4)? != HEADER_CHUNK {
+        return Err("missing MIDI header chunk".to_string());
+    }
+    let header_len = r.u32()? as usize;
+    let header = r.take(header_len)?;
+    if header_len < 6 {
+        return Err(format!("invalid MIDI header length {header_len}"));
+    }
+    let format = u16::from_be_bytes([header[0], header[1]]);
+    let count = u16::from_be_bytes([header[2], header[3]]);
+    let ticks_per_beat = u16::from_be_bytes([header[4], header[5]]);
+
+    let mut tracks = vec![];
+    while tracks.len() < count as usize {
+        let id = r.take(4)?;
+        let len = r.u32()? as usize;
+        let chunk = r.take(len)?;
+        //unknown chunks must be ignored
+        if id == TRACK_CHUNK {
+            tracks.push(decode_track(chunk)?);
+        }
+    }
+
+    Ok(Smf {
+        format,
+        ticks_per_beat,
+        tracks,
+    })
+}
+
+fn decode_track(bytes: &[u8]) -> Result<Vec<Event>, String> {
+    let mut r = Reader { bytes, pos: 0 };
+    let mut events = vec![];
+    //the status byte can be omitted when repeated
+    let mut running_status = 0;
+    let mut skipped = 0;
+
+    while r.pos < bytes.len() {
+        let delta = skipped + r.vlq()?;
+        skipped = 0;
+        let mut status = r.u8()?;
+        if status < 0x80 {
+            r.pos -= 1;
+            status = running_status;
+        }
+
+        let kind = match status {
+            META => {
+                let kind = r.u8()?;
+                let len = r.vlq()? as usize;
+                let data = r.take(len)?;
+                match kind {
+                    META_TRACK_NAME => Some(EventKind::TrackName(
+                        String::from_utf8_lossy(data).into_owned(),
+                    )),
+                    META_TEMPO if len == 3 => Some(EventKind::Tempo(u32::from_be_bytes([
+                        0, data[0], data[1], data[2],
+                    ]))),
+                    META_END_OF_TRACK => Some(EventKind::EndOfTrack),
+                    _ => None,
+                }
+            }
+            0xF0 | 0xF7 => {
+                let len = r.vlq()? as usize;
+                r.take(len)?;
+                None
+            }
+            _ => {
+                running_status = status;
+                let channel = status & 0x0F;
+                match status & 0xF0 {
+                    NOTE_ON => Some(EventKind::NoteOn {
+                        channel,
+                        key: r.u8()?,
+                        velocity: r.u8()?,
+                    }),
+                    NOTE_OFF => Some(EventKind::NoteOff {
+                        channel,
+                        key: r.u8()?,
+                        velocity: r.u8()?,
+                    }),
+                    PROGRAM_CHANGE => Some(EventKind::ProgramChange {
+                        channel,
+                        program: r.u8()?,
+                    }),
+                    0xD0 => {
+                        r.take(1)?;
+                        None
+                    }
+                    0xA0 | 0xB0 | 0xE0 => {
+                        r.take(2)?;
+                        None
+                    }
+                    _ => return Err(format!("invalid MIDI status byte {status:#04x}")),
+                }
+            }
+        };
+
+        match kind {
+            Some(kind) => events.push(Event { delta, kind }),
+            None => skipped = delta,
+        }
+    }
+
+    Ok(events)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        match self.bytes.get(self.pos..self.pos + len) {
+            Some(b) => {
+                self.pos += len;
+                Ok(b)
+            }
+            None => Err("unexpected end of MIDI data".to_string()),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn vlq(&mut self) -> Result<u32, String> {
+        let mut value: u32 = 0;
+        for _ in 0..4 {
+            let b = self.u8()?;
+            value = (value << 7) | (b & 0x7F) as u32;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("invalid MIDI variable length quantity".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::midi::smf::{self, Event, EventKind, Smf};
+
+    #[test]
+    fn encode_and_decode() {
+        let event = |delta, kind| Event { delta, kind };
+        let smf = Smf {
+            format: 1,
+            ticks_per_beat: 480,
+            tracks: vec![
+                vec![
+                    event(0, EventKind::TrackName("song".to_string())),
+                    event(0, EventKind::Tempo(500_000)),
+                    event(0, EventKind::EndOfTrack),
+                ],
+                vec![
+                    event(
+                        0,
+                        EventKind::ProgramChange {
+                            channel: 2,
+                            program: 40,
+                        },
+                    ),
+                    event(
+                        127,
+                        EventKind::NoteOn {
+                            channel: 2,
+                            key: 60,
+                            velocity: 100,
+                        },
+                    ),
+                    event(
+                        128,
+                        EventKind::NoteOff {
+                            channel: 2,
+                            key: 60,
+                            velocity: 0,
+                        },
+                    ),
+                    event(0x0FFF_FFFF, EventKind::EndOfTrack),
+                ],
+            ],
+        };
+
+        let bytes = smf::encode(&smf);
+        assert_eq!(&bytes[..4], b"MThd");
+        //128 is written on two bytes
+        assert!(bytes.windows(3).any(|w| w == [0x81, 0x00, 0x82]));
+        assert_eq!(smf::decode(&bytes).unwrap(), smf);
+        assert!(smf::decode(&bytes[..bytes.len() - 1]).is_err());
+
+        //running status is supported
+        let mut bytes = b"MThd\0\0\0\x06\0\0\0\x01\x01\xe0MTrk\0\0\0\x0b".to_vec();
+        bytes.extend_from_slice(&[0, 0x90, 60, 90, 10, 64, 90, 0, 0xFF, 0x2F, 0]);
+        let smf = smf::decode(&bytes).unwrap();
+        assert_eq!(smf.tracks[0].len(), 3);
+        assert_eq!(
+            smf.tracks[0][1],
+            event(
+                10,
+                EventKind::NoteOn {
+                    channel: 0,
+                    key: 64,
+                    velocity: 90
+                }
+            )
+        );
+    }
+}
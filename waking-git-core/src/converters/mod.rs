@@ -1,13 +1,40 @@
 pub mod codealkemi;
+pub mod codecity;
+pub mod dungeon;
+pub mod external;
+pub mod graph;
+pub mod metrics;
+pub mod midi;
+pub mod rules;
 pub mod shmup;
+pub mod spaces;
+use crate::cache;
 use crate::config;
+use crate::error::Error;
 use crate::extractor;
+use crate::formats;
 use crate::repo;
 use crate::utils;
 
+/// Options shared by the converters
+#[derive(Default, Debug)]
+pub struct Options {
+    /// rules applied on top of the converter default mapping
+    pub rules: rules::Rules,
+    /// build sub entities from the functions, classes, impls...
+    /// declared in files
+    pub sub_entities: bool,
+}
+
+/// returns the seed of the world generated from the commit `commit_oid`
+pub fn seed_from_commit(commit_oid: &str) -> u64 {
+    let hex = commit_oid.get(..16).unwrap_or(commit_oid);
+    u64::from_str_radix(hex, 16).unwrap_or_default()
+}
+
 pub trait Converter<Data> {
     /// run the converter
-    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, String>;
+    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, Error>;
 
     /// Return converter name
     fn name(&self) -> String {
@@ -15,30 +42,261 @@ pub trait Converter<Data> {
     }
 }
 
-pub fn convert<Data: serde::Serialize>(
+/// Converted is the data generated by a builtin or an external converter
+pub enum Converted {
+    Shmup(shmup::Data),
+    Codealkemi(codealkemi::Data),
+    Codecity(codecity::Data),
+    Dungeon(dungeon::Data),
+    Graph(graph::Data),
+    Midi(midi::Data),
+    /// the data of the external converter `name`
+    External {
+        name: String,
+        data: serde_json::Value,
+    },
+}
+
+impl Converted {
+    /// returns the name of the converter that generated the data
+    pub fn converter(&self) -> &str {
+        match self {
+            Converted::Shmup(_) => shmup::CONVERTER_NAME,
+            Converted::Codealkemi(_) => codealkemi::CONVERTER_NAME,
+            Converted::Codecity(_) => codecity::CONVERTER_NAME,
+            Converted::Dungeon(_) => dungeon::CONVERTER_NAME,
+            Converted::Graph(_) => graph::CONVERTER_NAME,
+            Converted::Midi(_) => midi::CONVERTER_NAME,
+            Converted::External { name, .. } => name,
+        }
+    }
+
+    /// returns the data encoded in `format`
+    pub fn encode(&self, format: formats::Format) -> Result<Vec<u8>, Error> {
+        match self {
+            Converted::Shmup(d) => formats::encode(d, format),
+            Converted::Codealkemi(d) => formats::encode(d, format),
+            Converted::Codecity(d) => formats::encode(d, format),
+            Converted::Dungeon(d) => formats::encode(d, format),
+            Converted::Graph(d) => formats::encode(d, format),
+            Converted::Midi(d) => formats::encode(d, format),
+            Converted::External { data, .. } => formats::encode(data, format),
+        }
+    }
+}
+
+//a builtin converter, its name and the function running it
+type Builtin = (
+    &'static str,
+    fn(&extractor::Data, Options) -> Result<Converted, Error>,
+);
+
+//the builtin converters, `exists` and `run_by_name` both look them up here
+const BUILTINS: [Builtin; 6] = [
+    (shmup::CONVERTER_NAME, |data, options| {
+        Ok(Converted::Shmup(shmup::with_options(options).run(data)?))
+    }),
+    (codealkemi::CONVERTER_NAME, |data, options| {
+        Ok(Converted::Codealkemi(
+            codealkemi::with_options(options).run(data)?,
+        ))
+    }),
+    (codecity::CONVERTER_NAME, |data, options| {
+        Ok(Converted::Codecity(
+            codecity::with_options(options).run(data)?,
+        ))
+    }),
+    (graph::CONVERTER_NAME, |data, options| {
+        Ok(Converted::Graph(graph::with_options(options).run(data)?))
+    }),
+    (dungeon::CONVERTER_NAME, |data, options| {
+        Ok(Converted::Dungeon(
+            dungeon::with_options(options).run(data)?,
+        ))
+    }),
+    (midi::CONVERTER_NAME, |data, _| {
+        Ok(Converted::Midi(midi::new().run(data)?))
+    }),
+];
+
+/// returns the names of the builtin converters
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|(name, _)| *name)
+}
+
+/// returns true if `name` is a builtin converter
+/// or an external converter of the `registry`
+pub fn exists(name: &str, registry: &external::Registry) -> bool {
+    builtin_names().any(|n| n == name) || registry.find(name).is_some()
+}
+
+/// run the converter named `name`, either a builtin converter
+/// or an external converter of the `registry`, on the extracted data.
+/// Nothing is written to disk
+pub fn run_by_name(
+    name: &str,
+    extracted_data: &extractor::Data,
+    options: Options,
+    registry: &external::Registry,
+) -> Result<Converted, Error> {
+    if let Some((_, run)) = BUILTINS.iter().find(|(n, _)| *n == name) {
+        return run(extracted_data, options);
+    }
+    match registry.find(name) {
+        Some(spec) => Ok(Converted::External {
+            name: spec.name.to_owned(),
+            data: external::new(spec.to_owned())?.run(extracted_data)?,
+        }),
+        None => Err(Error::convert(format!("unknown converter `{name}`"))),
+    }
+}
+
+/// write the encoded converted data to the scanner folder of `git_repo`,
+/// the graph is also exported to graph tools formats and the song
+/// is also written as a MIDI file.
+/// `commit` is the commit the data was converted from, and `options`
+/// the fingerprint of the options it was converted with
+pub fn save(
     git_repo: &mut repo::Repo,
-    extracted_data: extractor::Data,
-    converter: &impl Converter<Data>,
-) -> Result<(Data, String), String> {
-    let data = converter.run(&extracted_data)?;
+    converted: &Converted,
+    commit: &str,
+    options: &str,
+    encoded_data: &[u8],
+) -> Result<(), Error> {
+    write(
+        git_repo,
+        converted.converter(),
+        commit,
+        options,
+        encoded_data,
+    )?;
+    match converted {
+        Converted::Graph(data) => graph::export(git_repo, data),
+        Converted::Midi(data) => midi::export(git_repo, data),
+        _ => Ok(()),
+    }
+}
+
+//write the converted file of the converter `name`, and set
+//the `converted_file_path` of `git_repo`
+fn write(
+    git_repo: &mut repo::Repo,
+    name: &str,
+    commit: &str,
+    options: &str,
+    encoded_data: &[u8],
+) -> Result<(), Error> {
     let dest_path = format!(
-        "{}/{}-{}",
+        "{}/{}-{}.{}",
         git_repo.scanner_path,
-        converter.name(),
-        config::CONVERTER_FILE_NAME_PREFIX
+        name,
+        config::CONVERTER_FILE_NAME_PREFIX,
+        git_repo.format.extension()
     );
     git_repo.converted_file_path = dest_path.clone();
-    let json_data = serde_json::to_string(&data).unwrap_or_else(|_| "".to_string());
-    match utils::store_json_data(
-        json_data.to_owned(),
-        git_repo.scanner_path.to_owned(),
-        &dest_path,
-    ) {
-        Ok(_) => (),
+    if let Err(err) = utils::store_data(encoded_data, git_repo.scanner_path.to_owned(), &dest_path)
+    {
+        return Err(Error::io(format!("failed to write `{dest_path}`"), err));
+    }
+
+    //remember the commit and the options the file was converted from, so that it can be reused.
+    //the file is written already, a cache that cannot be updated only warns
+    let mut c = match cache::load(&git_repo.scanner_path) {
+        Ok(c) => c,
         Err(err) => {
-            return Err(format!("Error: failed to convert repository data: {err}"));
+            eprintln!("Warning: {err}");
+            cache::Cache::default()
         }
     };
+    c.record(name, commit, options, &dest_path);
+    if let Err(err) = c.save(&git_repo.scanner_path) {
+        eprintln!("Warning: {err}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::{self, codealkemi, codecity, shmup, Converter};
+    use crate::utils::test::{self, TempDir};
+    use crate::{config, extractor, formats, repo, schemas};
+
+    //create a small git repository, with a few files and directories, and open it
+    fn init_repo() -> (TempDir, repo::Repo) {
+        let (dir, r) = test::temp_repo();
+        let files = [
+            ("README.md", "# repo\n"),
+            ("src/main.rs", "fn main() {\n    run();\n}\n"),
+            (
+                "src/lib.rs",
+                "pub fn run() {\n    if true {\n        println!(\"run\");\n    }\n}\n",
+            ),
+            ("src/cmd/scan.rs", "pub fn scan() {}\n"),
+            ("src/cmd/play.rs", "pub fn play() {}\n"),
+            ("docs/index.md", "# docs\n"),
+        ];
+
+        test::commit_files(&r, &files);
+        let git_repo = repo::open_local(&dir.join("app"), &config::Config::default()).unwrap();
+        (dir, git_repo)
+    }
 
-    Ok((data, json_data))
+    #[test]
+    fn identical_inputs_give_identical_outputs() {
+        let (_dir, git_repo) = init_repo();
+
+        let scan = || {
+            let data = extractor::run(&git_repo).unwrap();
+            let shmup_data = shmup::new().run(&data).unwrap();
+            let codealkemi_data = codealkemi::new().run(&data).unwrap();
+            let codecity_data = codecity::new().run(&data).unwrap();
+            (
+                formats::encode(&shmup_data, formats::Format::Json).unwrap(),
+                formats::encode(&codealkemi_data, formats::Format::Json).unwrap(),
+                codecity_data,
+                shmup_data.seed,
+            )
+        };
+
+        let (shmup_1, codealkemi_1, city_1, seed) = scan();
+        let (shmup_2, codealkemi_2, city_2, _) = scan();
+        assert_eq!(shmup_1, shmup_2);
+        assert_eq!(codealkemi_1, codealkemi_2);
+        let encode = |d| formats::encode(d, formats::Format::Json).unwrap();
+        assert_eq!(encode(&city_1), encode(&city_2));
+
+        //every building stands in its district
+        assert_eq!(city_1.buildings.len(), 6);
+        for b in city_1.buildings.values() {
+            let area = city_1.districts[&b.district_id].area;
+            let f = b.footprint;
+            assert!(f.width > 0. && f.length > 0.);
+            assert!(f.x >= area.x && f.x + f.width <= area.x + area.width + 1e-9);
+            assert!(f.y >= area.y && f.y + f.length <= area.y + area.length + 1e-9);
+        }
+
+        let head = git_repo.repo.head().unwrap().target().unwrap();
+        assert_eq!(seed, converters::seed_from_commit(&head.to_string()));
+        assert_ne!(seed, 0);
+    }
+
+    #[test]
+    fn builtin_converters_exist() {
+        let registry = converters::external::Registry::default();
+        //every converted data schema comes from a builtin converter
+        for name in schemas::NAMES.iter().filter(|n| **n != schemas::EXTRACTED) {
+            assert!(converters::exists(name, &registry), "{name}");
+        }
+        assert!(!converters::exists("unknown", &registry));
+
+        //each builtin converter runs under its own name
+        let (_dir, git_repo) = init_repo();
+        let data = extractor::run(&git_repo).unwrap();
+        for name in converters::builtin_names() {
+            let converted =
+                converters::run_by_name(name, &data, converters::Options::default(), &registry)
+                    .unwrap();
+            assert_eq!(converted.converter(), name);
+        }
+    }
 }
@@ -0,0 +1,534 @@
+use crate::converters::metrics;
+use crate::error::Error;
+use crate::{extractor, hash, languages};
+use evalexpr::{ContextWithMutableVariables, HashMapContext, Node};
+use globset::{Glob, GlobMatcher};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// RulesFile is the content of a rules file.
+///
+/// Here is an example of a rules file:
+///
+/// ```yaml
+/// rules:
+///   # every rust file get twice as much hp
+///   - match:
+///       path: "src/**/*.rs"
+///       kind: programming
+///     set:
+///       hp: "complexity_norm * 2"
+///       weapon: '"fast_gun"'
+///     clamp:
+///       hp: [0, 1]
+///   # files that changed a lot are fast
+///   - match:
+///       when: "churn > 10"
+///     set:
+///       speed: 1
+/// ```
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct RulesFile {
+    #[serde(default)]
+    pub rules: Vec<RuleSpec>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct RuleSpec {
+    //the conditions an entity must fulfil for the rule to be applied
+    #[serde(default, rename = "match")]
+    pub matcher: MatcherSpec,
+    //maps an entity attribute to a constant or to an expression.
+    //strings are always evaluated as expressions, string literals
+    //must therefore be quoted.
+    //nested attributes are separated by dots, e.g: `language.color`
+    #[serde(default)]
+    pub set: BTreeMap<String, serde_yaml::Value>,
+    //maps a numeric entity attribute to its [min, max] bounds
+    #[serde(default)]
+    pub clamp: BTreeMap<String, (f64, f64)>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct MatcherSpec {
+    //a glob matched against the file path, e.g: `src/**/*.rs`
+    pub path: Option<String>,
+    //the language kind: programming | markup | data | prose
+    pub kind: Option<String>,
+    //the language name, e.g: `Rust`
+    pub language: Option<String>,
+    //a boolean expression, e.g: `loc > 100 && churn_norm > 0.5`
+    pub when: Option<String>,
+}
+
+/// Rules is the compiled version of a `RulesFile`,
+/// ready to be applied to converted entities
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+    //the path and the hash of the content the rules were loaded from
+    fingerprint: String,
+}
+
+#[derive(Debug)]
+struct Rule {
+    path: Option<GlobMatcher>,
+    kind: Option<String>,
+    language: Option<String>,
+    when: Option<Node>,
+    set: Vec<(String, Assignment)>,
+    clamp: Vec<(String, (f64, f64))>,
+}
+
+#[derive(Debug)]
+enum Assignment {
+    Constant(serde_json::Value),
+    Expression(Node),
+}
+
+/// Context holds the extracted data of the file an entity
+/// was built from.
+///
+/// Every field is made available to the rules expressions, normalized
+/// metrics are available with a `_norm` suffix, e.g: `complexity_norm`
+#[derive(Debug, Default, Clone)]
+pub struct Context {
+    pub name: String,
+    pub path: String,
+    pub extension: String,
+    pub language: String,
+    pub kind: String,
+    pub metrics: metrics::FileMetrics,
+    pub normalized: metrics::FileMetrics,
+}
+
+/// load and compile the rules file located at `path`
+pub fn load(path: &str) -> Result<Rules, Error> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(err) => {
+            return Err(Error::io(
+                format!("failed to read rules file `{path}`"),
+                err,
+            ))
+        }
+    };
+
+    match parse(&content) {
+        Ok(rules) => Ok(Rules {
+            fingerprint: format!("{path}:{}", rules.fingerprint),
+            ..rules
+        }),
+        Err(err) => Err(Error::Parse {
+            message: format!("invalid rules file `{path}`"),
+            source: Some(Box::new(err)),
+        }),
+    }
+}
+
+/// parse and compile a yaml rules file content
+pub fn parse(content: &str) -> Result<Rules, Error> {
+    let file: RulesFile = match serde_yaml::from_str(content) {
+        Ok(f) => f,
+        Err(err) => {
+            return Err(Error::Parse {
+                message: "failed to parse rules".to_string(),
+                source: Some(Box::new(err)),
+            })
+        }
+    };
+
+    let rules = compile(file)?;
+    Ok(Rules {
+        fingerprint: hash::new(content.to_owned()),
+        ..rules
+    })
+}
+
+pub fn compile(file: RulesFile) -> Result<Rules, Error> {
+    let mut rules = vec![];
+    for (i, spec) in file.rules.into_iter().enumerate() {
+        match compile_rule(spec) {
+            Ok(r) => rules.push(r),
+            Err(err) => return Err(Error::parse(format!("rule {i}: {err}"))),
+        }
+    }
+
+    Ok(Rules {
+        rules,
+        fingerprint: String::new(),
+    })
+}
+
+fn compile_rule(spec: RuleSpec) -> Result<Rule, String> {
+    let path = match spec.matcher.path {
+        Some(p) => match Glob::new(&p) {
+            Ok(g) => Some(g.compile_matcher()),
+            Err(err) => return Err(format!("invalid path glob `{p}`: {err}")),
+        },
+        None => None,
+    };
+
+    let when = match spec.matcher.when {
+        Some(w) => Some(build_expression(&w)?),
+        None => None,
+    };
+
+    let mut set = vec![];
+    for (attribute, value) in spec.set {
+        let assignment = match value {
+            serde_yaml::Value::String(expr) => Assignment::Expression(build_expression(&expr)?),
+            serde_yaml::Value::Number(_) | serde_yaml::Value::Bool(_) => {
+                match serde_json::to_value(&value) {
+                    Ok(v) => Assignment::Constant(v),
+                    Err(err) => return Err(format!("invalid value for `{attribute}`: {err}")),
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "invalid value for `{attribute}`: expected a number, a boolean or an expression"
+                ))
+            }
+        };
+        set.push((attribute, assignment));
+    }
+
+    for (attribute, (min, max)) in &spec.clamp {
+        if min > max {
+            return Err(format!(
+                "invalid clamp for `{attribute}`: {min} is greater than {max}"
+            ));
+        }
+    }
+
+    Ok(Rule {
+        path,
+        kind: spec.matcher.kind,
+        language: spec.matcher.language,
+        when,
+        set,
+        clamp: spec.clamp.into_iter().collect(),
+    })
+}
+
+fn build_expression(expr: &str) -> Result<Node, String> {
+    match evalexpr::build_operator_tree(expr) {
+        Ok(node) => Ok(node),
+        Err(err) => Err(format!("invalid expression `{expr}`: {err}")),
+    }
+}
+
+/// build the rules context of the file pointed by `blob`
+pub fn new_context(
+    blob: &extractor::git::Blob,
+    files: &HashMap<String, extractor::code::FileData>,
+    distributions: &metrics::Distributions,
+    languages: &languages::Languages,
+) -> Context {
+    let p = Path::new(blob.path.as_str());
+    let extension = match p.extension() {
+        Some(ext) => ext.to_string_lossy().into_owned(),
+        None => "".to_owned(),
+    };
+    let spec = languages::spec_from_path(languages, &blob.path);
+    let metrics = metrics::file_metrics(blob, files);
+
+    Context {
+        name: blob.name.to_owned(),
+        path: blob.path.to_owned(),
+        extension,
+        language: spec.name,
+        kind: spec.kind,
+        normalized: distributions.normalize(&metrics),
+        metrics,
+    }
+}
+
+impl Rules {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// returns the path and the content hash of the rules file,
+    /// empty when the rules were not loaded from a file content
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// apply the rules, in order, to the `entity`.
+    /// a rule overrides the attributes set by the rules preceding it.
+    pub fn apply<E: Serialize + DeserializeOwned>(
+        &self,
+        ctx: &Context,
+        entity: E,
+    ) -> Result<E, Error> {
+        match self.apply_rules(ctx, entity) {
+            Ok(e) => Ok(e),
+            Err(err) => Err(Error::convert(err)),
+        }
+    }
+
+    fn apply_rules<E: Serialize + DeserializeOwned>(
+        &self,
+        ctx: &Context,
+        entity: E,
+    ) -> Result<E, String> {
+        if self.rules.is_empty() {
+            return Ok(entity);
+        }
+
+        let mut value = match serde_json::to_value(&entity) {
+            Ok(v) => v,
+            Err(err) => return Err(format!("failed to serialize entity: {err}")),
+        };
+
+        for rule in &self.rules {
+            let eval_ctx = build_eval_context(ctx, &value)?;
+            if !rule.matches(ctx, &eval_ctx)? {
+                continue;
+            }
+
+            for (attribute, assignment) in &rule.set {
+                let v = match assignment {
+                    Assignment::Constant(v) => v.to_owned(),
+                    Assignment::Expression(node) => match node.eval_with_context(&eval_ctx) {
+                        Ok(v) => to_json_value(v, attribute)?,
+                        Err(err) => {
+                            return Err(format!(
+                                "failed to evaluate `{attribute}` for `{}`: {err}",
+                                ctx.path
+                            ))
+                        }
+                    },
+                };
+                set_attribute(&mut value, attribute, v)?;
+            }
+
+            for (attribute, (min, max)) in &rule.clamp {
+                clamp_attribute(&mut value, attribute, *min, *max)?;
+            }
+        }
+
+        match serde_json::from_value(value) {
+            Ok(e) => Ok(e),
+            Err(err) => Err(format!(
+                "rules produced an invalid entity for `{}`: {err}",
+                ctx.path
+            )),
+        }
+    }
+}
+
+impl Rule {
+    fn matches(&self, ctx: &Context, eval_ctx: &HashMapContext) -> Result<bool, String> {
+        if let Some(path) = &self.path {
+            if !path.is_match(&ctx.path) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(kind) = &self.kind {
+            if !kind.eq_ignore_ascii_case(&ctx.kind) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(language) = &self.language {
+            if !language.eq_ignore_ascii_case(&ctx.language) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(when) = &self.when {
+            return match when.eval_boolean_with_context(eval_ctx) {
+                Ok(b) => Ok(b),
+                Err(err) => Err(format!(
+                    "failed to evaluate match condition for `{}`: {err}",
+                    ctx.path
+                )),
+            };
+        }
+
+        Ok(true)
+    }
+}
+
+/// build the variables available to the rules expressions.
+/// The scalar attributes of the entity come first so that
+/// the file data takes precedence over them.
+fn build_eval_context(ctx: &Context, entity: &serde_json::Value) -> Result<HashMapContext, String> {
+    let mut vars: BTreeMap<String, evalexpr::Value> = BTreeMap::new();
+
+    if let serde_json::Value::Object(fields) = entity {
+        for (k, v) in fields {
+            let v = match v {
+                serde_json::Value::Bool(b) => evalexpr::Value::Boolean(*b),
+                serde_json::Value::String(s) => evalexpr::Value::String(s.to_owned()),
+                serde_json::Value::Number(n) => match n.as_i64() {
+                    Some(i) => evalexpr::Value::Int(i),
+                    None => evalexpr::Value::Float(n.as_f64().unwrap_or_default()),
+                },
+                _ => continue,
+            };
+            vars.insert(k.to_owned(), v);
+        }
+    }
+
+    let strings = [
+        ("name", &ctx.name),
+        ("path", &ctx.path),
+        ("extension", &ctx.extension),
+        ("language", &ctx.language),
+        ("kind", &ctx.kind),
+    ];
+    for (k, v) in strings {
+        vars.insert(k.to_owned(), evalexpr::Value::String(v.to_owned()));
+    }
+
+    let numbers = [
+        (
+            "complexity",
+            &ctx.metrics.complexity,
+            &ctx.normalized.complexity,
+        ),
+        ("loc", &ctx.metrics.loc, &ctx.normalized.loc),
+        (
+            "functions",
+            &ctx.metrics.functions,
+            &ctx.normalized.functions,
+        ),
+        ("comments", &ctx.metrics.comments, &ctx.normalized.comments),
+        ("churn", &ctx.metrics.churn, &ctx.normalized.churn),
+    ];
+    for (k, raw, norm) in numbers {
+        vars.insert(k.to_owned(), evalexpr::Value::Float(*raw));
+        vars.insert(format!("{k}_norm"), evalexpr::Value::Float(*norm));
+    }
+
+    let mut eval_ctx = HashMapContext::new();
+    for (k, v) in vars {
+        if let Err(err) = eval_ctx.set_value(k.to_owned(), v) {
+            return Err(format!("failed to set `{k}` variable: {err}"));
+        }
+    }
+
+    Ok(eval_ctx)
+}
+
+fn to_json_value(v: evalexpr::Value, attribute: &str) -> Result<serde_json::Value, String> {
+    match v {
+        evalexpr::Value::String(s) => Ok(serde_json::Value::String(s)),
+        evalexpr::Value::Int(i) => Ok(serde_json::Value::from(i)),
+        evalexpr::Value::Boolean(b) => Ok(serde_json::Value::Bool(b)),
+        evalexpr::Value::Float(f) => match serde_json::Number::from_f64(f) {
+            Some(n) => Ok(serde_json::Value::Number(n)),
+            None => Err(format!("`{attribute}` evaluated to an invalid number: {f}")),
+        },
+        _ => Err(format!(
+            "`{attribute}` must evaluate to a number, a string or a boolean"
+        )),
+    }
+}
+
+fn get_attribute<'a>(
+    entity: &'a mut serde_json::Value,
+    attribute: &str,
+) -> Result<&'a mut serde_json::Value, String> {
+    let mut current = entity;
+    for key in attribute.split('.') {
+        current = match current.get_mut(key) {
+            Some(v) => v,
+            None => return Err(format!("unknown entity attribute `{attribute}`")),
+        };
+    }
+
+    Ok(current)
+}
+
+fn set_attribute(
+    entity: &mut serde_json::Value,
+    attribute: &str,
+    value: serde_json::Value,
+) -> Result<(), String> {
+    *get_attribute(entity, attribute)? = value;
+    Ok(())
+}
+
+fn clamp_attribute(
+    entity: &mut serde_json::Value,
+    attribute: &str,
+    min: f64,
+    max: f64,
+) -> Result<(), String> {
+    let v = get_attribute(entity, attribute)?;
+    let n = match v.as_f64() {
+        Some(n) => n,
+        None => return Err(format!("can't clamp non numeric attribute `{attribute}`")),
+    };
+
+    if let Some(n) = serde_json::Number::from_f64(n.clamp(min, max)) {
+        *v = serde_json::Value::Number(n);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::{rules, shmup};
+
+    #[test]
+    fn apply_rules_to_entity() {
+        let r = rules::parse(
+            r#"
+rules:
+  - match:
+      path: "src/**/*.rs"
+    set:
+      hp: "complexity_norm * 4"
+      weapon: '"fast_gun"'
+      destructible: false
+    clamp:
+      hp: [0, 1]
+  - match:
+      when: "churn > 10"
+    set:
+      speed: 1
+"#,
+        )
+        .unwrap();
+
+        let mut ctx = rules::Context {
+            path: "src/cmd/main.rs".to_string(),
+            ..Default::default()
+        };
+        ctx.normalized.complexity = 0.5;
+        ctx.metrics.churn = 2.;
+
+        let entity = r.apply(&ctx, shmup::Entity::default()).unwrap();
+        assert_eq!(entity.hp, 1.);
+        assert_eq!(entity.weapon, "fast_gun");
+        assert!(!entity.destructible);
+        assert_eq!(entity.speed, 0.);
+
+        ctx.path = "README.md".to_string();
+        ctx.metrics.churn = 12.;
+        let entity = r.apply(&ctx, shmup::Entity::default()).unwrap();
+        assert_eq!(entity.hp, 0.);
+        assert_eq!(entity.speed, 1.);
+    }
+
+    #[test]
+    fn reject_invalid_rules() {
+        assert!(rules::parse("rules:\n  - set:\n      hp: '(1 + 2'\n").is_err());
+        assert!(rules::parse("rules:\n  - match:\n      path: 'src/[*'\n").is_err());
+        assert!(rules::parse("rules:\n  - clamp:\n      hp: [1, 0]\n").is_err());
+
+        let r = rules::parse("rules:\n  - set:\n      unknown: 1\n").unwrap();
+        let ctx = rules::Context::default();
+        assert!(r.apply(&ctx, shmup::Entity::default()).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use super::{Boss, BossPhase, Data, Source};
+use crate::converters::metrics;
+use crate::{patterns, weapons};
+
+//number of files promoted to bosses repository-wide,
+//for each of the complexity and churn metrics
+pub const REPOSITORY_BOSSES: usize = 3;
+
+pub const SCOPE_REPOSITORY: &str = "repository";
+pub const SCOPE_SCENE: &str = "scene";
+pub const REASON_COMPLEXITY: &str = "complexity";
+pub const REASON_CHURN: &str = "churn";
+
+//hp of a boss phase for an entity with no hp,
+//it is scaled up to twice that value by the entity hp
+const PHASE_HP: f32 = 20.;
+
+//the weapon sets of the boss phases following the first one,
+//the first phase uses the entity weapon
+const PHASES_WEAPONS: [&[&str]; 2] = [
+    &[weapons::FAST_GUN, weapons::MULTI_DIRECTION_RECTANGLE_GUN],
+    &[
+        weapons::FAST_GUN,
+        weapons::MULTI_DIRECTION_RECTANGLE_GUN,
+        weapons::MULTI_DIRECTION_CIRCLE_GUN,
+    ],
+];
+
+/// Candidate is an entity that could be promoted to a boss
+struct Candidate {
+    scene_id: String,
+    entity_id: String,
+    metrics: metrics::FileMetrics,
+    normalized: metrics::FileMetrics,
+}
+
+/// promote the most complex and most modified files of the repository,
+/// and the most complex file of each scene, to bosses
+pub(super) fn promote(data: &mut Data, src: &Source) {
+    let mut candidates: Vec<Candidate> = vec![];
+    for scene in data.scenes.values() {
+        for entity in scene.entities.values() {
+            let blob = match src.objs.get(&entity.oid).and_then(|o| o.blob.as_ref()) {
+                Some(blob) => blob,
+                None => continue,
+            };
+            //only files with code can be promoted
+            if !src.files.contains_key(&blob.path_sha) {
+                continue;
+            }
+
+            let m = metrics::file_metrics(blob, src.files);
+            candidates.push(Candidate {
+                scene_id: scene.id.to_owned(),
+                entity_id: entity.id.to_owned(),
+                normalized: src.distributions.normalize(&m),
+                metrics: m,
+            });
+        }
+    }
+
+    //indexes of the candidates sorted by descending metric, the entity
+    //id breaks ties so that the same files are always promoted
+    let sorted_by = |keep: fn(&Candidate) -> bool, metric: fn(&Candidate) -> f64| {
+        let mut indexes: Vec<usize> = (0..candidates.len())
+            .filter(|i| keep(&candidates[*i]))
+            .collect();
+        indexes.sort_by(|a, b| {
+            let (a, b) = (&candidates[*a], &candidates[*b]);
+            metric(b)
+                .total_cmp(&metric(a))
+                .then_with(|| a.entity_id.cmp(&b.entity_id))
+        });
+        indexes
+    };
+    let by_complexity = sorted_by(|c| c.metrics.complexity > 0., |c| c.metrics.complexity);
+    //only the files modified more than the median file are promoted for their churn
+    let by_churn = sorted_by(|c| c.normalized.churn > 0.5, |c| c.metrics.churn);
+
+    let mut promoted: Vec<(usize, &str, &str)> = vec![];
+    let mut promote_candidate = |i: usize, scope, reason| {
+        if !promoted.iter().any(|(p, _, _)| *p == i) {
+            promoted.push((i, scope, reason));
+        }
+    };
+    for i in by_complexity.iter().take(REPOSITORY_BOSSES) {
+        promote_candidate(*i, SCOPE_REPOSITORY, REASON_COMPLEXITY);
+    }
+    for i in by_churn.iter().take(REPOSITORY_BOSSES) {
+        promote_candidate(*i, SCOPE_REPOSITORY, REASON_CHURN);
+    }
+
+    //each scene ends with its most complex file
+    let mut scene_bosses: Vec<(String, String)> = vec![];
+    for scene_id in data.scenes.keys() {
+        let most_complex = by_complexity
+            .iter()
+            .find(|i| &candidates[**i].scene_id == scene_id);
+        if let Some(i) = most_complex {
+            promote_candidate(*i, SCOPE_SCENE, REASON_COMPLEXITY);
+            scene_bosses.push((scene_id.to_owned(), candidates[*i].entity_id.to_owned()));
+        }
+    }
+
+    for (i, scope, reason) in promoted {
+        let c = &candidates[i];
+        if let Some(entity) = data
+            .scenes
+            .get_mut(&c.scene_id)
+            .and_then(|s| s.entities.get_mut(&c.entity_id))
+        {
+            entity.boss = Some(new_boss(entity, c, scope, reason));
+        }
+    }
+
+    for (scene_id, entity_id) in scene_bosses {
+        if let Some(scene) = data.scenes.get_mut(&scene_id) {
+            scene.boss = entity_id;
+        }
+    }
+}
+
+/// build the boss of `entity`, repository bosses have
+/// one more phase than scene bosses
+fn new_boss(entity: &super::Entity, c: &Candidate, scope: &str, reason: &str) -> Boss {
+    let number_of_phases = match scope {
+        SCOPE_REPOSITORY => 3,
+        _ => 2,
+    };
+
+    let first_weapon = match entity.weapon.as_str() {
+        weapons::NONE => weapons::SIMPLE_GUN,
+        w => w,
+    };
+
+    let phases = (0..number_of_phases)
+        .map(|i| {
+            let (weapons, movement_pattern) = match i {
+                0 => (vec![first_weapon], entity.movement_pattern.as_str()),
+                1 => (PHASES_WEAPONS[0].to_vec(), patterns::WANDER),
+                _ => (PHASES_WEAPONS[1].to_vec(), patterns::MOVE_TOWARDS),
+            };
+
+            BossPhase {
+                hp_threshold: 1. - i as f32 / number_of_phases as f32,
+                weapons: weapons.iter().map(|w| w.to_string()).collect(),
+                movement_pattern: movement_pattern.to_owned(),
+                //files declaring more functions spawn bigger waves
+                spawn_wave: i * (1 + (c.normalized.functions * 3.).round() as u32),
+            }
+        })
+        .collect();
+
+    Boss {
+        scope: scope.to_owned(),
+        reason: reason.to_owned(),
+        hp: PHASE_HP * number_of_phases as f32 * (1. + entity.hp),
+        phases,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::metrics::FileMetrics;
+    use crate::converters::shmup::{bosses, Entity};
+    use crate::{patterns, weapons};
+
+    #[test]
+    fn build_boss_phases() {
+        let entity = Entity {
+            hp: 0.5,
+            weapon: weapons::NONE.to_string(),
+            movement_pattern: patterns::NONE.to_string(),
+            ..Default::default()
+        };
+        let c = bosses::Candidate {
+            scene_id: "scene".to_string(),
+            entity_id: "entity".to_string(),
+            metrics: FileMetrics::default(),
+            normalized: FileMetrics {
+                functions: 1.,
+                ..Default::default()
+            },
+        };
+
+        let boss = bosses::new_boss(
+            &entity,
+            &c,
+            bosses::SCOPE_REPOSITORY,
+            bosses::REASON_COMPLEXITY,
+        );
+        assert_eq!(boss.phases.len(), 3);
+        assert_eq!(boss.hp, 90.);
+        assert_eq!(boss.phases[0].weapons, vec![weapons::SIMPLE_GUN]);
+        assert_eq!(boss.phases[0].spawn_wave, 0);
+        assert_eq!(boss.phases[1].hp_threshold, 1. - 1. / 3.);
+        assert_eq!(boss.phases[2].movement_pattern, patterns::MOVE_TOWARDS);
+        assert_eq!(boss.phases[2].spawn_wave, 8);
+
+        let boss = bosses::new_boss(&entity, &c, bosses::SCOPE_SCENE, bosses::REASON_COMPLEXITY);
+        assert_eq!(boss.phases.len(), 2);
+        assert_eq!(boss.phases[1].hp_threshold, 0.5);
+    }
+}
@@ -1,31 +1,68 @@
-use crate::{converters, extractor, languages, shapes};
+use crate::converters::{metrics, rules, spaces};
+use crate::error::Error;
+use crate::{converters, extractor, languages, patterns, shapes, shields, weapons};
 use rayon::prelude::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
-pub struct ShmupConverter {}
+pub mod bosses;
 
-const CONVERTER_NAME: &str = "shmup";
+pub struct ShmupConverter {
+    options: converters::Options,
+}
+
+pub const CONVERTER_NAME: &str = "shmup";
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
 pub struct Data {
     //the id of the main scene
     //it corresponds to the root tree of the git repository
+    //and is stored as the sha256 of that path.
     pub main_scene: String,
-    pub scenes: HashMap<String, Scene>,
+    //the seed used by the players to generate the world layout,
+    //it is derived from the commit sha so that the same commit
+    //always generates the same world
+    #[serde(default)]
+    pub seed: u64,
+    pub scenes: BTreeMap<String, Scene>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
 pub struct Scene {
-    pub entities: HashMap<String, Entity>,
+    //The id is the sha256 of the tree path,
+    //it stays the same across commits
+    pub id: String,
+    //the oid is the git tree object id
+    pub oid: String,
+    pub name: String,
+    //the path of the tree relative to the root of the repository
+    pub path: String,
+    //the id of the parent scene, empty for the main scene
+    pub parent: String,
+    //the depth of the scene in the scene graph, 0 for the main scene
+    pub depth: u32,
+    //stats aggregated over the scene and its sub scenes
+    pub stats: metrics::SceneStats,
+    // Entities, has the sha256 of the blob path as its key, and the entity
+    // as value.
+    pub entities: BTreeMap<String, Entity>,
     pub sub_scenes: Vec<String>,
+    //the id of the scene boss, the most complex file of the scene,
+    //empty if the scene has no code
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub boss: String,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
 pub struct Entity {
+    //The id is the sha256 of the blob path,
+    //it stays the same across commits
     pub id: String,
+    //the oid is the git blob object id
+    pub oid: String,
     //the scene id the object belongs to
     pub scene_id: String,
     pub name: String,
@@ -42,15 +79,90 @@ pub struct Entity {
     pub size: f32,
     pub shield: String,
     pub destructible: bool,
+    //turrets are built from the functions, classes, impls...
+    //declared in the file, when sub entities are enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turrets: Vec<Turret>,
+    //set when the entity is promoted to a boss
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boss: Option<Boss>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Boss {
+    //repository | scene, whether the boss is one of the top files
+    //of the repository or the most complex file of its scene
+    pub scope: String,
+    //the metric the entity was promoted for: complexity | churn
+    pub reason: String,
+    //the boss hp pool, in bullet damages
+    pub hp: f32,
+    pub phases: Vec<BossPhase>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct BossPhase {
+    //the phase starts once the ratio of hp left drops
+    //to the threshold, a value between [0-1]
+    pub hp_threshold: f32,
+    pub weapons: Vec<String>,
+    pub movement_pattern: String,
+    //the number of minions spawned when the phase starts
+    pub spawn_wave: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone)]
+pub struct Turret {
+    //The id is the sha256 of the file path and of the
+    //qualified name of the space the turret is built from
+    pub id: String,
+    pub name: String,
+    //the kind of space the turret is built from
+    //function | class | struct | trait | impl | namespace
+    pub kind: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    //the hp is the share of the file complexity held
+    //by the turret, a value between [0-1]
+    pub hp: f32,
+    //the size is the share of the file lines of code held
+    //by the turret, a value between [0-1]
+    pub size: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turrets: Vec<Turret>,
+}
+
+/// Source holds the extracted data used to build the scenes
+struct Source<'a> {
+    objs: &'a HashMap<String, extractor::git::Object>,
+    files: &'a HashMap<String, extractor::code::FileData>,
+    distributions: metrics::Distributions,
+    options: &'a converters::Options,
+}
+
+impl metrics::StatsScene for Scene {
+    fn sub_scenes(&self) -> &Vec<String> {
+        &self.sub_scenes
+    }
+
+    fn stats_mut(&mut self) -> &mut metrics::SceneStats {
+        &mut self.stats
+    }
 }
 
 pub fn new() -> ShmupConverter {
-    ShmupConverter {}
+    ShmupConverter {
+        options: converters::Options::default(),
+    }
+}
+
+pub fn with_options(options: converters::Options) -> ShmupConverter {
+    ShmupConverter { options }
 }
 
 impl converters::Converter<Data> for ShmupConverter {
-    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, String> {
-        Ok(build_converter_data(extracted_data))
+    fn run(&self, extracted_data: &extractor::Data) -> Result<Data, Error> {
+        build_converter_data(extracted_data, &self.options)
     }
 
     fn name(&self) -> String {
@@ -58,108 +170,218 @@ impl converters::Converter<Data> for ShmupConverter {
     }
 }
 
-fn build_converter_data(extracted_data: &extractor::Data) -> Data {
+fn build_converter_data(
+    extracted_data: &extractor::Data,
+    options: &converters::Options,
+) -> Result<Data, Error> {
     let commit_oid = &extracted_data.git.ref_target.1;
-    let objs = &extracted_data.git.objects;
-    let files = &extracted_data.code.files_data;
+    let src = Source {
+        objs: &extracted_data.git.objects,
+        files: &extracted_data.code.files_data,
+        distributions: metrics::new_distributions(extracted_data),
+        options,
+    };
 
     let data = Data {
         main_scene: get_main_scene(extracted_data),
+        seed: converters::seed_from_commit(commit_oid),
         ..Default::default()
     };
 
     let mut_data = Arc::new(Mutex::new(data));
 
     //Get the initial commit
-    if let Some(commit) = &objs[commit_oid].commit {
+    if let Some(commit) = &src.objs[commit_oid].commit {
         let trees_oid = vec![commit.tree.to_owned()];
-        add_scenes(&trees_oid, mut_data.to_owned(), objs, files);
+        add_scenes(&trees_oid, "", 0, mut_data.to_owned(), &src)?;
     }
 
-    let data = mut_data.lock().unwrap().to_owned();
-    data
+    let mut data = mut_data.lock().unwrap().to_owned();
+    let main_scene = data.main_scene.to_owned();
+    metrics::aggregate_stats(&main_scene, &mut data.scenes);
+    bosses::promote(&mut data, &src);
+    Ok(data)
 }
 
+// Gets sha256 path of the root directory.
 fn get_main_scene(data: &extractor::Data) -> String {
     let root_commit_id = &data.git.ref_target.1;
-    if let Some(root_commit) = &data.git.objects[root_commit_id].commit {
-        return root_commit.tree.to_string();
-    }
 
-    "".to_string()
+    let Some(commit) = &data.git.objects[root_commit_id].commit else {
+        return String::new();
+    };
+
+    let Some(tree) = &data.git.objects[&commit.tree].tree else {
+        return String::new();
+    };
+
+    tree.path_sha.clone()
 }
 
 fn add_scenes(
     trees_oid: &Vec<String>,
+    parent: &str,
+    depth: u32,
     data: Arc<Mutex<Data>>,
-    objs: &HashMap<String, extractor::git::Object>,
-    files: &HashMap<String, extractor::code::FileData>,
-) {
-    trees_oid.par_iter().for_each(|tree_oid| {
-        if let Some(tree) = &objs[tree_oid].tree {
+    src: &Source,
+) -> Result<(), Error> {
+    trees_oid.par_iter().try_for_each(|tree_oid| {
+        if let Some(tree) = &src.objs[tree_oid].tree {
             let mut scene = Scene {
+                id: tree.path_sha.to_owned(),
+                oid: tree.sha.to_owned(),
+                name: tree.name.to_owned(),
+                path: tree.path.to_owned(),
+                parent: parent.to_owned(),
+                depth,
                 ..Default::default()
             };
+            let mut sub_scene_oids: Vec<String> = vec![];
 
             // Create entities
             for oid in &tree.objects {
-                if let Some(blob) = &objs[oid].blob {
-                    let mut entity = blob_to_entity(blob, files);
-                    entity.scene_id = oid.to_owned();
-                    scene.entities.insert(oid.to_owned(), entity);
-                } else {
-                    scene.sub_scenes.push(oid.to_owned());
+                if let Some(blob) = &src.objs[oid].blob {
+                    let mut entity = blob_to_entity(blob, src)?;
+                    entity.scene_id = scene.id.to_owned();
+                    scene.entities.insert(entity.id.to_owned(), entity);
+
+                    let m = metrics::file_metrics(blob, src.files);
+                    scene.stats.add_file(blob, &m);
+                } else if let Some(sub_tree) = &src.objs[oid].tree {
+                    // Store sub_scenes path_sha
+                    scene.sub_scenes.push(sub_tree.path_sha.to_owned());
+                    sub_scene_oids.push(oid.to_owned());
                 }
             }
 
-            if !scene.sub_scenes.is_empty() {
-                add_scenes(&scene.sub_scenes, data.to_owned(), objs, files);
+            scene.sub_scenes.sort();
+            if !sub_scene_oids.is_empty() {
+                add_scenes(&sub_scene_oids, &scene.id, depth + 1, data.to_owned(), src)?;
             }
 
             data.lock()
                 .unwrap()
                 .scenes
-                .insert(tree.sha.to_owned(), scene);
+                .insert(scene.id.to_owned(), scene);
         }
-    });
+
+        Ok(())
+    })
 }
 
-fn blob_to_entity(
-    blob: &extractor::git::Blob,
-    files: &HashMap<String, extractor::code::FileData>,
-) -> Entity {
+/// build an entity from the blob and its metrics.
+///
+/// Every gameplay attribute is derived from the file metrics normalized
+/// against the rest of the repository:
+/// - the cyclomatic complexity sets the hp
+/// - the lines of code set the size
+/// - the number of functions picks the weapon
+/// - the churn sets the speed and the movement pattern
+/// - the comment lines pick the shield
+///
+/// Files that could not be analysed (docs, data, assets...) are
+/// indestructible and unarmed.
+///
+/// The `rules` are applied on top of that default mapping.
+fn blob_to_entity(blob: &extractor::git::Blob, src: &Source) -> Result<Entity, Error> {
+    let (files, distributions) = (src.files, &src.distributions);
     let languages = languages::new();
-    Entity {
-        id: blob.sha.to_owned(),
+    let has_code = files.contains_key(&blob.path_sha);
+    let raw = metrics::file_metrics(blob, files);
+    let m = distributions.normalize(&raw);
+    let mut entity = Entity {
+        id: blob.path_sha.to_owned(),
+        oid: blob.sha.to_owned(),
         name: blob.name.to_owned(),
         color: get_color(blob, &languages),
         kind: get_kind(blob, &languages),
-        speed: get_speed(blob, files),
-        hp: 1.,
+        hp: m.complexity as f32,
+        size: m.loc as f32,
+        speed: m.churn as f32,
+        weapon: get_weapon(has_code, m.functions).to_owned(),
+        movement_pattern: get_movement_pattern(m.churn).to_owned(),
+        shield: get_shield(has_code, m.comments).to_owned(),
+        destructible: has_code,
         ..Default::default()
+    };
+
+    if src.options.sub_entities {
+        entity.turrets = spaces::from_blob(blob, files)
+            .iter()
+            .map(|s| space_to_turret(s, &raw))
+            .collect();
+    }
+
+    let rules = &src.options.rules;
+    if rules.is_empty() {
+        return Ok(entity);
     }
+
+    let ctx = rules::new_context(blob, files, distributions, &languages);
+    rules.apply(&ctx, entity)
 }
 
-/// This function returns the speed of an entity
-/// relative its spaces
-///
-/// It's important to note that this function is
-/// a placeholder and is only used to demonstrate
-/// how we can map files with blob data
-fn get_speed(
-    blob: &extractor::git::Blob,
-    files: &HashMap<String, extractor::code::FileData>,
-) -> f32 {
-    match files.get(&blob.path_sha) {
-        Some(file) => {
-            let ret = 1. / file.spaces.spaces.len() as f32;
-            if ret.is_infinite() {
-                1.
-            } else {
-                ret
-            }
+/// build a turret from a space declared in a file with `file_metrics`
+fn space_to_turret(space: &spaces::Space, file_metrics: &metrics::FileMetrics) -> Turret {
+    let share = |v: f64, total: f64| {
+        if total > 0. {
+            (v / total).clamp(0., 1.) as f32
+        } else {
+            0.
         }
-        None => 0.1,
+    };
+
+    Turret {
+        id: space.id.to_owned(),
+        name: space.name.to_owned(),
+        kind: space.kind.to_owned(),
+        start_line: space.start_line,
+        end_line: space.end_line,
+        hp: share(space.complexity, file_metrics.complexity),
+        size: share(space.loc, file_metrics.loc),
+        turrets: space
+            .spaces
+            .iter()
+            .map(|s| space_to_turret(s, file_metrics))
+            .collect(),
+    }
+}
+
+/// pick a weapon from the normalized number of functions,
+/// the more functions a file has the more dangerous its weapon is
+fn get_weapon(has_code: bool, functions: f64) -> &'static str {
+    if !has_code {
+        return weapons::NONE;
+    }
+
+    match functions {
+        f if f < 0.25 => weapons::SIMPLE_GUN,
+        f if f < 0.5 => weapons::FAST_GUN,
+        f if f < 0.75 => weapons::MULTI_DIRECTION_RECTANGLE_GUN,
+        _ => weapons::MULTI_DIRECTION_CIRCLE_GUN,
+    }
+}
+
+/// pick a movement pattern from the normalized churn,
+/// files that rarely change stay still while hot files chase the player
+fn get_movement_pattern(churn: f64) -> &'static str {
+    match churn {
+        c if c <= 0.5 => patterns::NONE,
+        c if c < 0.75 => patterns::WANDER,
+        _ => patterns::MOVE_TOWARDS,
+    }
+}
+
+/// pick a shield from the normalized number of comment lines
+fn get_shield(has_code: bool, comments: f64) -> &'static str {
+    if !has_code {
+        return shields::NONE;
+    }
+
+    match comments {
+        c if c < 0.5 => shields::NONE,
+        c if c < 0.75 => shields::HALF_GUARD,
+        _ => shields::FULL,
     }
 }
 
@@ -0,0 +1,111 @@
+use crate::{extractor, hash};
+use rust_code_analysis::FuncSpace;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Space is a function, class, impl... declared in a file.
+/// Converters use them to build the sub entities of a file entity.
+#[derive(Serialize, Deserialize, JsonSchema, Default, Debug, Clone, PartialEq)]
+pub struct Space {
+    //The id is the sha256 of the file path and of the qualified
+    //name of the space, it stays the same across commits
+    pub id: String,
+    pub name: String,
+    //the kind of space: function | class | struct | trait | impl | namespace
+    pub kind: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    //cyclomatic complexity of the space
+    pub complexity: f64,
+    //source lines of code of the space
+    pub loc: f64,
+    //number of functions declared in the space
+    pub functions: f64,
+    //the spaces nested in that space
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spaces: Vec<Space>,
+}
+
+/// returns the spaces declared in the file pointed by `blob`,
+/// or an empty list if the file could not be analysed
+pub fn from_blob(
+    blob: &extractor::git::Blob,
+    files: &HashMap<String, extractor::code::FileData>,
+) -> Vec<Space> {
+    match files.get(&blob.path_sha) {
+        Some(file) => from_func_spaces(&blob.path, "", &file.spaces.spaces),
+        None => vec![],
+    }
+}
+
+fn from_func_spaces(path: &str, parent: &str, func_spaces: &[FuncSpace]) -> Vec<Space> {
+    //Spaces with the same name, as overloaded functions,
+    //are told apart by their position among their siblings
+    let mut seen: HashMap<String, u32> = HashMap::new();
+
+    func_spaces
+        .iter()
+        .map(|s| {
+            let name = s.name.to_owned().unwrap_or_default();
+            let count = seen.entry(name.to_owned()).or_default();
+            let qualified_name = match *count {
+                0 => format!("{parent}::{name}"),
+                n => format!("{parent}::{name}#{n}"),
+            };
+            *count += 1;
+
+            Space {
+                id: hash::new(format!("{path}{qualified_name}")),
+                kind: s.kind.to_string(),
+                start_line: s.start_line,
+                end_line: s.end_line,
+                complexity: s.metrics.cyclomatic.cyclomatic_sum(),
+                loc: s.metrics.loc.sloc(),
+                functions: s.metrics.nom.functions_sum(),
+                spaces: from_func_spaces(path, &qualified_name, &s.spaces),
+                name,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converters::spaces;
+    use crate::extractor::code;
+    use rust_code_analysis::SpaceKind;
+
+    #[test]
+    fn build_spaces_from_func_spaces() {
+        let func = |name: &str, kind: SpaceKind, spaces| {
+            let mut s = code::default_func_spaces();
+            s.name = Some(name.to_string());
+            s.kind = kind;
+            s.spaces = spaces;
+            s
+        };
+        let func_spaces = vec![
+            func(
+                "Foo",
+                SpaceKind::Impl,
+                vec![func("new", SpaceKind::Function, vec![])],
+            ),
+            func("Foo", SpaceKind::Impl, vec![]),
+            func("main", SpaceKind::Function, vec![]),
+        ];
+
+        let s = spaces::from_func_spaces("src/main.rs", "", &func_spaces);
+        assert_eq!(s.len(), 3);
+        assert_eq!(s[0].kind, "impl");
+        assert_eq!(s[0].spaces[0].name, "new");
+        assert_eq!(s[0].spaces[0].kind, "function");
+        //spaces sharing the same name get different ids
+        assert_ne!(s[0].id, s[1].id);
+        assert_eq!(s[0].id, crate::hash::new("src/main.rs::Foo".to_string()));
+        assert_eq!(
+            s[0].spaces[0].id,
+            crate::hash::new("src/main.rs::Foo::new".to_string())
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::io;
+
+/// Source is the underlying error of an `Error`
+pub type Source = Box<dyn std::error::Error + Send + Sync>;
+
+/// Error is the error returned by the waking-git-core functions,
+/// its kind tells which step failed.
+///
+/// The message describes what was done when the error occurred,
+/// the underlying error, if any, is returned by `source`. It is also
+/// printed after the message, the errors must therefore be printed
+/// without walking their sources
+#[derive(Debug)]
+pub enum Error {
+    /// the repository url cannot be scanned
+    InvalidUrl(String),
+    /// the repository could not be cloned, opened or fetched
+    Clone {
+        message: String,
+        source: Option<Source>,
+    },
+    /// the ref traversed by the extractor is missing from the repository
+    RefNotFound(git2::Error),
+    /// the repository data could not be extracted
+    Extract {
+        message: String,
+        source: Option<Source>,
+    },
+    /// a file, an option or an expression is invalid
+    Parse {
+        message: String,
+        source: Option<Source>,
+    },
+    /// the extracted data could not be converted
+    Convert {
+        message: String,
+        source: Option<Source>,
+    },
+    /// a file or a folder could not be read or written
+    Io { message: String, source: io::Error },
+    /// the data could not be encoded or decoded
+    Serialize {
+        message: String,
+        source: Option<Source>,
+    },
+    /// the players binary could not be found, installed or run.
+    /// `code` is the exit code of the player that failed, 1 otherwise
+    Player {
+        message: String,
+        code: i32,
+        source: Option<Source>,
+    },
+}
+
+impl Error {
+    /// returns a parse error without an underlying error
+    pub fn parse(message: impl Into<String>) -> Error {
+        Error::Parse {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// returns a convert error without an underlying error
+    pub fn convert(message: impl Into<String>) -> Error {
+        Error::Convert {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// returns an io error, `message` tells what was done with the file
+    pub fn io(message: impl Into<String>, source: io::Error) -> Error {
+        Error::Io {
+            message: message.into(),
+            source,
+        }
+    }
+
+    /// returns a player error without an underlying error
+    pub fn player(message: impl Into<String>) -> Error {
+        Error::Player {
+            message: message.into(),
+            code: 1,
+            source: None,
+        }
+    }
+
+    /// returns the exit code of the player that failed, 1 for the other errors
+    pub fn player_code(&self) -> i32 {
+        match self {
+            Error::Player { code, .. } => *code,
+            _ => 1,
+        }
+    }
+
+    /// returns a serialize error caused by `source`
+    pub fn serialize(message: impl Into<String>, source: impl Into<Source>) -> Error {
+        Error::Serialize {
+            message: message.into(),
+            source: Some(source.into()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        //the underlying error is printed after the message, so that
+        //the errors printed as they are keep all their details
+        let (message, source): (&str, Option<&dyn fmt::Display>) = match self {
+            Error::InvalidUrl(message) => (message, None),
+            Error::RefNotFound(source) => ("failed to find the ref to traverse", Some(source)),
+            Error::Io { message, source } => (message, Some(source)),
+            Error::Clone { message, source }
+            | Error::Extract { message, source }
+            | Error::Parse { message, source }
+            | Error::Convert { message, source }
+            | Error::Serialize { message, source }
+            | Error::Player {
+                message, source, ..
+            } => (message, source.as_ref().map(|s| s as &dyn fmt::Display)),
+        };
+        match source {
+            Some(s) => write!(f, "{message}: {s}"),
+            None => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidUrl(_) => None,
+            Error::RefNotFound(source) => Some(source),
+            Error::Io { source, .. } => Some(source),
+            Error::Clone { source, .. }
+            | Error::Extract { source, .. }
+            | Error::Parse { source, .. }
+            | Error::Convert { source, .. }
+            | Error::Serialize { source, .. }
+            | Error::Player { source, .. } => source
+                .as_ref()
+                .map(|s| s.as_ref() as &(dyn std::error::Error + 'static)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use std::error::Error as _;
+    use std::io;
+
+    #[test]
+    fn print_errors_with_their_source() {
+        let err = Error::io(
+            "failed to read `a.json`",
+            io::Error::new(io::ErrorKind::NotFound, "not found"),
+        );
+        assert_eq!(err.to_string(), "failed to read `a.json`: not found");
+        assert_eq!(err.source().unwrap().to_string(), "not found");
+
+        let err = Error::parse("unknown format `xml`");
+        assert_eq!(err.to_string(), "unknown format `xml`");
+        assert!(err.source().is_none());
+        assert!(matches!(err, Error::Parse { .. }));
+        assert_eq!(err.player_code(), 1);
+
+        //wake exits with the code of the player that failed
+        let err = Error::Player {
+            message: "boom".to_string(),
+            code: 3,
+            source: None,
+        };
+        assert_eq!(err.player_code(), 3);
+    }
+}
@@ -1,50 +1,461 @@
 use crate::config;
+use crate::converters::{dungeon, shmup};
+use crate::error::Error;
+use std::collections::VecDeque;
+use std::env;
 use std::fs;
-use std::io;
-use std::path;
-use std::process::Command;
-
-pub fn run_player(player: String, file: String) -> Result<String, io::Error> {
-    let players_bin = get_players_bin();
-
-    //check that players_bin file exists
-    if !path::Path::new(&players_bin).exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("`{players_bin}` file not found"),
-        ));
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+pub mod session;
+
+pub const PLAYERS_BIN_NAME: &str = "players";
+//the environment variable overriding the path to the players binary
+pub const PLAYERS_BIN_ENV: &str = "WAKE_PLAYERS_BIN";
+//the environment variable asking the players to print colors,
+//even though their output is piped to wake
+pub const CLICOLOR_FORCE_ENV: &str = "CLICOLOR_FORCE";
+
+//the number of lines of the player stderr reported when it fails
+const MAX_REPORTED_STDERR_LINES: usize = 20;
+
+/// Player is a subcommand of the players binary,
+/// playing the data of a converter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Player {
+    pub name: &'static str,
+    //the converter generating the data the player reads
+    pub converter: &'static str,
+    pub description: &'static str,
+}
+
+/// The players available in the players binary
+pub const PLAYERS: [Player; 2] = [
+    Player {
+        name: "shmup",
+        converter: shmup::CONVERTER_NAME,
+        description: "a shoot'em up game, opened in a window",
+    },
+    Player {
+        name: "dungeon",
+        converter: dungeon::CONVERTER_NAME,
+        description: "a roguelike dungeon, drawn in the terminal",
+    },
+];
+
+/// returns the player named `name`
+pub fn find_player(name: &str) -> Result<Player, Error> {
+    match PLAYERS.iter().find(|p| p.name == name) {
+        Some(p) => Ok(*p),
+        None => Err(Error::parse(format!(
+            "unknown player `{name}`, expected one of: {}",
+            PLAYERS.map(|p| p.name).join(", ")
+        ))),
     }
+}
 
-    let output = Command::new(players_bin)
-        .arg(player)
+/// check that the players binary provides the `player` subcommand
+pub fn check_player(players_bin: &str, player: &str) -> Result<(), Error> {
+    let output = match Command::new(players_bin).arg(player).arg("--help").output() {
+        Ok(o) => o,
+        Err(err) => return Err(run_error(players_bin, err)),
+    };
+    if !output.status.success() {
+        return Err(Error::player(format!(
+            "`{players_bin}` does not provide the `{player}` player, \
+            reinstall it with `wake players install`"
+        )));
+    }
+    Ok(())
+}
+
+//returns the error of the players binary that could not be run
+fn run_error(players_bin: &str, err: io::Error) -> Error {
+    Error::Player {
+        message: format!("failed to run `{players_bin}`"),
+        code: 1,
+        source: Some(Box::new(err)),
+    }
+}
+
+/// run `player` on the converted `file`, its stdout and stderr are
+/// streamed to the wake ones as they come. returns the session
+/// the player wrote to `result_file`.
+///
+/// With `watch`, the player reloads the world every time `file` changes
+pub fn run_player(
+    players_bin: &str,
+    player: &str,
+    file: &str,
+    result_file: &str,
+    watch: bool,
+) -> Result<session::Session, Error> {
+    //the result of a previous session must not be reported
+    let _ = fs::remove_file(result_file);
+
+    let mut cmd = Command::new(players_bin);
+    cmd.arg(player)
         .arg(file)
-        .spawn()?
-        .wait_with_output()?;
+        .arg("--result")
+        .arg(result_file)
+        .args(watch.then_some("--watch"))
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if io::stdout().is_terminal() {
+        cmd.env(CLICOLOR_FORCE_ENV, "1");
+    }
 
-    if !output.status.success() {
-        let err = String::from_utf8(output.stderr).unwrap_or(String::from(""));
-        return Err(io::Error::other(err));
+    let mut child = match cmd.spawn() {
+        Ok(c) => c,
+        Err(err) => {
+            return Err(Error::Player {
+                message: format!("failed to start `{players_bin}`"),
+                code: 1,
+                source: Some(Box::new(err)),
+            })
+        }
+    };
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| thread::spawn(move || stream(out, io::stdout(), 0)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| thread::spawn(move || stream(err, io::stderr(), MAX_REPORTED_STDERR_LINES)));
+
+    let status = child.wait();
+    if let Some(t) = stdout {
+        let _ = t.join();
     }
+    let lines = match stderr {
+        Some(t) => t.join().unwrap_or_default(),
+        None => vec![],
+    };
 
-    let out = String::from_utf8(output.stdout).unwrap_or(String::from(""));
-    Ok(out)
+    let status = match status {
+        Ok(s) => s,
+        Err(err) => {
+            return Err(Error::Player {
+                message: format!("failed to wait for `{player}`"),
+                code: 1,
+                source: Some(Box::new(err)),
+            })
+        }
+    };
+    if !status.success() {
+        let message = match lines.is_empty() {
+            true => format!("`{player}` player exited with {status}"),
+            false => lines.join("\n"),
+        };
+        //a player killed by a signal has no exit code
+        return Err(Error::Player {
+            message,
+            code: status.code().unwrap_or(1),
+            source: None,
+        });
+    }
+
+    session::load(result_file)
 }
 
-fn get_players_bin() -> String {
-    let dir = config::get_home_dir();
-    let wake_path = format!("{}/{}", dir, config::WAKE_FOLDER);
-    let bin = format!("{wake_path}/bin");
+//copy the lines read from `reader` to `writer` as they come,
+//and returns the last `keep` ones
+fn stream(reader: impl Read, mut writer: impl Write, keep: usize) -> Vec<String> {
+    let mut reader = BufReader::new(reader);
+    let mut lines = VecDeque::new();
+    let mut line = vec![];
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => (),
+        }
+        let _ = writer.write_all(&line);
+        let _ = writer.flush();
+
+        if keep == 0 {
+            continue;
+        }
+        if lines.len() == keep {
+            lines.pop_front();
+        }
+        lines.push_back(String::from_utf8_lossy(&line).trim_end().to_owned());
+    }
+    lines.into()
+}
+
+/// returns the folder the players binary is installed in, `~/.wake/bin`
+pub fn get_bin_dir() -> String {
+    format!("{}/bin", config::Config::new().wake_path)
+}
+
+/// returns the path to the players binary, looked up in order from:
+/// - the `WAKE_PLAYERS_BIN` environment variable
+/// - the wake bin folder, `~/.wake/bin`
+/// - the folder of the current executable
+/// - the folders of the `PATH` environment variable
+pub fn find_players_bin() -> Result<String, Error> {
+    if let Ok(bin) = env::var(PLAYERS_BIN_ENV) {
+        if Path::new(&bin).is_file() {
+            return Ok(bin);
+        }
+        return Err(Error::player(format!(
+            "`{bin}` file set in `{PLAYERS_BIN_ENV}` not found"
+        )));
+    }
+
+    let mut dirs = vec![PathBuf::from(get_bin_dir())];
+    dirs.extend(get_build_dirs());
+    match find_in(&dirs) {
+        Some(bin) => Ok(bin),
+        None => Err(Error::player(format!(
+            "`{PLAYERS_BIN_NAME}` binary not found, build it with `cargo build -p players` \
+            and install it with `wake players install`"
+        ))),
+    }
+}
 
-    //Create the bin folder if it doesn't exist
-    let path = path::Path::new(&bin);
-    if !path.exists() {
-        match fs::create_dir_all(&bin) {
-            Ok(_) => {}
-            Err(err) => {
-                println!("Error: failed to create players bin folder: {err}");
+/// install the players binary `source` in the wake bin folder, as a copy,
+/// or as a symbolic link when `link` is set. returns the installed path.
+///
+/// `source` defaults to the players binary found next to
+/// the current executable or in the `PATH`
+pub fn install_players(source: Option<String>, link: bool) -> Result<String, Error> {
+    let source = match source {
+        Some(s) => s,
+        None => match find_in(&get_build_dirs()) {
+            Some(s) => s,
+            None => {
+                return Err(Error::player(format!(
+                    "`{PLAYERS_BIN_NAME}` binary not found next to `wake` or in the `PATH`, \
+                    build it with `cargo build -p players`"
+                )))
             }
+        },
+    };
+
+    install(Path::new(&source), Path::new(&get_bin_dir()), link)
+}
+
+/// returns the version printed by `players --version`
+pub fn players_version(players_bin: &str) -> Result<String, Error> {
+    let output = match Command::new(players_bin).arg("--version").output() {
+        Ok(o) => o,
+        Err(err) => return Err(run_error(players_bin, err)),
+    };
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr).into_owned();
+        return Err(Error::player(err));
+    }
+
+    //clap prints the binary name followed by its version
+    let out = String::from_utf8_lossy(&output.stdout);
+    match out.split_whitespace().last() {
+        Some(version) => Ok(version.to_owned()),
+        None => Err(Error::player(format!(
+            "`{players_bin} --version` printed no version"
+        ))),
+    }
+}
+
+/// check that the players binary has the same `version` as wake,
+/// so that it can read the converted data
+pub fn check_players_version(players_bin: &str, version: &str) -> Result<(), Error> {
+    let players = players_version(players_bin)?;
+    if players != version {
+        return Err(Error::player(format!(
+            "`{players_bin}` version {players} does not match wake version {version}, \
+            reinstall it with `wake players install`"
+        )));
+    }
+    Ok(())
+}
+
+//returns the folders a freshly built players binary can be found in,
+//the folder of the current executable and the folders of the `PATH`
+fn get_build_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(dir) = env::current_exe()
+        .ok()
+        .and_then(|e| e.parent().map(Path::to_path_buf))
+    {
+        dirs.push(dir);
+    }
+    if let Some(path) = env::var_os("PATH") {
+        dirs.extend(env::split_paths(&path));
+    }
+    dirs
+}
+
+//returns the first players binary found in `dirs`
+fn find_in(dirs: &[PathBuf]) -> Option<String> {
+    let name = format!("{PLAYERS_BIN_NAME}{}", env::consts::EXE_SUFFIX);
+    dirs.iter()
+        .map(|d| d.join(&name))
+        .find(|p| p.is_file())
+        .map(|p| p.to_string_lossy().into_owned())
+}
+
+fn install(source: &Path, dest_dir: &Path, link: bool) -> Result<String, Error> {
+    if !source.is_file() {
+        return Err(Error::player(format!(
+            "`{}` file not found",
+            source.display()
+        )));
+    }
+
+    if let Err(err) = fs::create_dir_all(dest_dir) {
+        return Err(Error::io(
+            format!("failed to create `{}`", dest_dir.display()),
+            err,
+        ));
+    }
+    let name = format!("{PLAYERS_BIN_NAME}{}", env::consts::EXE_SUFFIX);
+    let dest = dest_dir.join(name);
+    let source = match fs::canonicalize(source) {
+        Ok(s) => s,
+        Err(err) => {
+            return Err(Error::io(
+                format!("failed to resolve `{}`", source.display()),
+                err,
+            ))
         }
+    };
+    if fs::canonicalize(&dest).is_ok_and(|d| d == source) && !dest.is_symlink() {
+        return Err(Error::player(format!(
+            "`{}` is already installed",
+            source.display()
+        )));
+    }
+
+    //the previous binary or link is replaced
+    if dest.is_symlink() || dest.exists() {
+        if let Err(err) = fs::remove_file(&dest) {
+            return Err(Error::io(
+                format!("failed to remove `{}`", dest.display()),
+                err,
+            ));
+        }
+    }
+    let installed = match link {
+        true => symlink(&source, &dest),
+        false => fs::copy(&source, &dest).map(|_| ()),
+    };
+    if let Err(err) = installed {
+        return Err(Error::io(
+            format!("failed to install `{}`", dest.display()),
+            err,
+        ));
+    }
+
+    Ok(dest.to_string_lossy().into_owned())
+}
+
+#[cfg(unix)]
+fn symlink(source: &Path, dest: &Path) -> Result<(), io::Error> {
+    std::os::unix::fs::symlink(source, dest)
+}
+
+#[cfg(windows)]
+fn symlink(source: &Path, dest: &Path) -> Result<(), io::Error> {
+    std::os::windows::fs::symlink_file(source, dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exec;
+    use crate::utils::test;
+    #[cfg(unix)]
+    use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+    //fake binaries are shell scripts, so the tests running them are unix only
+
+    //write a fake players binary printing `version`
+    #[cfg(unix)]
+    fn fake_players(dir: &Path, version: &str) -> String {
+        fs::create_dir_all(dir).unwrap();
+        let bin = dir.join(exec::PLAYERS_BIN_NAME);
+        fs::write(&bin, format!("#!/bin/sh\necho \"players {version}\"\n")).unwrap();
+        fs::set_permissions(&bin, fs::Permissions::from_mode(0o755)).unwrap();
+        bin.to_string_lossy().into_owned()
+    }
+
+    //write a fake players binary running `script`
+    #[cfg(unix)]
+    fn fake_script(dir: &Path, script: &str) -> String {
+        fs::create_dir_all(dir).unwrap();
+        let bin = dir.join(exec::PLAYERS_BIN_NAME);
+        fs::write(&bin, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&bin, fs::Permissions::from_mode(0o755)).unwrap();
+        bin.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn run_player_and_report_the_session() {
+        let dir = test::temp_dir();
+        let result = dir.join("result.json");
+
+        //the result file is the 4th argument, after `--result`
+        let bin = fake_script(
+            &dir.path,
+            r#"echo "playing $2"
+echo '{"player":"shmup","score":42,"time_played":1.5,"scenes_visited":["a","b"]}' > "$4""#,
+        );
+        let session = exec::run_player(&bin, "shmup", "data.json", &result, false).unwrap();
+        assert_eq!(session.score, 42);
+        assert_eq!(session.scenes_visited, vec!["a", "b"]);
+
+        let bin = fake_script(&dir.path, "echo 'boom' >&2\nexit 3");
+        let err = exec::run_player(&bin, "shmup", "data.json", &result, false).unwrap_err();
+        assert_eq!(err.player_code(), 3);
+        assert_eq!(err.to_string(), "boom");
+
+        //players must write their result
+        let bin = fake_script(&dir.path, "exit 0");
+        let err = exec::run_player(&bin, "shmup", "data.json", &result, false).unwrap_err();
+        assert!(
+            err.to_string().contains("failed to read result file"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn find_players_by_name() {
+        let player = exec::find_player("dungeon").unwrap();
+        assert_eq!(player.converter, "dungeon");
+        let err = exec::find_player("racing").unwrap_err();
+        assert!(err.to_string().contains("shmup, dungeon"), "{err}");
     }
 
-    format!("{}/{}", bin, "players")
+    #[test]
+    #[cfg(unix)]
+    fn find_install_and_check_players() {
+        let dir = test::temp_dir();
+        let build = dir.path.join("build");
+        let bin_dir = dir.path.join("bin");
+        let source = fake_players(&build, "0.1.0");
+
+        let dirs = [bin_dir.to_owned(), build.to_owned()];
+        assert_eq!(exec::find_in(&dirs), Some(source.to_owned()));
+
+        //installed binaries are found first
+        let installed = exec::install(Path::new(&source), &bin_dir, false).unwrap();
+        assert_eq!(exec::find_in(&dirs), Some(installed.to_owned()));
+        assert!(exec::install(Path::new(&installed), &bin_dir, false).is_err());
+
+        exec::check_players_version(&installed, "0.1.0").unwrap();
+        let err = exec::check_players_version(&installed, "0.2.0").unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{err}");
+
+        //links follow the built binary
+        let linked = exec::install(Path::new(&source), &bin_dir, true).unwrap();
+        assert!(Path::new(&linked).is_symlink());
+        fake_players(&build, "0.2.0");
+        assert_eq!(exec::players_version(&linked).unwrap(), "0.2.0");
+    }
 }
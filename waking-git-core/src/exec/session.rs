@@ -0,0 +1,72 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Session is how a player session went, the player
+/// writes it to the result file when it exits
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Session {
+    //the name of the player
+    pub player: String,
+    pub score: u64,
+    //the time played, in seconds
+    pub time_played: f64,
+    //the ids of the scenes visited, in the order they were visited
+    pub scenes_visited: Vec<String>,
+}
+
+impl Session {
+    /// write the session to the json file located at `path`
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let content = match serde_json::to_vec_pretty(self) {
+            Ok(c) => c,
+            Err(err) => return Err(Error::serialize("failed to serialize session", err)),
+        };
+
+        if let Some(dir) = Path::new(path).parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                return Err(Error::io(
+                    format!("failed to create `{}`", dir.display()),
+                    err,
+                ));
+            }
+        }
+
+        match fs::write(path, content) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::io(
+                format!("failed to write result file `{path}`"),
+                err,
+            )),
+        }
+    }
+
+    /// record a visit of the scene `id`, unless it is the current scene
+    pub fn visit(&mut self, id: &str) {
+        if self.scenes_visited.last().map(String::as_str) != Some(id) {
+            self.scenes_visited.push(id.to_owned());
+        }
+    }
+}
+
+/// load the session written to the json file located at `path`
+pub fn load(path: &str) -> Result<Session, Error> {
+    let content = match fs::read(path) {
+        Ok(c) => c,
+        Err(err) => {
+            return Err(Error::io(
+                format!("failed to read result file `{path}`"),
+                err,
+            ))
+        }
+    };
+
+    match serde_json::from_slice(&content) {
+        Ok(s) => Ok(s),
+        Err(err) => Err(Error::serialize(
+            format!("invalid result file `{path}`"),
+            err,
+        )),
+    }
+}
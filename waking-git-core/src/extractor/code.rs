@@ -1,12 +1,14 @@
+use crate::error::Error;
 use crate::hash;
 use crate::repo::Repo;
 use rust_code_analysis::{get_function_spaces, read_file, CodeMetrics, FuncSpace, SpaceKind, LANG};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
 use walkdir::WalkDir;
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug)]
 pub struct FileData {
     pub name: String,
     pub path: String,
@@ -19,6 +21,8 @@ pub struct FileData {
     // skip_deserializing does not work well on the `spaces` field because
     // `FuncSpace` does not implement `Default`, forcing us set a default
     #[serde(default = "default_func_spaces")]
+    // `FuncSpace` does not implement `JsonSchema` either, its schema is left open
+    #[schemars(with = "serde_json::Value")]
     pub spaces: FuncSpace,
 }
 
@@ -53,19 +57,22 @@ impl Default for FileData {
     }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Default)]
 pub struct Code {
     pub repo_name: String,
     pub files_data: HashMap<String, FileData>,
 }
 
-pub fn get_repo_path(repo: &Repo) -> Result<&Path, String> {
+pub fn get_repo_path(repo: &Repo) -> Result<&Path, Error> {
     match repo.repo.path().parent() {
         Some(repo_path) => Ok(repo_path),
-        None => Err(format!(
-            "Failed to get repo path, repo: {:?}",
-            repo.repo.path().to_str()
-        )),
+        None => Err(Error::Extract {
+            message: format!(
+                "Failed to get repo path, repo: {:?}",
+                repo.repo.path().to_str()
+            ),
+            source: None,
+        }),
     }
 }
 
@@ -85,14 +92,23 @@ pub fn get_file_language(file_extension: &String) -> Option<LANG> {
     }
 }
 
-pub fn extract_code_data(repo_path: &Path) -> Result<Code, String> {
+pub fn extract_code_data(repo_path: &Path) -> Result<Code, Error> {
+    extract_files(repo_path, |_| None)
+}
+
+//extract the code data of the files of the repository, the data
+//returned by `reuse` for a file path is kept instead of analysing the file
+fn extract_files(
+    repo_path: &Path,
+    reuse: impl Fn(&str) -> Option<FileData>,
+) -> Result<Code, Error> {
     let repo_name = match repo_path.file_name() {
         Some(repo_name) => String::from(repo_name.to_string_lossy()),
         None => {
-            return Err(format!(
-                "Couldn't get repo name, path: {}",
-                repo_path.display()
-            ))
+            return Err(Error::Extract {
+                message: format!("Couldn't get repo name, path: {}", repo_path.display()),
+                source: None,
+            })
         }
     };
 
@@ -124,6 +140,19 @@ pub fn extract_code_data(repo_path: &Path) -> Result<Code, String> {
             None => continue,
         };
 
+        //Get the path file relative to the root of the git repository
+        //for a /Users/elhmn/.wake/scanner/github-com-elhmn-qautomata/src/example.rs file
+        //the `path` will be `src/example.rs`
+        let path = match file_path.strip_prefix(String::from(repo_path.to_string_lossy())) {
+            Ok(p) => p.to_str().unwrap_or_default().to_string(),
+            Err(_) => "".to_string(),
+        };
+
+        if let Some(file_data) = reuse(&path) {
+            code_data.files_data.insert(hash::new(path), file_data);
+            continue;
+        }
+
         let source_code = match read_file(file_path) {
             Ok(source_code) => source_code,
             Err(_) => continue,
@@ -134,14 +163,6 @@ pub fn extract_code_data(repo_path: &Path) -> Result<Code, String> {
             None => continue,
         };
 
-        //Get the path file relative to the root of the git repository
-        //for a /Users/elhmn/.wake/scanner/github-com-elhmn-qautomata/src/example.rs file
-        //the `path` will be `src/example.rs`
-        let path = match file_path.strip_prefix(String::from(repo_path.to_string_lossy())) {
-            Ok(p) => p.to_str().unwrap_or_default().to_string(),
-            Err(_) => "".to_string(),
-        };
-
         let file_data = FileData {
             name: file_name,
             path: path.clone(),
@@ -156,9 +177,25 @@ pub fn extract_code_data(repo_path: &Path) -> Result<Code, String> {
     Ok(code_data)
 }
 
-pub fn new(repo: &Repo) -> Result<Code, String> {
+pub fn new(repo: &Repo) -> Result<Code, Error> {
     let repo_path = get_repo_path(repo)?;
     let code_data = extract_code_data(repo_path)?;
 
     Ok(code_data)
 }
+
+/// returns the code data of the repository, the files that are not
+/// in the `changed` paths keep their `previous` data instead of being
+/// analysed again
+pub fn update(repo: &Repo, previous: &Code, changed: &[String]) -> Result<Code, Error> {
+    let repo_path = get_repo_path(repo)?;
+    extract_files(repo_path, |path| {
+        if changed.iter().any(|c| c == path) {
+            return None;
+        }
+        previous
+            .files_data
+            .get(&hash::new(path.to_owned()))
+            .cloned()
+    })
+}
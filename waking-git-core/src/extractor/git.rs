@@ -1,10 +1,12 @@
+use crate::error::Error;
 use crate::hash;
 use crate::repo;
 use git2::{self, Repository, TreeEntry};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Deserialize, Serialize, Clone, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub enum ObjectKind {
     Blob,
     Tree,
@@ -18,10 +20,10 @@ pub enum ObjectKind {
 //and use the output to populate the metrics
 //it is a little bit more involved and can be done
 //a later on
-#[derive(Deserialize, Serialize, Clone, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub struct Metrics {}
 
-#[derive(Deserialize, Serialize, Clone, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub struct Blob {
     pub filemode: i32,
     pub name: String,
@@ -34,9 +36,16 @@ pub struct Blob {
     //this is used to optimize code data lookup
     pub path_sha: String,
     pub sha: String,
+    //churn is the number of commits, reachable from the
+    //ref_target, that modified the file
+    #[serde(default)]
+    pub churn: u32,
+    //size of the blob in bytes
+    #[serde(default)]
+    pub size: u64,
 }
 
-#[derive(Deserialize, Serialize, Clone, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub struct Tree {
     pub name: String,
     //path is the path to the file/directory relative
@@ -54,7 +63,7 @@ pub struct Tree {
     pub objects: Vec<String>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub struct Tag {
     pub name: String,
     pub message: String,
@@ -66,7 +75,7 @@ pub struct Tag {
     pub commit_sha: String,
 }
 
-#[derive(Deserialize, Serialize, Clone, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub struct Commit {
     //sha the git object hash
     pub sha: String,
@@ -79,7 +88,7 @@ pub struct Commit {
     pub parents: Vec<String>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub struct Object {
     pub kind: ObjectKind,
 
@@ -93,7 +102,28 @@ pub struct Object {
     pub commit: Option<Commit>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Default)]
+/// Coupling is a pair of files modified by the same commits
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default, Debug, PartialEq)]
+pub struct Coupling {
+    //the paths of the files, `a` sorts before `b`
+    pub a: String,
+    pub b: String,
+    //number of commits that modified both files
+    pub commits: u32,
+}
+
+/// Revision is a commit reachable from the ref target,
+/// with the files it modified
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default, Debug, PartialEq)]
+pub struct Revision {
+    pub sha: String,
+    //the commit time, in seconds since the unix epoch
+    pub time: i64,
+    //the paths of the files modified by the commit, sorted
+    pub files: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub struct Git {
     pub metrics: Metrics,
     pub objects: HashMap<String, Object>,
@@ -102,6 +132,30 @@ pub struct Git {
     // (String, String) <=> (ref, oid)
     pub ref_target: (String, String),
     pub refs: HashMap<String, String>,
+    //the url of the `origin` remote the repository was cloned from
+    #[serde(default)]
+    pub url: String,
+    //the pairs of files often modified together, sorted by path
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coupling: Vec<Coupling>,
+    //the commits reachable from the ref target, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<Revision>,
+}
+
+//commits modifying more files than that, as merges
+//or reformattings, don't couple their files
+const MAX_COUPLED_FILES: usize = 30;
+//the number of commits two files must share to be coupled
+const MIN_COUPLING_COMMITS: u32 = 2;
+
+//History holds the data computed from the commits reachable
+//from the ref target
+#[derive(Default)]
+struct History {
+    churn: HashMap<String, u32>,
+    coupling: Vec<Coupling>,
+    revisions: Vec<Revision>,
 }
 
 impl Object {
@@ -112,10 +166,20 @@ impl Object {
     }
 }
 
-pub fn new(repo: &repo::Repo) -> Result<Git, String> {
+pub fn new(repo: &repo::Repo) -> Result<Git, Error> {
+    //a missing ref is told apart from the other git errors
+    if let Err(err) = repo.ref_target() {
+        return Err(Error::RefNotFound(err));
+    }
+
     let git_data = match extract_git_objects(repo) {
         Ok(d) => d,
-        Err(err) => return Err(format!("failed to extract git objects: {err}")),
+        Err(err) => {
+            return Err(Error::Extract {
+                message: "failed to extract git objects".to_string(),
+                source: Some(Box::new(err)),
+            })
+        }
     };
 
     Ok(git_data)
@@ -124,16 +188,9 @@ pub fn new(repo: &repo::Repo) -> Result<Git, String> {
 pub fn extract_git_objects(repo: &repo::Repo) -> Result<Git, git2::Error> {
     let r = &repo.repo;
 
-    let mut ref_name = "refs/heads/master";
     //Get default reference oid
-    //First we check for `master` and if `master` does not exist we fallback to `main`
-    let oid = match repo.repo.refname_to_id(ref_name) {
-        Ok(oid) => oid,
-        Err(_) => {
-            ref_name = "refs/heads/main";
-            repo.repo.refname_to_id(ref_name)?
-        }
-    };
+    let (ref_name, sha) = repo.ref_target()?;
+    let oid = git2::Oid::from_str(&sha)?;
 
     let mut objects: HashMap<String, Object> = HashMap::new();
     let mut obj = Object::new();
@@ -157,18 +214,88 @@ pub fn extract_git_objects(repo: &repo::Repo) -> Result<Git, git2::Error> {
     objects.insert(oid.to_string(), obj);
 
     //Add every git objects found during the tree object traversal
-    add_tree_objects(&commit.tree()?, &mut objects, r)?;
+    let history = get_history(r, &commit)?;
+    add_tree_objects(&commit.tree()?, &mut objects, &history.churn, r)?;
+
+    let url = match r.find_remote("origin") {
+        Ok(remote) => remote.url().unwrap_or_default().to_string(),
+        Err(_) => "".to_string(),
+    };
 
     Ok(Git {
         objects,
-        ref_target: (ref_name.to_string(), format!("{oid}")),
+        ref_target: (ref_name, sha),
+        url,
+        coupling: history.coupling,
+        history: history.revisions,
         ..Default::default()
     })
 }
 
+/// Count, for each file path, the number of commits reachable from
+/// `commit` that modified it, and the number of commits shared by
+/// each pair of files. Every commit is also listed with its time and
+/// the files it modified.
+///
+/// Repositories are shallow cloned by default, in which case every file
+/// will only be touched by the cloned commit.
+fn get_history(repo: &git2::Repository, commit: &git2::Commit) -> Result<History, git2::Error> {
+    let mut history = History::default();
+    let mut pairs: BTreeMap<(String, String), u32> = BTreeMap::new();
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(commit.id())?;
+
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        //Commits without parents are diffed against an empty tree
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        let mut paths = vec![];
+        for delta in diff.deltas() {
+            if let Some(path) = delta.new_file().path() {
+                let path = path.to_string_lossy().to_string();
+                *history.churn.entry(path.to_owned()).or_default() += 1;
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        history.revisions.push(Revision {
+            sha: commit.id().to_string(),
+            time: commit.time().seconds(),
+            files: paths.to_owned(),
+        });
+
+        //the commits without parents add every file of the repository
+        if commit.parent_count() == 0 || paths.len() > MAX_COUPLED_FILES {
+            continue;
+        }
+        for (i, a) in paths.iter().enumerate() {
+            for b in &paths[i + 1..] {
+                *pairs.entry((a.to_owned(), b.to_owned())).or_default() += 1;
+            }
+        }
+    }
+
+    //the revwalk order depends on the commit graph,
+    //the revisions are sorted so that the extraction is reproducible
+    history
+        .revisions
+        .sort_by(|a, b| a.time.cmp(&b.time).then(a.sha.cmp(&b.sha)));
+    history.coupling = pairs
+        .into_iter()
+        .filter(|(_, commits)| *commits >= MIN_COUPLING_COMMITS)
+        .map(|((a, b), commits)| Coupling { a, b, commits })
+        .collect();
+    Ok(history)
+}
+
 fn add_tree_objects(
     tree: &git2::Tree,
     objects: &mut HashMap<String, Object>,
+    churn: &HashMap<String, u32>,
     repo: &git2::Repository,
 ) -> Result<(), git2::Error> {
     //Create the root tree object
@@ -187,15 +314,24 @@ fn add_tree_objects(
         objects.insert(tree.id().to_string(), obj);
     }
 
-    tree.walk(git2::TreeWalkMode::PreOrder, |path, entry| {
+    let odb = repo.odb()?;
+    //the walk is aborted on the first error, returned once it ends
+    let mut failed = None;
+    let walked = tree.walk(git2::TreeWalkMode::PreOrder, |path, entry| {
         let mut obj = Object::new();
         if let Some(kind) = entry.kind() {
             match kind {
                 //Create and add Tree objects
-                git2::ObjectType::Tree => {
-                    obj.kind = ObjectKind::Tree;
-                    obj.tree = Some(build_tree_object(path.to_string(), entry, repo));
-                }
+                git2::ObjectType::Tree => match build_tree_object(path.to_string(), entry, repo) {
+                    Ok(t) => {
+                        obj.kind = ObjectKind::Tree;
+                        obj.tree = Some(t);
+                    }
+                    Err(err) => {
+                        failed = Some(err);
+                        return git2::TreeWalkResult::Abort;
+                    }
+                },
 
                 //Create and add Blob objects
                 git2::ObjectType::Blob => {
@@ -204,6 +340,11 @@ fn add_tree_objects(
                     obj.kind = ObjectKind::Blob;
                     obj.blob = Some(Blob {
                         name,
+                        churn: churn.get(&path).copied().unwrap_or_default(),
+                        size: match odb.read_header(entry.id()) {
+                            Ok((size, _)) => size as u64,
+                            Err(_) => 0,
+                        },
                         path: path.clone(),
                         path_sha: hash::new(path),
                         sha: entry.id().to_string(),
@@ -216,15 +357,21 @@ fn add_tree_objects(
 
         objects.insert(entry.id().to_string(), obj);
         git2::TreeWalkResult::Ok
-    })?;
-
-    Ok(())
+    });
+    if let Some(err) = failed {
+        return Err(err);
+    }
+    walked
 }
 
-fn build_tree_object(path: String, entry: &TreeEntry, repo: &Repository) -> Tree {
+fn build_tree_object(
+    path: String,
+    entry: &TreeEntry,
+    repo: &Repository,
+) -> Result<Tree, git2::Error> {
     let name = entry.name().unwrap_or("").to_string();
     let path = get_relative_path(path, name.clone());
-    Tree {
+    Ok(Tree {
         name,
         sha: entry.id().to_string(),
         path: path.clone(),
@@ -232,7 +379,7 @@ fn build_tree_object(path: String, entry: &TreeEntry, repo: &Repository) -> Tree
         filemode: entry.filemode(),
         objects: {
             let mut objs = vec![];
-            let t = repo.find_tree(entry.id()).unwrap();
+            let t = repo.find_tree(entry.id())?;
 
             //We walk down the tree to find every blob or tree objects and add them
             //to our list of objects
@@ -242,11 +389,10 @@ fn build_tree_object(path: String, entry: &TreeEntry, repo: &Repository) -> Tree
                     return git2::TreeWalkResult::Skip;
                 }
                 git2::TreeWalkResult::Ok
-            })
-            .unwrap();
+            })?;
             objs
         },
-    }
+    })
 }
 
 pub fn get_relative_path(path: String, file_name: String) -> String {
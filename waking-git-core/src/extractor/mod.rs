@@ -1,38 +1,60 @@
 pub mod code;
 pub mod git;
 use crate::config;
+use crate::error::Error;
+use crate::formats;
 use crate::repo;
 use crate::utils;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Clone, Default)]
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Default)]
 pub struct Data {
     pub git: git::Git,
     pub code: code::Code,
 }
 
-pub fn run(repo: &repo::Repo) -> Result<Data, String> {
+pub fn run(repo: &repo::Repo) -> Result<Data, Error> {
     Ok(Data {
         git: git::new(repo)?,
         code: code::new(repo)?,
     })
 }
 
-pub fn extract(git_repo: &mut repo::Repo) -> Result<(Data, String), String> {
+/// extract the repository data and store it in the `git_repo` format.
+/// returns the data and its encoded form
+pub fn extract(git_repo: &mut repo::Repo) -> Result<(Data, Vec<u8>), Error> {
     let data = run(git_repo)?;
-    let dest_path = format!("{}/{}", git_repo.scanner_path, config::EXTRACTOR_FILE_NAME);
-    git_repo.extracted_file_path = dest_path.clone();
-    let json_data = serde_json::to_string(&data).unwrap_or_else(|_| "".to_string());
-    match utils::store_json_data(
-        json_data.to_owned(),
-        git_repo.scanner_path.to_owned(),
-        &dest_path,
-    ) {
-        Ok(_) => (),
-        Err(err) => {
-            return Err(format!("Error: failed to extract repository data: {err}"));
-        }
-    };
+    store(git_repo, data)
+}
+
+/// returns the data of the repository new commit, only the files changed
+/// since the commit of the `previous` data are analysed again
+pub fn update(repo: &repo::Repo, previous: &Data) -> Result<Data, Error> {
+    let git = git::new(repo)?;
+    let changed = repo::changed_paths(&repo.repo, &previous.git.ref_target.1, &git.ref_target.1)?;
+    let code = code::update(repo, &previous.code, &changed)?;
+    Ok(Data { git, code })
+}
 
-    Ok((data, json_data))
+fn store(git_repo: &mut repo::Repo, data: Data) -> Result<(Data, Vec<u8>), Error> {
+    let encoded_data = formats::encode(&data, git_repo.format)?;
+    save(git_repo, &encoded_data)?;
+    Ok((data, encoded_data))
+}
+
+/// write the encoded extracted data to the scanner folder of `git_repo`,
+/// and set its `extracted_file_path`
+pub fn save(git_repo: &mut repo::Repo, encoded_data: &[u8]) -> Result<(), Error> {
+    let dest_path = format!(
+        "{}/{}.{}",
+        git_repo.scanner_path,
+        config::EXTRACTOR_FILE_NAME,
+        git_repo.format.extension()
+    );
+    git_repo.extracted_file_path = dest_path.clone();
+    match utils::store_data(encoded_data, git_repo.scanner_path.to_owned(), &dest_path) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::io(format!("failed to write `{dest_path}`"), err)),
+    }
 }
@@ -0,0 +1,505 @@
+use crate::error::Error;
+use crate::extractor::{self, git};
+use crate::hash;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Options of the filters run on the extracted data before it is converted
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
+pub struct Options {
+    /// globs of the file paths kept, every file is kept if empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// globs of the file and directory paths dropped
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// the depth of the deepest directories kept, 0 for the root directory
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+    /// the maximum number of files kept in each directory
+    #[serde(default)]
+    pub max_entities_per_scene: Option<usize>,
+}
+
+/// Filter drops objects from the trees of the extracted data
+pub trait Filter {
+    /// returns the objects of `tree` kept by the filter.
+    /// `depth` is the depth of the tree, 0 for the root tree
+    fn filter(
+        &self,
+        tree: &git::Tree,
+        depth: u32,
+        objects: &HashMap<String, git::Object>,
+    ) -> Vec<String>;
+
+    /// Return filter name
+    fn name(&self) -> String;
+}
+
+/// Include keeps the files whose path matches one of its globs
+pub struct Include {
+    globs: GlobSet,
+}
+
+/// Exclude drops the files and directories whose path matches one of its globs
+pub struct Exclude {
+    globs: GlobSet,
+}
+
+/// MaxDepth drops the directories deeper than `depth`
+pub struct MaxDepth {
+    depth: u32,
+}
+
+/// MaxEntitiesPerScene samples the files of a directory down to `max` files,
+/// evenly spread across the directory
+pub struct MaxEntitiesPerScene {
+    max: usize,
+}
+
+impl Filter for Include {
+    fn filter(
+        &self,
+        tree: &git::Tree,
+        _: u32,
+        objects: &HashMap<String, git::Object>,
+    ) -> Vec<String> {
+        retain(tree, objects, |o| match &o.blob {
+            Some(blob) => self.globs.is_match(&blob.path),
+            None => true,
+        })
+    }
+
+    fn name(&self) -> String {
+        "include".to_owned()
+    }
+}
+
+impl Filter for Exclude {
+    fn filter(
+        &self,
+        tree: &git::Tree,
+        _: u32,
+        objects: &HashMap<String, git::Object>,
+    ) -> Vec<String> {
+        retain(tree, objects, |o| match (&o.blob, &o.tree) {
+            (Some(blob), _) => !self.globs.is_match(&blob.path),
+            (_, Some(tree)) => !self.globs.is_match(&tree.path),
+            _ => true,
+        })
+    }
+
+    fn name(&self) -> String {
+        "exclude".to_owned()
+    }
+}
+
+impl Filter for MaxDepth {
+    fn filter(
+        &self,
+        tree: &git::Tree,
+        depth: u32,
+        objects: &HashMap<String, git::Object>,
+    ) -> Vec<String> {
+        retain(tree, objects, |o| o.tree.is_none() || depth < self.depth)
+    }
+
+    fn name(&self) -> String {
+        "max-depth".to_owned()
+    }
+}
+
+impl Filter for MaxEntitiesPerScene {
+    fn filter(
+        &self,
+        tree: &git::Tree,
+        _: u32,
+        objects: &HashMap<String, git::Object>,
+    ) -> Vec<String> {
+        let is_blob = |oid: &String| objects.get(oid).is_some_and(|o| o.blob.is_some());
+        let blobs: Vec<&String> = tree.objects.iter().filter(|oid| is_blob(oid)).collect();
+        if blobs.len() <= self.max {
+            return tree.objects.to_owned();
+        }
+
+        //pick every n-th file so that the sample
+        //stays the same from one scan to another
+        let sampled: HashSet<&String> = (0..self.max)
+            .map(|i| blobs[i * blobs.len() / self.max])
+            .collect();
+        tree.objects
+            .iter()
+            .filter(|oid| !is_blob(oid) || sampled.contains(oid))
+            .cloned()
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        "max-entities-per-scene".to_owned()
+    }
+}
+
+fn retain(
+    tree: &git::Tree,
+    objects: &HashMap<String, git::Object>,
+    keep: impl Fn(&git::Object) -> bool,
+) -> Vec<String> {
+    tree.objects
+        .iter()
+        .filter(|oid| objects.get(*oid).is_some_and(&keep))
+        .cloned()
+        .collect()
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+    for g in globs {
+        match Glob::new(g) {
+            Ok(glob) => builder.add(glob),
+            Err(err) => {
+                return Err(Error::Parse {
+                    message: format!("invalid path glob `{g}`"),
+                    source: Some(Box::new(err)),
+                })
+            }
+        };
+    }
+
+    match builder.build() {
+        Ok(set) => Ok(set),
+        Err(err) => Err(Error::Parse {
+            message: "failed to build path globs".to_string(),
+            source: Some(Box::new(err)),
+        }),
+    }
+}
+
+/// build the filters described by `options`,
+/// in the order they are run
+pub fn new(options: &Options) -> Result<Vec<Box<dyn Filter>>, Error> {
+    let mut filters: Vec<Box<dyn Filter>> = vec![];
+    if !options.include.is_empty() {
+        filters.push(Box::new(Include {
+            globs: build_glob_set(&options.include)?,
+        }));
+    }
+    if !options.exclude.is_empty() {
+        filters.push(Box::new(Exclude {
+            globs: build_glob_set(&options.exclude)?,
+        }));
+    }
+    if let Some(depth) = options.max_depth {
+        filters.push(Box::new(MaxDepth { depth }));
+    }
+    if let Some(max) = options.max_entities_per_scene {
+        filters.push(Box::new(MaxEntitiesPerScene { max }));
+    }
+
+    Ok(filters)
+}
+
+/// run the filters described by `options` on the extracted data
+pub fn run(data: &mut extractor::Data, options: &Options) -> Result<(), Error> {
+    let filters = new(options)?;
+    apply(data, &filters);
+    Ok(())
+}
+
+/// apply `filters` to every tree reachable from the ref target commit.
+///
+/// Directories left empty are dropped, as well as the git objects, the code
+/// data, the coupling and the history of the files that are no longer reachable
+pub fn apply(data: &mut extractor::Data, filters: &[Box<dyn Filter>]) {
+    if filters.is_empty() {
+        return;
+    }
+
+    let commit_oid = data.git.ref_target.1.to_owned();
+    let root_tree = match data
+        .git
+        .objects
+        .get(&commit_oid)
+        .and_then(|o| o.commit.as_ref())
+    {
+        Some(commit) => commit.tree.to_owned(),
+        None => return,
+    };
+
+    let mut visited = Visited::default();
+    filter_tree(&root_tree, 0, &mut data.git.objects, filters, &mut visited);
+
+    //drop everything that is no longer reachable
+    let mut reachable = HashSet::from([commit_oid]);
+    let mut paths = HashSet::new();
+    let mut to_visit = vec![root_tree];
+    while let Some(oid) = to_visit.pop() {
+        if let Some(o) = data.git.objects.get(&oid) {
+            if let Some(tree) = &o.tree {
+                to_visit.extend(tree.objects.iter().cloned());
+            }
+            if let Some(blob) = &o.blob {
+                paths.insert(blob.path_sha.to_owned());
+            }
+        }
+        reachable.insert(oid);
+    }
+    data.git.objects.retain(|oid, _| reachable.contains(oid));
+    data.code
+        .files_data
+        .retain(|path_sha, _| paths.contains(path_sha));
+    data.git.coupling.retain(|c| {
+        paths.contains(&hash::new(c.a.to_owned())) && paths.contains(&hash::new(c.b.to_owned()))
+    });
+    for revision in data.git.history.iter_mut() {
+        revision
+            .files
+            .retain(|f| paths.contains(&hash::new(f.to_owned())));
+    }
+}
+
+//the trees already filtered: identical trees share the same oid
+//but may be reached at different depths, so they are filtered once per depth
+#[derive(Default)]
+struct Visited {
+    //the unfiltered trees, before they were filtered in place
+    trees: HashMap<String, git::Tree>,
+    //the oid of the filtered tree for each (oid, depth),
+    //None when the tree was left empty
+    results: HashMap<(String, u32), Option<String>>,
+}
+
+//filter the objects of the tree `oid` and of its sub trees,
+//returns the oid of the filtered tree or None when it was left empty.
+//The first filtered copy of a tree replaces it, the other ones are
+//added as new objects whose oid is suffixed by their depth
+fn filter_tree(
+    oid: &str,
+    depth: u32,
+    objects: &mut HashMap<String, git::Object>,
+    filters: &[Box<dyn Filter>],
+    visited: &mut Visited,
+) -> Option<String> {
+    let key = (oid.to_owned(), depth);
+    if let Some(result) = visited.results.get(&key) {
+        return result.to_owned();
+    }
+
+    let (mut tree, filtered_oid) = match visited.trees.get(oid) {
+        Some(tree) => (tree.to_owned(), format!("{oid}:{depth}")),
+        None => {
+            let tree = objects.get(oid).and_then(|o| o.tree.to_owned())?;
+            visited.trees.insert(oid.to_owned(), tree.to_owned());
+            (tree, oid.to_owned())
+        }
+    };
+
+    for f in filters {
+        tree.objects = f.filter(&tree, depth, objects);
+    }
+
+    let mut kept = vec![];
+    for child in tree.objects {
+        match objects.get(&child).is_some_and(|o| o.tree.is_some()) {
+            true => kept.extend(filter_tree(&child, depth + 1, objects, filters, visited)),
+            false => kept.push(child),
+        }
+    }
+
+    let result = match kept.is_empty() {
+        true => None,
+        false => {
+            let mut obj = objects[oid].to_owned();
+            tree.objects = kept;
+            obj.tree = Some(tree);
+            objects.insert(filtered_oid.to_owned(), obj);
+            Some(filtered_oid)
+        }
+    };
+    visited.results.insert(key, result.to_owned());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractor::{self, code, git};
+    use crate::filters;
+    use crate::hash;
+
+    //build the extracted data of a repository made of `files`
+    fn new_data(files: &[&str]) -> extractor::Data {
+        let mut data = extractor::Data::default();
+        let objects = &mut data.git.objects;
+        let tree = |path: &str| git::Object {
+            kind: git::ObjectKind::Tree,
+            tree: Some(git::Tree {
+                name: path.rsplit('/').next().unwrap_or_default().to_string(),
+                path: path.to_string(),
+                path_sha: hash::new(path.to_string()),
+                sha: format!("tree:{path}"),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        objects.insert("tree:".to_string(), tree(""));
+        for file in files {
+            //add every directory of the file path to its parent
+            let mut parent = "".to_string();
+            let parts: Vec<&str> = file.split('/').collect();
+            for i in 0..parts.len() {
+                let path = parts[..=i].join("/");
+                let oid = match i == parts.len() - 1 {
+                    true => format!("blob:{path}"),
+                    false => format!("tree:{path}"),
+                };
+                if !objects.contains_key(&oid) {
+                    let obj = match i == parts.len() - 1 {
+                        true => git::Object {
+                            kind: git::ObjectKind::Blob,
+                            blob: Some(git::Blob {
+                                name: parts[i].to_string(),
+                                path: path.to_owned(),
+                                path_sha: hash::new(path.to_owned()),
+                                sha: oid.to_owned(),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        },
+                        false => tree(&path),
+                    };
+                    objects.insert(oid.to_owned(), obj);
+                    let parent_tree = objects
+                        .get_mut(&format!("tree:{parent}"))
+                        .and_then(|o| o.tree.as_mut())
+                        .unwrap();
+                    parent_tree.objects.push(oid);
+                }
+                parent = path;
+            }
+            data.code
+                .files_data
+                .insert(hash::new(file.to_string()), code::FileData::default());
+        }
+
+        objects.insert(
+            "commit".to_string(),
+            git::Object {
+                kind: git::ObjectKind::Commit,
+                commit: Some(git::Commit {
+                    tree: "tree:".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+        data.git.ref_target = ("refs/heads/main".to_string(), "commit".to_string());
+        data
+    }
+
+    fn blobs(data: &extractor::Data) -> Vec<String> {
+        let mut paths: Vec<String> = data
+            .git
+            .objects
+            .values()
+            .filter_map(|o| o.blob.as_ref())
+            .map(|b| b.path.to_owned())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn filter_extracted_data() {
+        let files = [
+            "README.md",
+            "src/main.rs",
+            "src/cmd/scan.rs",
+            "src/cmd/play.rs",
+            "tests/scan_test.rs",
+            "docs/index.md",
+        ];
+
+        let mut data = new_data(&files);
+        let options = filters::Options {
+            include: vec!["**/*.rs".to_string()],
+            exclude: vec!["tests".to_string()],
+            ..Default::default()
+        };
+        filters::run(&mut data, &options).unwrap();
+        assert_eq!(
+            blobs(&data),
+            vec!["src/cmd/play.rs", "src/cmd/scan.rs", "src/main.rs"]
+        );
+        //the directories left empty are dropped with their code data
+        assert!(!data.git.objects.contains_key("tree:docs"));
+        assert!(!data.git.objects.contains_key("tree:tests"));
+        assert_eq!(data.code.files_data.len(), 3);
+
+        let mut data = new_data(&files);
+        let options = filters::Options {
+            max_depth: Some(1),
+            max_entities_per_scene: Some(1),
+            ..Default::default()
+        };
+        filters::run(&mut data, &options).unwrap();
+        assert_eq!(
+            blobs(&data),
+            vec![
+                "README.md",
+                "docs/index.md",
+                "src/main.rs",
+                "tests/scan_test.rs"
+            ]
+        );
+        assert!(!data.git.objects.contains_key("tree:src/cmd"));
+    }
+
+    #[test]
+    fn filter_identical_trees_at_different_depths() {
+        let mut data = new_data(&["README.md", "a/lib/x.rs", "a/lib/sub/y.rs"]);
+        //the root directory holds a copy of `a/lib`, both share the same oid
+        let root = data.git.objects.get_mut("tree:").unwrap();
+        root.tree
+            .as_mut()
+            .unwrap()
+            .objects
+            .push("tree:a/lib".to_string());
+
+        let options = filters::Options {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        filters::run(&mut data, &options).unwrap();
+        let children = |oid: &str| {
+            data.git.objects[oid]
+                .tree
+                .as_ref()
+                .unwrap()
+                .objects
+                .to_owned()
+        };
+        assert_eq!(
+            children("tree:"),
+            vec!["blob:README.md", "tree:a", "tree:a/lib:1"]
+        );
+        assert_eq!(children("tree:a/lib"), vec!["blob:a/lib/x.rs"]);
+        assert_eq!(
+            children("tree:a/lib:1"),
+            vec!["blob:a/lib/x.rs", "tree:a/lib/sub"]
+        );
+        assert_eq!(
+            blobs(&data),
+            vec!["README.md", "a/lib/sub/y.rs", "a/lib/x.rs"]
+        );
+    }
+
+    #[test]
+    fn reject_invalid_globs() {
+        let options = filters::Options {
+            exclude: vec!["src/[".to_string()],
+            ..Default::default()
+        };
+        assert!(filters::new(&options).is_err());
+    }
+}
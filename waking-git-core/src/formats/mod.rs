@@ -0,0 +1,196 @@
+use crate::error::{Error, Source};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io::{Read, Write};
+
+pub const JSON: &str = "json";
+pub const JSON_GZ: &str = "json.gz";
+pub const MSGPACK: &str = "msgpack";
+pub const CBOR: &str = "cbor";
+
+/// The names of every format supported, they are also used as file extensions
+pub const NAMES: [&str; 4] = [JSON, JSON_GZ, MSGPACK, CBOR];
+
+/// Format of the extracted and converted files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Json,
+    JsonGz,
+    MsgPack,
+    Cbor,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Result<Format, Error> {
+        match name {
+            JSON => Ok(Format::Json),
+            JSON_GZ => Ok(Format::JsonGz),
+            MSGPACK => Ok(Format::MsgPack),
+            CBOR => Ok(Format::Cbor),
+            _ => Err(Error::parse(format!(
+                "unknown format `{name}`, expected one of: {}",
+                NAMES.join(", ")
+            ))),
+        }
+    }
+
+    /// the file extension of the format, without the leading dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Json => JSON,
+            Format::JsonGz => JSON_GZ,
+            Format::MsgPack => MSGPACK,
+            Format::Cbor => CBOR,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::JsonGz => "application/gzip",
+            Format::MsgPack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+}
+
+/// detect the format of `data` from its first bytes
+pub fn detect(data: &[u8]) -> Result<Format, Error> {
+    let first = data.iter().find(|b| !b.is_ascii_whitespace());
+    match first {
+        //gzip magic number
+        Some(0x1f) if data.get(1) == Some(&0x8b) => Ok(Format::JsonGz),
+        Some(b'{') | Some(b'[') => Ok(Format::Json),
+        //msgpack fixmap, map16 and map32
+        Some(0x80..=0x8f) | Some(0xde) | Some(0xdf) => Ok(Format::MsgPack),
+        //cbor maps, with a definite or indefinite length
+        Some(0xa0..=0xbb) | Some(0xbf) => Ok(Format::Cbor),
+        _ => Err(Error::parse(format!(
+            "unknown data format, expected one of: {}",
+            NAMES.join(", ")
+        ))),
+    }
+}
+
+/// returns `file_name` without its format extension
+pub fn strip_extension(file_name: &str) -> &str {
+    //`json.gz` is tested before `json`, as it ends with a `.gz`
+    let mut extensions = NAMES.to_vec();
+    extensions.sort_by_key(|e| std::cmp::Reverse(e.len()));
+
+    extensions
+        .iter()
+        .find_map(|e| file_name.strip_suffix(&format!(".{e}")))
+        .unwrap_or(file_name)
+}
+
+pub fn encode<T: Serialize>(data: &T, format: Format) -> Result<Vec<u8>, Error> {
+    let encoded = match format {
+        Format::Json => serde_json::to_vec(data).map_err(Source::from),
+        Format::JsonGz => serde_json::to_vec(data)
+            .map_err(Source::from)
+            .and_then(|json| {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&json).map_err(Source::from)?;
+                encoder.finish().map_err(Source::from)
+            }),
+        //structs are encoded as maps, as some fields of the
+        //extracted data are skipped when deserialized
+        Format::MsgPack => rmp_serde::to_vec_named(data).map_err(Source::from),
+        Format::Cbor => {
+            let mut buf = Vec::new();
+            ciborium::ser::into_writer(data, &mut buf)
+                .map(|_| buf)
+                .map_err(Source::from)
+        }
+    };
+
+    match encoded {
+        Ok(e) => Ok(e),
+        Err(err) => Err(Error::serialize(
+            format!("failed to encode data to {}", format.extension()),
+            err,
+        )),
+    }
+}
+
+/// decode `data`, its format is detected automatically
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+    let format = detect(data)?;
+    let decoded = match format {
+        Format::Json => serde_json::from_slice(data).map_err(Source::from),
+        Format::JsonGz => {
+            let mut json = Vec::new();
+            match GzDecoder::new(data).read_to_end(&mut json) {
+                Ok(_) => serde_json::from_slice(&json).map_err(Source::from),
+                Err(err) => Err(Source::from(err)),
+            }
+        }
+        Format::MsgPack => rmp_serde::from_slice(data).map_err(Source::from),
+        Format::Cbor => ciborium::de::from_reader(data).map_err(Source::from),
+    };
+
+    match decoded {
+        Ok(d) => Ok(d),
+        Err(err) => Err(Error::serialize(
+            format!("failed to decode {} data", format.extension()),
+            err,
+        )),
+    }
+}
+
+/// the path read as the standard input by `load`
+pub const STDIN: &str = "-";
+
+/// load the file at `path`, whatever its format,
+/// the data is read from stdin when `path` is `-`
+pub fn load<T: DeserializeOwned>(path: &str) -> Result<T, Error> {
+    if path == STDIN {
+        let mut data = Vec::new();
+        return match std::io::stdin().read_to_end(&mut data) {
+            Ok(_) => decode(&data),
+            Err(err) => Err(Error::io("failed to read stdin", err)),
+        };
+    }
+
+    match fs::read(path) {
+        Ok(data) => decode(&data),
+        Err(err) => Err(Error::io(format!("failed to read `{path}`"), err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formats::{self, Format};
+    use std::collections::HashMap;
+
+    #[test]
+    fn encode_and_decode_every_format() {
+        let data = HashMap::from([("name".to_string(), vec![1., 2.5])]);
+
+        for name in formats::NAMES {
+            let format = Format::from_name(name).unwrap();
+            let encoded = formats::encode(&data, format).unwrap();
+            assert_eq!(formats::detect(&encoded).ok(), Some(format));
+
+            let decoded: HashMap<String, Vec<f64>> = formats::decode(&encoded).unwrap();
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn strip_format_extension() {
+        assert_eq!(formats::strip_extension("extracted.json"), "extracted");
+        assert_eq!(formats::strip_extension("extracted.json.gz"), "extracted");
+        assert_eq!(
+            formats::strip_extension("shmup-converted.cbor"),
+            "shmup-converted"
+        );
+        assert_eq!(formats::strip_extension("data.txt"), "data.txt");
+    }
+}
@@ -1,5 +1,6 @@
 use serde::Deserialize;
 use std::collections::BTreeMap;
+use std::path::Path;
 
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
@@ -64,6 +65,15 @@ pub fn spec_from_extension(languages: &Languages, extension: &str) -> Spec {
     Spec { color, kind, name }
 }
 
+/// returns the spec of the language of the file `path`, found from
+/// its extension. It is empty for the files without an extension
+pub fn spec_from_path(languages: &Languages, path: &str) -> Spec {
+    match Path::new(path).extension() {
+        Some(ext) => spec_from_extension(languages, &format!(".{}", ext.to_string_lossy())),
+        None => Spec::default(),
+    }
+}
+
 pub fn color_from_extension(languages: &Languages, extension: &str) -> String {
     let mut found = Vec::new();
 
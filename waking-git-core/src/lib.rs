@@ -1,10 +1,21 @@
+pub mod cache;
 pub mod config;
 pub mod converters;
+pub mod error;
 pub mod exec;
 pub mod extractor;
+pub mod filters;
+pub mod formats;
 pub mod hash;
 pub mod languages;
+pub mod patterns;
+pub mod pipeline;
 pub mod repo;
+pub mod schemas;
 pub mod server;
 pub mod shapes;
+pub mod shields;
 pub mod utils;
+pub mod weapons;
+
+pub use error::Error;
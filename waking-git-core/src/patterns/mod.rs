@@ -0,0 +1,3 @@
+pub const NONE: &str = "none";
+pub const WANDER: &str = "wander";
+pub const MOVE_TOWARDS: &str = "move_towards";
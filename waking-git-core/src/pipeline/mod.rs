@@ -0,0 +1,511 @@
+use crate::config;
+use crate::converters::{self, external};
+use crate::error::Error;
+use crate::extractor;
+use crate::filters;
+use crate::formats;
+use crate::hash;
+use crate::repo;
+use std::io::Write;
+use std::path::Path;
+
+/// Source is the repository scanned by a pipeline
+pub enum Source {
+    /// a https url, the repository is cloned in the storage folder
+    Url(String),
+    /// a path inside a local repository, it is scanned in place
+    Path(String),
+    /// a git bundle file, it is cloned in the storage folder
+    Bundle(String),
+}
+
+/// Sink tells where the pipeline writes the extracted and converted data
+pub enum Sink {
+    /// nothing is written, the data is only returned
+    Memory,
+    /// the extracted and converted files are written to the scanner
+    /// folder of the repository, or to the folder given
+    File(Option<String>),
+    /// the encoded converted data is written to the writer
+    Writer(Box<dyn Write + Send>),
+}
+
+/// Pipeline scans a repository, its data is extracted,
+/// filtered and converted.
+///
+/// It is run at once with `run`, or step by step
+/// with `open`, `Scan::extract` or `Scan::update`, and `Scan::convert`
+pub struct Pipeline {
+    source: Source,
+    ref_name: Option<String>,
+    format: formats::Format,
+    filters: filters::Options,
+    converter: Option<String>,
+    options: converters::Options,
+    registry: external::Registry,
+    sink: Sink,
+}
+
+/// Scan is a pipeline whose repository is opened,
+/// the repository stays locked until it is dropped
+pub struct Scan {
+    /// the scanned repository, its file paths are set
+    /// when the files are written
+    pub repo: repo::Repo,
+    converter: String,
+    filter_options: filters::Options,
+    filters: Vec<Box<dyn filters::Filter>>,
+    options: converters::Options,
+    registry: external::Registry,
+    sink: Sink,
+    _lock: repo::Lock,
+}
+
+/// Output is the data of a scanned repository
+pub struct Output {
+    pub repo: repo::Repo,
+    /// the extracted data the converter was run on, once filtered
+    pub extracted: extractor::Data,
+    /// the encoded extracted data, before it is filtered
+    pub extracted_encoded: Vec<u8>,
+    pub converted: converters::Converted,
+    pub converted_encoded: Vec<u8>,
+}
+
+/// returns a pipeline scanning `source` with the default converter
+/// of the settings, the data is json encoded and only returned
+pub fn new(source: Source) -> Pipeline {
+    Pipeline {
+        source,
+        ref_name: None,
+        format: formats::Format::default(),
+        filters: filters::Options::default(),
+        converter: None,
+        options: converters::Options::default(),
+        registry: external::Registry::default(),
+        sink: Sink::Memory,
+    }
+}
+
+/// returns a pipeline scanning the repository `url`
+pub fn from_url(url: &str) -> Pipeline {
+    new(Source::Url(url.to_owned()))
+}
+
+/// returns a pipeline scanning the local repository containing `path`
+pub fn from_path(path: &str) -> Pipeline {
+    new(Source::Path(path.to_owned()))
+}
+
+/// returns a pipeline scanning the git bundle file `path`
+pub fn from_bundle(path: &str) -> Pipeline {
+    new(Source::Bundle(path.to_owned()))
+}
+
+/// returns a pipeline scanning `repository`, a git bundle file,
+/// a local repository or an url, told apart by what is found on disk
+pub fn from_repository(repository: &str) -> Pipeline {
+    let path = Path::new(repository);
+    if path.is_file() {
+        return from_bundle(repository);
+    }
+    match path.is_dir() {
+        true => from_path(repository),
+        false => from_url(repository),
+    }
+}
+
+impl Pipeline {
+    /// scan the branch, tag or commit `ref_name` instead of the default one,
+    /// it is checked out in the clones of the urls and bundles
+    pub fn with_ref(mut self, ref_name: &str) -> Pipeline {
+        self.ref_name = Some(ref_name.to_owned());
+        self
+    }
+
+    /// encode the extracted and converted data in `format`
+    pub fn with_format(mut self, format: formats::Format) -> Pipeline {
+        self.format = format;
+        self
+    }
+
+    /// filter the extracted data before it is converted,
+    /// the globs of the settings are used when `options` has none
+    pub fn with_filters(mut self, options: filters::Options) -> Pipeline {
+        self.filters = options;
+        self
+    }
+
+    /// convert the data with the converter `name`, a builtin
+    /// converter or an external converter of the registry
+    pub fn with_converter(mut self, name: &str) -> Pipeline {
+        self.converter = Some(name.to_owned());
+        self
+    }
+
+    /// run the converter with `options`
+    pub fn with_options(mut self, options: converters::Options) -> Pipeline {
+        self.options = options;
+        self
+    }
+
+    /// look the external converters up in `registry`
+    pub fn with_registry(mut self, registry: external::Registry) -> Pipeline {
+        self.registry = registry;
+        self
+    }
+
+    /// write the data to `sink`
+    pub fn with_sink(mut self, sink: Sink) -> Pipeline {
+        self.sink = sink;
+        self
+    }
+
+    /// open the repository, extract and convert its data
+    pub fn run(self, conf: &config::Config) -> Result<Output, Error> {
+        let mut scan = self.open(conf)?;
+        let extracted = scan.extract()?;
+        scan.convert(extracted)
+    }
+
+    /// check the options of the pipeline and open its repository,
+    /// the repository is locked, then the urls and bundles are cloned
+    pub fn open(self, conf: &config::Config) -> Result<Scan, Error> {
+        let converter = match self.converter {
+            Some(c) => c,
+            None => conf.settings.default_converter.to_owned(),
+        };
+        if !converters::exists(&converter, &self.registry) {
+            return Err(Error::convert(format!("unknown converter `{converter}`")));
+        }
+        let mut options = self.filters;
+        if options.include.is_empty() {
+            options.include = conf.settings.include.to_owned();
+        }
+        if options.exclude.is_empty() {
+            options.exclude = conf.settings.exclude.to_owned();
+        }
+        let filters = filters::new(&options)?;
+
+        //the scans of the same repository folder wait for each other
+        let (lock, mut git_repo) = match &self.source {
+            Source::Url(url) => {
+                let lock = repo::lock(&repo::get_folder_name(url)?, conf)?;
+                (lock, repo::clone_repository(url, conf)?)
+            }
+            //the working directory of a local repository is left as it is
+            Source::Path(path) if self.ref_name.is_some() => {
+                return Err(Error::parse(format!(
+                    "a ref cannot be scanned in the local repository `{path}`, check it out instead"
+                )))
+            }
+            Source::Path(path) => {
+                let git_repo = repo::open_local(path, conf)?;
+                (repo::lock(&git_repo.folder_name, conf)?, git_repo)
+            }
+            Source::Bundle(path) => {
+                let lock = repo::lock(&repo::get_bundle_folder_name(path)?, conf)?;
+                (lock, repo::open_bundle(path, conf)?)
+            }
+        };
+        git_repo.format = self.format;
+        git_repo.ref_name = self.ref_name;
+        if !matches!(self.source, Source::Path(_)) {
+            if let Err(err) = git_repo.ref_target() {
+                return Err(Error::RefNotFound(err));
+            }
+            if let Err(err) = git_repo.checkout() {
+                return Err(Error::Clone {
+                    message: format!("Failed to check out `{}`", git_repo.folder_path),
+                    source: Some(Box::new(err)),
+                });
+            }
+        }
+        if let Sink::File(Some(dir)) = &self.sink {
+            git_repo.scanner_path = dir.to_owned();
+        }
+
+        Ok(Scan {
+            repo: git_repo,
+            converter,
+            filter_options: options,
+            filters,
+            options: self.options,
+            registry: self.registry,
+            sink: self.sink,
+            _lock: lock,
+        })
+    }
+}
+
+impl Scan {
+    /// extract the repository data, the file sink gets the data before it is filtered.
+    /// returns the filtered data and the encoded data
+    pub fn extract(&mut self) -> Result<(extractor::Data, Vec<u8>), Error> {
+        let data = extractor::run(&self.repo)?;
+        self.filter(data)
+    }
+
+    /// extract the data of the repository new commit like `extract` does, only
+    /// the files changed since the commit of the `previous` data returned by
+    /// `extract` or `update` are analysed again
+    pub fn update(
+        &mut self,
+        previous: &extractor::Data,
+    ) -> Result<(extractor::Data, Vec<u8>), Error> {
+        let data = extractor::update(&self.repo, previous)?;
+        self.filter(data)
+    }
+
+    //write the extracted data to the file sink, then filter it
+    fn filter(&mut self, mut data: extractor::Data) -> Result<(extractor::Data, Vec<u8>), Error> {
+        let encoded_data = formats::encode(&data, self.repo.format)?;
+        if let Sink::File(_) = self.sink {
+            extractor::save(&mut self.repo, &encoded_data)?;
+        }
+        filters::apply(&mut data, &self.filters);
+
+        Ok((data, encoded_data))
+    }
+
+    /// returns the fingerprint of the filters, the converter options and the
+    /// format the data is scanned with, the cache tells the conversions apart by it
+    pub fn fingerprint(&self) -> String {
+        let filters = serde_json::to_string(&self.filter_options).unwrap_or_default();
+        hash::new(format!(
+            "{filters}|{}|{}|{}",
+            self.options.rules.fingerprint(),
+            self.options.sub_entities,
+            self.repo.format.extension()
+        ))
+    }
+
+    /// convert the data returned by `extract` or `update` and write it to the sink
+    pub fn convert(mut self, extracted: (extractor::Data, Vec<u8>)) -> Result<Output, Error> {
+        let (extracted_data, extracted_encoded) = extracted;
+        let fingerprint = self.fingerprint();
+        let converted = converters::run_by_name(
+            &self.converter,
+            &extracted_data,
+            self.options,
+            &self.registry,
+        )?;
+        let converted_encoded = converted.encode(self.repo.format)?;
+
+        match &mut self.sink {
+            Sink::Memory => (),
+            Sink::File(_) => {
+                let commit = &extracted_data.git.ref_target.1;
+                converters::save(
+                    &mut self.repo,
+                    &converted,
+                    commit,
+                    &fingerprint,
+                    &converted_encoded,
+                )?;
+            }
+            Sink::Writer(w) => {
+                if let Err(err) = w.write_all(&converted_encoded).and_then(|_| w.flush()) {
+                    return Err(Error::io("failed to write the converted data", err));
+                }
+            }
+        }
+
+        Ok(Output {
+            repo: self.repo,
+            extracted: extracted_data,
+            extracted_encoded,
+            converted,
+            converted_encoded,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache;
+    use crate::config;
+    use crate::converters::Converted;
+    use crate::error::Error;
+    use crate::filters;
+    use crate::formats;
+    use crate::pipeline::{self, Sink};
+    use crate::utils::test::{self, commit, TempDir};
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    fn conf(dir: &TempDir) -> config::Config {
+        config::Config {
+            storage_path: dir.join("repos"),
+            scanner_path: dir.join("scanner"),
+            ..Default::default()
+        }
+    }
+
+    fn bundle(dir: &TempDir, r: &git2::Repository) -> String {
+        let bundle = dir.join("app.bundle");
+        let status = Command::new("git")
+            .current_dir(r.workdir().unwrap())
+            .args(["bundle", "create", &bundle, "--all"])
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+        bundle
+    }
+
+    #[test]
+    fn scan_in_memory() {
+        let (dir, r) = test::temp_repo();
+        commit(&r, "a.rs", "fn a() {}\n");
+        commit(&r, "b.rs", "fn b() {}\n");
+
+        let path = dir.join("app");
+        let output = pipeline::from_path(&path)
+            .with_converter("dungeon")
+            .run(&conf(&dir))
+            .unwrap();
+        assert!(matches!(output.converted, Converted::Dungeon(_)));
+        assert_eq!(output.extracted.code.files_data.len(), 2);
+        let decoded: serde_json::Value = formats::decode(&output.converted_encoded).unwrap();
+        assert!(decoded.is_object());
+
+        //nothing is written to disk
+        assert!(!dir.path.join("scanner").exists());
+        assert!(output.repo.converted_file_path.is_empty());
+
+        let err = pipeline::from_path(&path)
+            .with_converter("unknown")
+            .run(&conf(&dir))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "unknown converter `unknown`");
+    }
+
+    #[test]
+    fn scan_a_ref_to_files() {
+        let (dir, r) = test::temp_repo();
+        let first = commit(&r, "a.rs", "fn a() {}\n");
+        r.tag_lightweight("v1", &r.find_object(first, None).unwrap(), false)
+            .unwrap();
+        commit(&r, "b.rs", "fn b() {}\n");
+        let bundle = bundle(&dir, &r);
+
+        let out = dir.join("out");
+        let output = pipeline::from_bundle(&bundle)
+            .with_ref("v1")
+            .with_format(formats::Format::MsgPack)
+            .with_sink(Sink::File(Some(out.to_owned())))
+            .run(&conf(&dir))
+            .unwrap();
+        let (ref_name, sha) = &output.extracted.git.ref_target;
+        assert_eq!(ref_name, "refs/tags/v1");
+        assert_eq!(sha, &first.to_string());
+        assert_eq!(output.extracted.code.files_data.len(), 1);
+        assert_eq!(
+            output.repo.converted_file_path,
+            format!("{out}/shmup-converted.msgpack")
+        );
+        assert!(Path::new(&output.repo.extracted_file_path).is_file());
+        assert_eq!(
+            fs::read(&output.repo.converted_file_path).unwrap(),
+            output.converted_encoded
+        );
+
+        //the default branch is checked out again
+        let output = pipeline::from_bundle(&bundle).run(&conf(&dir)).unwrap();
+        assert_eq!(output.extracted.code.files_data.len(), 2);
+
+        let err = pipeline::from_bundle(&bundle)
+            .with_ref("v2")
+            .run(&conf(&dir))
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::RefNotFound(_)), "{err}");
+        let path = dir.join("app");
+        let err = pipeline::from_path(&path)
+            .with_ref("v1")
+            .run(&conf(&dir))
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::Parse { .. }), "{err}");
+    }
+
+    #[test]
+    fn scan_to_a_writer() {
+        let (dir, r) = test::temp_repo();
+        commit(&r, "a.rs", "fn a() {}\n");
+
+        let written = dir.path.join("written.json");
+        let file = fs::File::create(&written).unwrap();
+        let path = dir.join("app");
+        let output = pipeline::from_path(&path)
+            .with_converter("codecity")
+            .with_sink(Sink::Writer(Box::new(file)))
+            .run(&conf(&dir))
+            .unwrap();
+        assert!(matches!(output.converted, Converted::Codecity(_)));
+        assert_eq!(fs::read(&written).unwrap(), output.converted_encoded);
+        assert!(!dir.path.join("scanner").exists());
+    }
+
+    #[test]
+    fn update_the_scan_of_a_new_commit() {
+        let (dir, r) = test::temp_repo();
+        commit(&r, "a.rs", "fn a() {}\n");
+        commit(&r, "b.md", "# b\n");
+
+        let path = dir.join("app");
+        let filters = filters::Options {
+            exclude: vec!["*.md".to_string()],
+            ..Default::default()
+        };
+        let open = || {
+            pipeline::from_path(&path)
+                .with_filters(filters.clone())
+                .open(&conf(&dir))
+                .unwrap()
+        };
+        let (previous, _) = open().extract().unwrap();
+        assert_eq!(previous.code.files_data.len(), 1);
+
+        //the new files are analysed, and filtered like the first ones
+        let last = commit(&r, "c.rs", "fn c() {}\n");
+        let (data, _) = open().update(&previous).unwrap();
+        assert_eq!(data.git.ref_target.1, last.to_string());
+        assert_eq!(data.code.files_data.len(), 2);
+    }
+
+    #[test]
+    fn cache_the_scans_by_commit_and_options() {
+        let (dir, r) = test::temp_repo();
+        let last = commit(&r, "a.rs", "fn a() {}\n");
+
+        //the cache is written again, it does not fail the scan
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        fs::write(cache::get_path(&out), "converted: [").unwrap();
+        let path = dir.join("app");
+        let open = |filters: filters::Options| {
+            pipeline::from_path(&path)
+                .with_filters(filters)
+                .with_sink(Sink::File(Some(out.to_owned())))
+                .open(&conf(&dir))
+                .unwrap()
+        };
+        let mut scan = open(filters::Options::default());
+        let fingerprint = scan.fingerprint();
+        let extracted = scan.extract().unwrap();
+        scan.convert(extracted).unwrap();
+        let c = cache::load(&out).unwrap();
+        assert!(c.find("shmup", &last.to_string(), &fingerprint).is_some());
+
+        //the files converted with other options are not reused
+        let scan = open(filters::Options {
+            max_depth: Some(1),
+            ..Default::default()
+        });
+        assert_ne!(scan.fingerprint(), fingerprint);
+    }
+}
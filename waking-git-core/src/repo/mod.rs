@@ -1,9 +1,23 @@
 use crate::config;
-use git2::{build::RepoBuilder, Direction, FetchOptions, Repository};
+use crate::error::Error;
+use crate::formats;
+use git2::{
+    build::{CheckoutBuilder, RepoBuilder},
+    Direction, FetchOptions, Repository,
+};
 use std::fs;
+use std::io::{self, Write};
 use std::path;
+use std::process;
+use std::thread;
+use std::time::Duration;
 use url::Url;
 
+//the time after which the lock of a repository is considered left
+//by a scan that did not terminate
+const STALE_LOCK_AFTER: Duration = Duration::from_secs(30 * 60);
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(100);
+
 pub struct Repo {
     pub repo: Repository,
     /// The slugged name of the git repository
@@ -20,74 +34,79 @@ pub struct Repo {
 
     /// the absolute path of the converted.json file generared by the converter
     pub converted_file_path: String,
+
+    /// the format of the extracted and converted files
+    pub format: formats::Format,
+
+    /// traverse the checked out HEAD instead of the default branch,
+    /// set for the local repositories
+    pub follow_head: bool,
+
+    /// the branch, tag or commit to traverse instead of the default one
+    pub ref_name: Option<String>,
 }
 
-pub fn clone_repository(repo: &String, conf: &config::Config) -> Result<Repo, String> {
+pub fn clone_repository(repo: &String, conf: &config::Config) -> Result<Repo, Error> {
     //Create the temporary directory if it doesn't exist
     let path = path::Path::new(&conf.wake_path);
     if !path.exists() {
-        match fs::create_dir(&conf.wake_path) {
+        match fs::create_dir_all(&conf.wake_path) {
             Ok(()) => {
-                println!("`{}` Temporary folder was created", conf.wake_path);
+                eprintln!("`{}` Temporary folder was created", conf.wake_path);
             }
             Err(err) => {
-                return Err(format!(
-                    "Failed to create `{}` folder: {}",
-                    conf.wake_path, err
+                return Err(Error::io(
+                    format!("Failed to create `{}` folder", conf.wake_path),
+                    err,
                 ));
             }
         };
     }
 
-    let r = match new_repo_from_url(repo.to_string(), conf) {
-        Ok(r) => r,
-        Err(err) => {
-            return Err(format!("Error: {err}"));
-        }
-    };
-
-    Ok(r)
+    new_repo_from_url(repo.to_string(), conf)
 }
 
-pub fn new_repo_from_url(url: String, conf: &config::Config) -> Result<Repo, String> {
-    let repo_storage = conf.storage_path.to_owned();
-    let repo_name: String;
-    let repo_owner: String;
-    let mut host_name: String;
-    let p_url = Url::parse(&url);
-    match p_url {
+/// returns the name of the folder the repository `url` is cloned in,
+/// made of its host, owner and name
+pub fn get_folder_name(url: &str) -> Result<String, Error> {
+    let p_url = match Url::parse(url) {
+        Ok(u) => u,
         Err(err) => {
-            return Err(format!("Failed to parse `{url}` repository url: {err}"));
+            return Err(Error::InvalidUrl(format!(
+                "Failed to parse `{url}` repository url: {err}"
+            )));
         }
-        Ok(p_url) => {
-            //Check that the repo is a url
-            if p_url.scheme() != "https" {
-                return Err(
-                    "Failed to fetch the repository: Repository not a https url".to_string()
-                );
-            }
-
-            //Extract repository name and owner
-            let path_segments: Vec<&str> = p_url.path().split('/').collect();
-            if path_segments.len() <= 2 {
-                return Err(format!(
-                    "Failed to parse repository owner and name from `{p_url}`"
-                ));
-            }
+    };
 
-            repo_owner = path_segments[1].to_string();
-            repo_name = path_segments[2].to_string();
-            match p_url.host_str() {
-                Some(h) => host_name = format!("{h}-"),
-                None => host_name = "".to_string(),
-            }
+    //Check that the repo is a url
+    if p_url.scheme() != "https" {
+        return Err(Error::InvalidUrl(
+            "Failed to fetch the repository: Repository not a https url".to_string(),
+        ));
+    }
 
-            host_name = host_name.replace('.', "-");
-        }
+    //Extract repository name and owner
+    let path_segments: Vec<&str> = p_url.path().split('/').collect();
+    if path_segments.len() <= 2 {
+        return Err(Error::InvalidUrl(format!(
+            "Failed to parse repository owner and name from `{p_url}`"
+        )));
     }
 
+    let repo_owner = path_segments[1];
+    let repo_name = path_segments[2];
+    let host_name = match p_url.host_str() {
+        Some(h) => format!("{h}-").replace('.', "-"),
+        None => "".to_string(),
+    };
+    Ok(format!("{host_name}{repo_owner}-{repo_name}"))
+}
+
+pub fn new_repo_from_url(url: String, conf: &config::Config) -> Result<Repo, Error> {
+    let repo_storage = conf.storage_path.to_owned();
+
     //Clone the repository if it doesn't exist on disk
-    let folder_name = format!("{host_name}{repo_owner}-{repo_name}");
+    let folder_name = get_folder_name(&url)?;
     let dest_path = format!("{repo_storage}/{folder_name}");
     let path = path::Path::new(&dest_path);
     let git_repo: Repository = if !path.exists() {
@@ -95,7 +114,10 @@ pub fn new_repo_from_url(url: String, conf: &config::Config) -> Result<Repo, Str
         let repo = match Repository::init(&tmp_git2_repo) {
             Ok(r) => r,
             Err(err) => {
-                return Err(format!("Failed to initialize repo {tmp_git2_repo}: {err}"));
+                return Err(clone_error(
+                    format!("Failed to initialize repo {tmp_git2_repo}"),
+                    err,
+                ));
             }
         };
 
@@ -103,25 +125,36 @@ pub fn new_repo_from_url(url: String, conf: &config::Config) -> Result<Repo, Str
         let mut remote = match repo.remote_anonymous(url.as_str()) {
             Ok(r) => r,
             Err(err) => {
-                return Err(format!(
-                    "Failed to fetch the remote_anonymous of {url}: {err}"
+                return Err(clone_error(
+                    format!("Failed to fetch the remote_anonymous of {url}"),
+                    err,
                 ));
             }
         };
         if let Err(err) = remote.connect(Direction::Fetch) {
-            return Err(format!("Failed to connect the remote: {err}"));
+            return Err(clone_error("Failed to connect the remote", err));
         }
         let default = match remote.default_branch() {
             Ok(b) => b,
             Err(err) => {
-                return Err(format!("Failed to get the default branch: {err}"));
+                return Err(clone_error("Failed to get the default branch", err));
+            }
+        };
+        let branch = match default.as_str() {
+            Some(b) => b.replace("refs/heads/", ""),
+            None => {
+                return Err(Error::Clone {
+                    message: format!("Invalid default branch name of `{url}`"),
+                    source: None,
+                })
             }
         };
-        let branch = default.as_str().unwrap().replace("refs/heads/", "");
 
-        // Set the --depth option to 1.
+        // Set the --depth option, 0 clones the whole history
         let mut fetch_option = FetchOptions::new();
-        fetch_option.depth(1);
+        if conf.settings.clone_depth > 0 {
+            fetch_option.depth(conf.settings.clone_depth as i32);
+        }
 
         let mut builder = RepoBuilder::new();
         builder.branch(&branch);
@@ -130,23 +163,33 @@ pub fn new_repo_from_url(url: String, conf: &config::Config) -> Result<Repo, Str
         match builder.clone(url.as_str(), path) {
             Ok(git_repo) => git_repo,
             Err(err) => {
-                return Err(format!("Failed to clone `{url}` repository: {err}"));
+                return Err(clone_error(
+                    format!("Failed to clone `{url}` repository"),
+                    err,
+                ));
             }
         }
     } else {
-        match Repository::open(&dest_path) {
+        let git_repo = match Repository::open(&dest_path) {
             Ok(git_repo) => git_repo,
             Err(err) => {
-                return Err(format!("Failed to clone `{url}` repository: {err}"));
+                return Err(clone_error(
+                    format!("Failed to clone `{url}` repository"),
+                    err,
+                ));
+            }
+        };
+        if conf.settings.fetch == config::FetchPolicy::Always {
+            if let Err(err) = fetch(&git_repo, conf.settings.clone_depth) {
+                return Err(clone_error(
+                    format!("Failed to fetch `{url}` repository"),
+                    err,
+                ));
             }
         }
+        git_repo
     };
-    let scanner_path = format!(
-        "{}/{}/{}",
-        conf.wake_path,
-        config::SCANNER_FOLDER_NAME,
-        folder_name
-    );
+    let scanner_path = format!("{}/{}", conf.scanner_path, folder_name);
 
     let repo = Repo {
         repo: git_repo,
@@ -155,7 +198,395 @@ pub fn new_repo_from_url(url: String, conf: &config::Config) -> Result<Repo, Str
         scanner_path,
         extracted_file_path: "".to_string(),
         converted_file_path: "".to_string(),
+        format: formats::Format::default(),
+        follow_head: false,
+        ref_name: None,
     };
 
     Ok(repo)
 }
+
+/// open the local repository containing `path`, without cloning it.
+/// Its files are written to the `local-<folder name>` scanner folder
+pub fn open_local(path: &str, conf: &config::Config) -> Result<Repo, Error> {
+    let git_repo = match Repository::discover(path) {
+        Ok(r) => r,
+        Err(err) => {
+            return Err(clone_error(
+                format!("Failed to open `{path}` repository"),
+                err,
+            ))
+        }
+    };
+    let workdir = match git_repo.workdir().map(fs::canonicalize) {
+        Some(Ok(w)) => w,
+        Some(Err(err)) => {
+            return Err(Error::io(
+                format!("Failed to open `{path}` repository"),
+                err,
+            ))
+        }
+        None => {
+            return Err(Error::Clone {
+                message: format!("`{path}` repository has no working directory"),
+                source: None,
+            })
+        }
+    };
+    let name = match workdir.file_name() {
+        Some(n) => n.to_string_lossy().replace('.', "-"),
+        None => {
+            return Err(Error::Clone {
+                message: format!("Failed to get `{path}` repository name"),
+                source: None,
+            })
+        }
+    };
+    let folder_name = format!("local-{name}");
+    let scanner_path = format!("{}/{}", conf.scanner_path, folder_name);
+
+    Ok(Repo {
+        repo: git_repo,
+        folder_name,
+        folder_path: workdir.to_string_lossy().into_owned(),
+        scanner_path,
+        extracted_file_path: "".to_string(),
+        converted_file_path: "".to_string(),
+        format: formats::Format::default(),
+        follow_head: true,
+        ref_name: None,
+    })
+}
+
+/// returns the `bundle-<file name>` folder name of the git bundle file `path`
+pub fn get_bundle_folder_name(path: &str) -> Result<String, Error> {
+    match path::Path::new(path).file_stem() {
+        Some(n) => Ok(format!("bundle-{}", n.to_string_lossy().replace('.', "-"))),
+        None => Err(Error::InvalidUrl(format!(
+            "Failed to get `{path}` bundle name"
+        ))),
+    }
+}
+
+/// clone the git bundle file `path` in the storage folder, it is
+/// cloned again when the fetch policy is `always`.
+/// Its files are written to the `bundle-<file name>` scanner folder
+pub fn open_bundle(path: &str, conf: &config::Config) -> Result<Repo, Error> {
+    let folder_name = get_bundle_folder_name(path)?;
+    let dest_path = format!("{}/{folder_name}", conf.storage_path);
+    let dest = path::Path::new(&dest_path);
+    if dest.exists() && conf.settings.fetch == config::FetchPolicy::Always {
+        if let Err(err) = fs::remove_dir_all(dest) {
+            return Err(Error::io(format!("Failed to remove `{dest_path}`"), err));
+        }
+    }
+
+    //libgit2 cannot read the bundles, they are cloned by git
+    if !dest.exists() {
+        let output = process::Command::new("git")
+            .args(["clone", "--quiet", path, &dest_path])
+            .output();
+        match output {
+            Ok(o) if o.status.success() => (),
+            Ok(o) => {
+                return Err(Error::Clone {
+                    message: format!(
+                        "Failed to clone `{path}` bundle: {}",
+                        String::from_utf8_lossy(&o.stderr).trim()
+                    ),
+                    source: None,
+                })
+            }
+            Err(err) => {
+                return Err(Error::io(
+                    format!("Failed to run git to clone `{path}` bundle"),
+                    err,
+                ))
+            }
+        }
+    }
+    let git_repo = match Repository::open(&dest_path) {
+        Ok(r) => r,
+        Err(err) => {
+            return Err(clone_error(
+                format!("Failed to open `{path}` bundle clone"),
+                err,
+            ))
+        }
+    };
+    let scanner_path = format!("{}/{}", conf.scanner_path, folder_name);
+
+    Ok(Repo {
+        repo: git_repo,
+        folder_name,
+        folder_path: dest_path,
+        scanner_path,
+        extracted_file_path: "".to_string(),
+        converted_file_path: "".to_string(),
+        format: formats::Format::default(),
+        follow_head: true,
+        ref_name: None,
+    })
+}
+
+/// returns the paths of the files added, modified or deleted
+/// between the commits `from` and `to` of `r`
+pub fn changed_paths(r: &Repository, from: &str, to: &str) -> Result<Vec<String>, Error> {
+    let tree = |sha: &str| r.find_commit(git2::Oid::from_str(sha)?)?.tree();
+    let diff = || r.diff_tree_to_tree(Some(&tree(from)?), Some(&tree(to)?), None);
+    let diff = match diff() {
+        Ok(d) => d,
+        Err(err) => {
+            return Err(Error::Extract {
+                message: format!("failed to list the files changed between `{from}` and `{to}`"),
+                source: Some(Box::new(err)),
+            })
+        }
+    };
+
+    let mut paths = vec![];
+    for delta in diff.deltas() {
+        for file in [delta.old_file(), delta.new_file()] {
+            if let Some(p) = file.path().and_then(|p| p.to_str()) {
+                if !paths.iter().any(|c| c == p) {
+                    paths.push(p.to_owned());
+                }
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Lock is held while a repository is cloned and scanned, so that the
+/// scans of the same repository do not race on its files. It is released
+/// when dropped
+#[derive(Debug)]
+pub struct Lock {
+    path: String,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// wait for the lock of the repository folder `folder_name`, a file
+/// created next to the repository clones in the storage folder.
+///
+/// The locks older than `STALE_LOCK_AFTER`, left by a scan that
+/// did not terminate, are ignored
+pub fn lock(folder_name: &str, conf: &config::Config) -> Result<Lock, Error> {
+    if let Err(err) = fs::create_dir_all(&conf.storage_path) {
+        return Err(Error::io(
+            format!("Failed to create `{}` folder", conf.storage_path),
+            err,
+        ));
+    }
+
+    let path = format!("{}/{folder_name}.lock", conf.storage_path);
+    loop {
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut f) => {
+                let _ = write!(f, "{}", process::id());
+                return Ok(Lock { path });
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                if is_stale(&path) {
+                    let _ = fs::remove_file(&path);
+                    continue;
+                }
+                thread::sleep(LOCK_RETRY_DELAY);
+            }
+            Err(err) => return Err(Error::io(format!("Failed to lock `{path}`"), err)),
+        }
+    }
+}
+
+fn clone_error(message: impl Into<String>, err: git2::Error) -> Error {
+    Error::Clone {
+        message: message.into(),
+        source: Some(Box::new(err)),
+    }
+}
+
+fn is_stale(path: &str) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age > STALE_LOCK_AFTER)
+}
+
+/// fetch the current branch of the `origin` remote of `r`,
+/// and check it out. `depth` commits are fetched, 0 fetches
+/// the whole history
+pub fn fetch(r: &Repository, depth: u32) -> Result<(), git2::Error> {
+    let head = r.head()?;
+    let (ref_name, branch) = match (head.name(), head.shorthand()) {
+        (Some(n), Some(b)) => (n.to_owned(), b.to_owned()),
+        _ => return Err(git2::Error::from_str("invalid HEAD reference name")),
+    };
+
+    let mut fetch_option = FetchOptions::new();
+    if depth > 0 {
+        fetch_option.depth(depth as i32);
+    }
+    let remote_ref = format!("refs/remotes/origin/{branch}");
+    let mut remote = r.find_remote("origin")?;
+    remote.fetch(
+        &[format!("+refs/heads/{branch}:{remote_ref}")],
+        Some(&mut fetch_option),
+        None,
+    )?;
+
+    //the branch follows the remote, the local history is dropped
+    let oid = r.refname_to_id(&remote_ref)?;
+    r.reference(&ref_name, oid, true, "wake: fetch")?;
+    r.checkout_head(Some(CheckoutBuilder::new().force()))
+}
+
+impl Repo {
+    /// returns the ref used to traverse the repository and its commit oid,
+    /// `ref_name` when it is set, the checked out HEAD when `follow_head` is set,
+    /// `master` if it exists and `main` otherwise
+    pub fn ref_target(&self) -> Result<(String, String), git2::Error> {
+        if let Some(name) = &self.ref_name {
+            let oid = self.repo.revparse_single(name)?.peel_to_commit()?.id();
+            //the branches and tags are named by their full name, the commits by their oid
+            let full_name = match self.repo.resolve_reference_from_short_name(name) {
+                Ok(r) => r.name().unwrap_or(name).to_owned(),
+                Err(_) => name.to_owned(),
+            };
+            return Ok((full_name, oid.to_string()));
+        }
+
+        if self.follow_head {
+            let head = self.repo.head()?;
+            let oid = head.peel_to_commit()?.id();
+            return Ok((head.name().unwrap_or("HEAD").to_owned(), oid.to_string()));
+        }
+
+        let mut ref_name = "refs/heads/master";
+        let oid = match self.repo.refname_to_id(ref_name) {
+            Ok(oid) => oid,
+            Err(_) => {
+                ref_name = "refs/heads/main";
+                self.repo.refname_to_id(ref_name)?
+            }
+        };
+        Ok((ref_name.to_owned(), oid.to_string()))
+    }
+
+    /// check out the files of `ref_name`, or of HEAD when it is not set,
+    /// as the code is extracted from the working directory
+    pub fn checkout(&self) -> Result<(), git2::Error> {
+        let mut builder = CheckoutBuilder::new();
+        builder.force();
+        match &self.ref_name {
+            Some(name) => {
+                let commit = self.repo.revparse_single(name)?.peel_to_commit()?;
+                self.repo
+                    .checkout_tree(commit.as_object(), Some(&mut builder))
+            }
+            None => self.repo.checkout_head(Some(&mut builder)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repo;
+    use crate::utils::test::{self, commit};
+    use std::path::Path;
+
+    #[test]
+    fn lock_repositories() {
+        let dir = test::temp_dir();
+        let conf = crate::config::Config {
+            storage_path: dir.path.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let url = "https://github.com/elhmn/ckp";
+        let folder_name = "github-com-elhmn-ckp";
+        assert_eq!(repo::get_folder_name(url).unwrap(), folder_name);
+        assert_eq!(
+            repo::get_bundle_folder_name("/tmp/my.app.bundle").unwrap(),
+            "bundle-my-app"
+        );
+
+        //the second lock waits for the first one to be released
+        let first = repo::lock(folder_name, &conf).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let storage = conf.storage_path.to_owned();
+        let waiter = std::thread::spawn(move || {
+            let conf = crate::config::Config {
+                storage_path: storage,
+                ..Default::default()
+            };
+            let _second = repo::lock(folder_name, &conf).unwrap();
+            tx.send(()).unwrap();
+        });
+        let timeout = std::time::Duration::from_millis(300);
+        assert!(rx.recv_timeout(timeout).is_err());
+        drop(first);
+        waiter.join().unwrap();
+        assert!(rx.try_recv().is_ok());
+        assert!(!dir.path.join("github-com-elhmn-ckp.lock").exists());
+    }
+
+    #[test]
+    fn fetch_the_remote_branch() {
+        let (dir, origin) = test::temp_repo();
+        commit(&origin, "a.txt", "a");
+
+        let url = format!("file://{}", dir.join("app"));
+        let clone = git2::Repository::clone(&url, dir.path.join("clone")).unwrap();
+        let last = commit(&origin, "b.txt", "b");
+
+        repo::fetch(&clone, 0).unwrap();
+        assert_eq!(clone.head().unwrap().target(), Some(last));
+        assert!(dir.path.join("clone/b.txt").is_file());
+    }
+
+    #[test]
+    fn update_local_repositories() {
+        let dir = test::temp_dir();
+        let r = git2::Repository::init(dir.path.join("my.app")).unwrap();
+        let first = commit(&r, "a.rs", "fn a() {}\n");
+        commit(&r, "b.rs", "fn b() {}\n");
+
+        let conf = crate::config::Config {
+            scanner_path: dir.join("scanner"),
+            ..Default::default()
+        };
+        let path = dir.join("my.app");
+        let mut local = repo::open_local(&path, &conf).unwrap();
+        assert_eq!(local.folder_name, "local-my-app");
+
+        //the checked out branch is followed
+        r.branch("feature", &r.find_commit(first).unwrap(), false)
+            .unwrap();
+        r.set_head("refs/heads/feature").unwrap();
+        r.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))
+            .unwrap();
+        let (ref_name, sha) = local.ref_target().unwrap();
+        assert_eq!(ref_name, "refs/heads/feature");
+        assert_eq!(sha, first.to_string());
+
+        let (previous, _) = crate::extractor::extract(&mut local).unwrap();
+        assert_eq!(previous.code.files_data.len(), 1);
+        let last = commit(&r, "c.rs", "fn c() {}\n");
+        let changed = repo::changed_paths(&r, &sha, &last.to_string()).unwrap();
+        assert_eq!(changed, vec!["c.rs"]);
+
+        let data = crate::extractor::update(&local, &previous).unwrap();
+        assert_eq!(data.git.ref_target.1, last.to_string());
+        assert_eq!(data.code.files_data.len(), 2);
+        assert!(Path::new(&local.extracted_file_path).is_file());
+    }
+}
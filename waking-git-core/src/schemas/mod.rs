@@ -0,0 +1,214 @@
+use crate::converters::{codealkemi, codecity, dungeon, graph, midi, shmup};
+use crate::error::Error;
+use crate::{config, extractor, formats};
+use jsonschema::JSONSchema;
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+use std::fmt;
+use std::path::Path;
+
+pub const EXTRACTED: &str = "extracted";
+pub const SHMUP: &str = "shmup";
+pub const CODEALKEMI: &str = "codealkemi";
+pub const GRAPH: &str = "graph";
+pub const CODECITY: &str = "codecity";
+pub const DUNGEON: &str = "dungeon";
+pub const MIDI: &str = "midi";
+
+/// The names of every schema shipped with wake
+pub const NAMES: [&str; 7] = [EXTRACTED, SHMUP, CODEALKEMI, GRAPH, CODECITY, DUNGEON, MIDI];
+
+/// Violation is a value of a document that does not match its schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    //the JSON pointer to the invalid value, as `/scenes/<id>/entities`
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.pointer.as_str() {
+            "" => write!(f, "/: {}", self.message),
+            p => write!(f, "{p}: {}", self.message),
+        }
+    }
+}
+
+/// returns the JSON schema named `name`, generated from the rust types
+pub fn get(name: &str) -> Result<RootSchema, Error> {
+    let (mut schema, title) = match name {
+        EXTRACTED => (schema_for!(extractor::Data), "wake extracted data"),
+        SHMUP => (schema_for!(shmup::Data), "wake shmup converted data"),
+        CODEALKEMI => (
+            schema_for!(codealkemi::Data),
+            "wake codealkemi converted data",
+        ),
+        GRAPH => (schema_for!(graph::Data), "wake graph converted data"),
+        CODECITY => (schema_for!(codecity::Data), "wake codecity converted data"),
+        DUNGEON => (schema_for!(dungeon::Data), "wake dungeon converted data"),
+        MIDI => (schema_for!(midi::Data), "wake midi converted data"),
+        _ => {
+            return Err(Error::parse(format!(
+                "unknown schema `{name}`, expected one of: {}",
+                NAMES.join(", ")
+            )))
+        }
+    };
+
+    schema.schema.metadata().title = Some(title.to_owned());
+    Ok(schema)
+}
+
+/// returns the name of the schema a file generated by wake follows,
+/// based on its file name: `extracted.<format>` or `<converter>-converted.<format>`
+pub fn name_from_path(path: &str) -> Result<&'static str, Error> {
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = formats::strip_extension(&file_name);
+
+    if file_name == config::EXTRACTOR_FILE_NAME {
+        return Ok(EXTRACTED);
+    }
+
+    let suffix = format!("-{}", config::CONVERTER_FILE_NAME_PREFIX);
+    if let Some(converter) = file_name.strip_suffix(&suffix) {
+        if let Some(name) = NAMES.iter().find(|n| **n == converter) {
+            return Ok(name);
+        }
+    }
+
+    Err(Error::parse(format!(
+        "can't guess the schema of `{path}`, expected a `{}.<format>` or `<converter>{suffix}.<format>` file",
+        config::EXTRACTOR_FILE_NAME
+    )))
+}
+
+/// validate `document` against the schema named `name`,
+/// and return every violation found
+pub fn validate(name: &str, document: &serde_json::Value) -> Result<Vec<Violation>, Error> {
+    let schema = match serde_json::to_value(get(name)?) {
+        Ok(s) => s,
+        Err(err) => {
+            return Err(Error::serialize(
+                format!("failed to serialize the `{name}` schema"),
+                err,
+            ))
+        }
+    };
+
+    validate_with(&schema, document)
+}
+
+/// validate `document` against a JSON `schema`,
+/// and return every violation found
+pub fn validate_with(
+    schema: &serde_json::Value,
+    document: &serde_json::Value,
+) -> Result<Vec<Violation>, Error> {
+    //the compile error borrows the schema, only its message is kept
+    let compiled = match JSONSchema::compile(schema) {
+        Ok(c) => c,
+        Err(err) => return Err(Error::parse(format!("failed to compile the schema: {err}"))),
+    };
+
+    let violations = match compiled.validate(document) {
+        Ok(_) => vec![],
+        Err(errors) => errors
+            .map(|err| Violation {
+                pointer: err.instance_path.to_string(),
+                message: err.to_string(),
+            })
+            .collect(),
+    };
+
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schemas;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn schemas_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("schemas")
+    }
+
+    //run the tests with `WAKE_UPDATE_SCHEMAS=1` to update the shipped
+    //schemas after a change to the extracted or converted data types
+    #[test]
+    fn shipped_schemas_are_up_to_date() {
+        for name in schemas::NAMES {
+            let schema = schemas::get(name).unwrap();
+            let generated = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+            let path = schemas_dir().join(format!("{name}.schema.json"));
+
+            if std::env::var("WAKE_UPDATE_SCHEMAS").is_ok() {
+                fs::write(&path, &generated).unwrap();
+            }
+
+            let shipped = fs::read_to_string(&path).unwrap_or_default();
+            assert!(
+                shipped == generated,
+                "`{}` is out of date, run the tests with `WAKE_UPDATE_SCHEMAS=1` to update it",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn golden_files_validate() {
+        for name in schemas::NAMES {
+            let path = schemas_dir().join("golden").join(match name {
+                schemas::EXTRACTED => "extracted.json".to_string(),
+                n => format!("{n}-converted.json"),
+            });
+            let content = fs::read_to_string(&path).unwrap();
+            let document = serde_json::from_str(&content).unwrap();
+
+            let violations = schemas::validate(name, &document).unwrap();
+            assert!(
+                violations.is_empty(),
+                "{} does not validate: {violations:?}",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn report_violations_with_json_pointers() {
+        let document = serde_json::json!({
+            "version": "1.0.0",
+            "name": "codealkemi",
+            "repo_name": "repo",
+            "url": "",
+            "commit": "abc",
+            "main_scene": "root",
+            "scenes": {"root": {"entities": {}, "sub_scenes": "src"}}
+        });
+
+        let violations = schemas::validate(schemas::CODEALKEMI, &document).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].pointer, "/scenes/root/sub_scenes");
+    }
+
+    #[test]
+    fn guess_schema_from_file_name() {
+        let name = |p| schemas::name_from_path(p).ok();
+        assert_eq!(
+            name("scanner/repo/extracted.json"),
+            Some(schemas::EXTRACTED)
+        );
+        assert_eq!(name("shmup-converted.json"), Some(schemas::SHMUP));
+        assert_eq!(name("codealkemi-converted.json"), Some(schemas::CODEALKEMI));
+        assert_eq!(name("graph-converted.json"), Some(schemas::GRAPH));
+        assert_eq!(name("codecity-converted.json"), Some(schemas::CODECITY));
+        assert_eq!(name("dungeon-converted.json"), Some(schemas::DUNGEON));
+        assert_eq!(name("midi-converted.json"), Some(schemas::MIDI));
+        assert_eq!(name("shmup-converted.msgpack"), Some(schemas::SHMUP));
+        assert!(name("data.json").is_none());
+    }
+}
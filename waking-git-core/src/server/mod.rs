@@ -1,7 +1,11 @@
-use crate::repo;
+use crate::config;
+use crate::converters::{self, external};
+use crate::error::Error;
+use crate::filters;
+use crate::formats;
+use crate::pipeline;
 use axum::{
-    body::StreamBody,
-    http::{header, HeaderMap, StatusCode},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::IntoResponse,
     routing::get,
     Extension, Json, Router,
@@ -10,42 +14,46 @@ use flate2::write::GzEncoder;
 use flate2::Compression;
 use serde::{Deserialize, Serialize};
 use simple_logger;
-use std::fs::File;
+use std::net::SocketAddr;
 use std::sync::{
     mpsc::{self, Receiver, Sender},
     Arc, Mutex,
 };
 use tar::Builder;
-use tokio_util::io::ReaderStream;
 
 struct Server {
     pub port: String,
+    pub conf: Arc<config::Config>,
     pub tx: Mutex<Sender<Arc<Task>>>,
     pub rx: Mutex<Receiver<Arc<Task>>>,
 }
 
-type ScanResult = (String, String, repo::Repo);
+//the extracted data, the converted data and the scanned repository
+type ScanResult = pipeline::Output;
+
+//the error returned to the client, with its status code
+type ResponseError = (StatusCode, String);
 
 struct Task {
-    pub task: fn(conf: Arc<crate::config::Config>, repo: Arc<String>) -> Result<ScanResult, String>,
-    pub repo: Arc<String>,
-    pub conf: Arc<crate::config::Config>,
+    pub task: fn(conf: Arc<config::Config>, req: Arc<ScanRequest>) -> Result<ScanResult, Error>,
+    pub req: Arc<ScanRequest>,
+    pub conf: Arc<config::Config>,
     //TODO: create type for the Sender and Receiver,
     //it is quite complicated to read at the moment
-    pub tx: Mutex<Sender<Result<ScanResult, String>>>,
-    pub rx: Mutex<Receiver<Result<ScanResult, String>>>,
+    pub tx: Mutex<Sender<Result<ScanResult, Error>>>,
+    pub rx: Mutex<Receiver<Result<ScanResult, Error>>>,
 }
 
 impl Task {
     pub fn new(
-        task: fn(conf: Arc<crate::config::Config>, repo: Arc<String>) -> Result<ScanResult, String>,
-        repo: Arc<String>,
-        conf: Arc<crate::config::Config>,
+        task: fn(conf: Arc<config::Config>, req: Arc<ScanRequest>) -> Result<ScanResult, Error>,
+        req: Arc<ScanRequest>,
+        conf: Arc<config::Config>,
     ) -> Self {
         let (tx, rx) = mpsc::channel();
         Self {
             task,
-            repo,
+            req,
             conf,
             tx: Mutex::new(tx),
             rx: Mutex::new(rx),
@@ -53,14 +61,31 @@ impl Task {
     }
 }
 
-pub fn run(port: String) {
+/// run the server on `port`, the repositories are scanned
+/// by a pool of `conf.settings.workers` threads
+pub fn run(port: String, conf: config::Config) {
     //Initialise the verbose logger
     //TODO: it should be a little less verbose
-    simple_logger::init().unwrap();
+    if let Err(err) = simple_logger::init() {
+        eprintln!("Failed to initialise the logger: {err}");
+    }
 
     let (tx, rx) = mpsc::channel();
+    //0 workers lets rayon use the number of cpus
+    let pool = match rayon::ThreadPoolBuilder::new()
+        .num_threads(conf.settings.workers)
+        .thread_name(|i| format!("wake-scan-{i}"))
+        .build()
+    {
+        Ok(p) => p,
+        Err(err) => {
+            log::error!("Failed to create the scan workers: {err}");
+            return;
+        }
+    };
     let server: Arc<Server> = Arc::new(Server {
         port,
+        conf: Arc::new(conf),
         tx: Mutex::new(tx),
         rx: Mutex::new(rx),
     });
@@ -79,27 +104,49 @@ pub fn run(port: String) {
     //
     //The current solution is good enough for now, but it is not ideal.
     let s = server.clone();
-    rayon::spawn(move || {
-        while let Ok(task) = s.rx.lock().unwrap().recv() {
-            rayon::spawn(move || {
-                let t = task.clone();
-                let ret = t.task.to_owned()(t.conf.to_owned(), t.repo.to_owned());
-                task.tx.lock().unwrap().send(ret).unwrap();
-            })
-        }
+    std::thread::spawn(move || loop {
+        let task = match s.rx.lock().map(|rx| rx.recv()) {
+            Ok(Ok(t)) => t,
+            //the server is shutting down
+            _ => break,
+        };
+        pool.spawn(move || {
+            let ret = (task.task)(task.conf.to_owned(), task.req.to_owned());
+            //the request may have been dropped in the meantime
+            let sent = match task.tx.lock() {
+                Ok(tx) => tx.send(ret).is_ok(),
+                Err(_) => false,
+            };
+            if !sent {
+                log::error!("Failed to send the task result");
+            }
+        })
     });
 
-    let main_runtime = tokio::runtime::Builder::new_multi_thread()
+    let main_runtime = match tokio::runtime::Builder::new_multi_thread()
         .thread_name("tokio-main")
         .enable_all()
         .build()
-        .unwrap();
+    {
+        Ok(r) => r,
+        Err(err) => {
+            log::error!("Failed to create the runtime: {err}");
+            return;
+        }
+    };
     main_runtime.block_on(async {
         serve(server.clone()).await;
     });
 }
 
 async fn serve(server: Arc<Server>) {
+    let addr: SocketAddr = match format!("0.0.0.0:{}", server.port).parse() {
+        Ok(a) => a,
+        Err(err) => {
+            log::error!("Invalid port `{}`: {err}", server.port);
+            return;
+        }
+    };
     println!("Server running on port {}", server.port);
 
     // build our application with a single route
@@ -110,17 +157,28 @@ async fn serve(server: Arc<Server>) {
         .route("/scan/converted", get(get_converted))
         .layer(Extension(server.clone()));
 
-    axum::Server::bind(&format!("0.0.0.0:{}", server.port).parse().unwrap())
+    if let Err(err) = axum::Server::bind(&addr)
         .serve(app.into_make_service())
         .await
-        .unwrap();
+    {
+        log::error!("Server error: {err}");
+    }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Default)]
 pub struct ScanRequest {
     pub repo_url: Option<String>,
-    //TODO: add ref
+    //the branch, tag or commit to scan, the default branch otherwise
     pub _ref_: Option<String>,
+    //the format of the scanned data: json | json.gz | msgpack | cbor
+    //json by default
+    pub format: Option<String>,
+    //the name of the converter, a builtin or a registered external converter,
+    //shmup by default
+    pub converter: Option<String>,
+    //the filters run on the extracted data before it is converted
+    #[serde(flatten)]
+    pub filters: filters::Options,
 }
 
 //This example should pretty much show you how to write basic handler
@@ -129,78 +187,38 @@ async fn get_scan(
     server: Extension<Arc<Server>>,
     Json(payload): Json<ScanRequest>,
 ) -> impl IntoResponse {
-    let repo = payload.repo_url.unwrap_or_default();
-    let conf = crate::config::Config::new();
-    let task = Arc::new(Task::new(scan, Arc::new(repo), Arc::new(conf)));
+    let output = run_task(&server, payload)?;
 
-    //Sending the task to the scheduler
-    if let Err(err) = server.tx.lock().unwrap().send(task.clone()) {
-        log::error!("Failed to send task: {}", err);
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to send task".to_owned(),
-        ));
-    };
-
-    //Wait for the scheduler response
-    let (_, _, repo) = match task.rx.lock().unwrap().recv().unwrap() {
-        Ok(d) => d,
+    //Create a compressed tarball
+    let body = match create_tarball(&output) {
+        Ok(b) => b,
         Err(err) => {
-            log::error!("Failed to scan data: {err}");
+            log::error!("Failed to create the tarball: {err}");
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to scan data".to_owned(),
+                "Failed to create the tarball".to_owned(),
             ));
         }
     };
 
-    //Create a compressed tarball
-    let tarball = format!("{}/{}.tar", &repo.scanner_path, &repo.folder_name);
-    let compressed_tarball = format!("{tarball}.gz");
-    let output_file = File::create(&compressed_tarball).unwrap();
-    let mut encoder = GzEncoder::new(output_file, Compression::default());
-    let mut builder = Builder::new(&mut encoder);
-    builder
-        .append_file(
-            "converted.json",
-            &mut File::open(&repo.converted_file_path).unwrap(),
-        )
-        .unwrap();
-    builder
-        .append_file(
-            "extracted.json",
-            &mut File::open(&repo.extracted_file_path).unwrap(),
-        )
-        .unwrap();
-    builder.finish().unwrap();
-
-    //Sending the compressed tarball to the client
-    let file = match tokio::fs::File::open(&compressed_tarball).await {
-        Ok(file) => file,
+    //Setting response headers
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/gzip"),
+    );
+    headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    let disposition = format!("attachment; filename={}.tar.gz", output.repo.folder_name);
+    match HeaderValue::from_str(&disposition) {
+        Ok(v) => headers.insert(header::CONTENT_DISPOSITION, v),
         Err(err) => {
-            log::error!("Failed to open file: {err}");
+            log::error!("Invalid content disposition `{disposition}`: {err}");
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to open file".to_owned(),
+                "Failed to set the response headers".to_owned(),
             ));
         }
     };
-    let stream = ReaderStream::new(file);
-    let body = StreamBody::new(stream);
-    //Setting response headers
-    let mut headers = HeaderMap::new();
-    headers.insert(header::CONTENT_TYPE, "application/gzip".parse().unwrap());
-    headers.insert(header::CONTENT_ENCODING, "gzip".parse().unwrap());
-    //TODO: add a proper content length header
-    headers.insert(
-        header::CONTENT_DISPOSITION,
-        format!(
-            "attachment; filename={}",
-            format_args!("{}.tar.gz", repo.folder_name)
-        )
-        .parse()
-        .unwrap(),
-    );
 
     Ok((StatusCode::OK, headers, body))
 }
@@ -209,48 +227,46 @@ async fn get_extracted(
     server: Extension<Arc<Server>>,
     Json(payload): Json<ScanRequest>,
 ) -> impl IntoResponse {
-    let repo = payload.repo_url.unwrap_or_default();
-    let conf = crate::config::Config::new();
-    let task = Arc::new(Task::new(scan, Arc::new(repo), Arc::new(conf)));
-
-    //Sending the task to the scheduler
-    if let Err(err) = server.tx.lock().unwrap().send(task.clone()) {
-        log::error!("Failed to send task: {}", err);
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to send task".to_owned(),
-        ));
-    }
-
-    //Wait for the scheduler response
-    let (extracted, _, _) = match task.rx.lock().unwrap().recv().unwrap() {
-        Ok(d) => d,
-        Err(err) => {
-            log::error!("Failed to extract data: {err}");
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to extract data".to_owned(),
-            ));
-        }
-    };
+    let output = run_task(&server, payload)?;
 
     let mut headers = HeaderMap::new();
-    headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(output.repo.format.content_type()),
+    );
 
-    Ok((StatusCode::OK, headers, extracted))
+    Ok::<_, ResponseError>((StatusCode::OK, headers, output.extracted_encoded))
 }
 
 async fn get_converted(
     server: Extension<Arc<Server>>,
     Json(payload): Json<ScanRequest>,
 ) -> impl IntoResponse {
-    let repo = payload.repo_url.unwrap_or_default();
-    let conf = crate::config::Config::new();
-    let task = Arc::new(Task::new(scan, Arc::new(repo), Arc::new(conf)));
+    let output = run_task(&server, payload)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(output.repo.format.content_type()),
+    );
+
+    Ok::<_, ResponseError>((StatusCode::OK, headers, output.converted_encoded))
+}
+
+//sends the scan of `req` to the scheduler and waits for its result
+fn run_task(server: &Server, req: ScanRequest) -> Result<ScanResult, ResponseError> {
+    if let Err(err) = check_request(&req, &server.conf) {
+        return Err((StatusCode::BAD_REQUEST, err.to_string()));
+    }
+    let task = Arc::new(Task::new(scan, Arc::new(req), server.conf.to_owned()));
 
     //Sending the task to the scheduler
-    if let Err(err) = server.tx.lock().unwrap().send(task.clone()) {
-        log::error!("Failed to send task: {}", err);
+    let sent = match server.tx.lock() {
+        Ok(tx) => tx.send(task.clone()).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    if let Err(err) = sent {
+        log::error!("Failed to send task: {err}");
         return Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             "Failed to send task".to_owned(),
@@ -258,51 +274,115 @@ async fn get_converted(
     }
 
     //Wait for the scheduler response
-    let ret = task.rx.lock().unwrap().recv().unwrap();
-    let (_, converted, _) = match ret {
-        Ok(d) => d,
+    let ret = match task.rx.lock() {
+        Ok(rx) => rx.recv().map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    match ret {
+        Ok(Ok(d)) => Ok(d),
+        Ok(Err(err)) => {
+            log::error!("Failed to scan data: {err}");
+            Err((status_code(&err), err.to_string()))
+        }
         Err(err) => {
-            log::error!("Failed to convert data: {err}");
-            return Err((
+            log::error!("Failed to receive the task result: {err}");
+            Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to convert data".to_owned(),
+                "Failed to scan data".to_owned(),
+            ))
+        }
+    }
+}
+
+//returns the status code of the response to a failed scan
+fn status_code(err: &Error) -> StatusCode {
+    match err {
+        Error::InvalidUrl(_) | Error::Parse { .. } => StatusCode::BAD_REQUEST,
+        Error::RefNotFound(_) => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+//returns the compressed tarball of the extracted and converted data
+fn create_tarball(output: &pipeline::Output) -> Result<Vec<u8>, Error> {
+    let extension = output.repo.format.extension();
+    let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, data) in [
+        ("converted", &output.converted_encoded),
+        ("extracted", &output.extracted_encoded),
+    ] {
+        let path = format!("{name}.{extension}");
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        if let Err(err) = builder.append_data(&mut header, &path, data.as_slice()) {
+            return Err(Error::io(
+                format!("failed to add `{path}` to the tarball"),
+                err,
             ));
         }
-    };
+    }
+    match builder.into_inner().and_then(|encoder| encoder.finish()) {
+        Ok(tarball) => Ok(tarball),
+        Err(err) => Err(Error::io("failed to write the tarball", err)),
+    }
+}
 
-    let mut headers = HeaderMap::new();
-    headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+//checks the request options before the repository is scanned
+fn check_request(req: &ScanRequest, conf: &config::Config) -> Result<(), Error> {
+    get_format(req)?;
+    filters::new(&get_filters(req, conf))?;
+    let registry = external::load_registry(&conf.converters_path)?;
+    let converter = get_converter(req, conf);
+    if !converters::exists(converter, &registry) {
+        return Err(Error::convert(format!("unknown converter `{converter}`")));
+    }
+    Ok(())
+}
 
-    Ok((StatusCode::OK, headers, converted))
+//returns the converter requested, the default converter of the settings otherwise
+fn get_converter<'a>(req: &'a ScanRequest, conf: &'a config::Config) -> &'a str {
+    match &req.converter {
+        Some(c) => c,
+        None => &conf.settings.default_converter,
+    }
 }
 
-async fn ping() -> &'static str {
-    "pong"
+//returns the filters requested, the globs of the settings
+//are used when the request has none
+fn get_filters(req: &ScanRequest, conf: &config::Config) -> filters::Options {
+    let mut options = req.filters.to_owned();
+    if options.include.is_empty() {
+        options.include = conf.settings.include.to_owned();
+    }
+    if options.exclude.is_empty() {
+        options.exclude = conf.settings.exclude.to_owned();
+    }
+    options
 }
 
-fn scan(conf: Arc<crate::config::Config>, repo: Arc<String>) -> Result<ScanResult, String> {
-    let mut git_repo = match crate::repo::clone_repository(&repo, &conf) {
-        Ok(r) => r,
-        Err(err) => {
-            return Err(format!("failed to clone repository: {err}"));
-        }
-    };
+//returns the format requested, json by default
+fn get_format(req: &ScanRequest) -> Result<formats::Format, Error> {
+    match &req.format {
+        Some(f) => formats::Format::from_name(f),
+        None => Ok(formats::Format::default()),
+    }
+}
 
-    let (extracted_data, extracted_json_data) = match crate::extractor::extract(&mut git_repo) {
-        Ok(d) => d,
-        Err(err) => {
-            return Err(format!("failed to extract repository data: {err}"));
-        }
-    };
+async fn ping() -> &'static str {
+    "pong"
+}
 
-    let conv = crate::converters::shmup::new();
-    let (_, converted_json_data) =
-        match crate::converters::convert(&mut git_repo, extracted_data, &conv) {
-            Ok(d) => d,
-            Err(err) => {
-                return Err(format!("failed to convert extracted data: {err}"));
-            }
-        };
+fn scan(conf: Arc<config::Config>, req: Arc<ScanRequest>) -> Result<ScanResult, Error> {
+    let repo = req.repo_url.to_owned().unwrap_or_default();
+    let mut scan = pipeline::from_url(&repo)
+        .with_format(get_format(&req)?)
+        .with_filters(req.filters.to_owned())
+        .with_converter(get_converter(&req, &conf))
+        .with_registry(external::load_registry(&conf.converters_path)?);
+    if let Some(r) = req._ref_.as_deref().filter(|r| !r.is_empty()) {
+        scan = scan.with_ref(r);
+    }
 
-    Ok((extracted_json_data, converted_json_data, git_repo))
+    scan.run(&conf)
 }
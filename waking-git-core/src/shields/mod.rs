@@ -0,0 +1,3 @@
+pub const NONE: &str = "none";
+pub const HALF_GUARD: &str = "half-guard";
+pub const FULL: &str = "full";
@@ -5,14 +5,19 @@ use std::io::prelude::*;
 use std::io::Error;
 use std::path;
 
-pub fn store_json_data(data: String, dest_folder: String, dest_path: &String) -> Result<(), Error> {
+/// write `data` to `dest_path`, in the `dest_folder` created if needed.
+/// The data is written to a temporary file first, so that the players
+/// watching `dest_path` never read a file partially written
+pub fn store_data(data: &[u8], dest_folder: String, dest_path: &String) -> Result<(), Error> {
     let path = path::Path::new(&dest_folder);
     if !path.exists() {
         fs::create_dir_all(&dest_folder)?;
     }
 
-    let mut file = File::create(dest_path)?;
-    file.write_all(data.as_bytes())?;
+    let tmp_path = format!("{dest_path}.tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(data)?;
+    fs::rename(&tmp_path, dest_path)?;
 
     Ok(())
 }
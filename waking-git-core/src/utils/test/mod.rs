@@ -1,10 +1,34 @@
 use std::env;
 use std::fs;
 use std::path;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub const TMP_DIR: &str = "/tmp/wake-tmp-folder";
 
+//the number of temporary folders created by the process
+static TEMP_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// TempDir is a temporary folder of its own, so that the tests
+/// running at the same time do not share files. It is removed when dropped
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+impl TempDir {
+    /// returns the path of `name` in the folder
+    pub fn join(&self, name: &str) -> String {
+        self.path.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
 fn create_tmp_folder() {
     //Create a temporary folder
     //Create the temporary directory if it doesn't exist
@@ -35,3 +59,48 @@ pub fn teardown() {
 fn move_to_tmp_folder() {
     env::set_current_dir(Path::new(TMP_DIR)).unwrap();
 }
+
+//write `files` in the working directory of `r` and commit them on HEAD,
+//returns the oid of the commit
+pub fn commit_files(r: &git2::Repository, files: &[(&str, &str)]) -> git2::Oid {
+    let workdir = r.workdir().unwrap().to_owned();
+    let mut index = r.index().unwrap();
+    for (file, content) in files {
+        let path = workdir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        index.add_path(Path::new(file)).unwrap();
+    }
+    index.write().unwrap();
+    let tree = r.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = git2::Signature::now("wake", "wake@example.com").unwrap();
+    let parents = match r.head() {
+        Ok(h) => vec![h.peel_to_commit().unwrap()],
+        Err(_) => vec![],
+    };
+    let parents: Vec<&git2::Commit> = parents.iter().collect();
+    r.commit(Some("HEAD"), &sig, &sig, "commit", &tree, &parents)
+        .unwrap()
+}
+
+//write `file` in the working directory of `r` and commit it on HEAD
+pub fn commit(r: &git2::Repository, file: &str, content: &str) -> git2::Oid {
+    commit_files(r, &[(file, content)])
+}
+
+//returns a new empty temporary folder
+pub fn temp_dir() -> TempDir {
+    let n = TEMP_DIRS.fetch_add(1, Ordering::SeqCst);
+    let path = env::temp_dir().join(format!("wake-test-{}-{n}", process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    TempDir { path }
+}
+
+//returns a new git repository, without commits, created
+//in the `app` folder of a new temporary folder
+pub fn temp_repo() -> (TempDir, git2::Repository) {
+    let dir = temp_dir();
+    let r = git2::Repository::init(dir.path.join("app")).unwrap();
+    (dir, r)
+}
@@ -0,0 +1,5 @@
+pub const NONE: &str = "none";
+pub const SIMPLE_GUN: &str = "simple_gun";
+pub const FAST_GUN: &str = "fast_gun";
+pub const MULTI_DIRECTION_RECTANGLE_GUN: &str = "multi_direction_rectangle_gun";
+pub const MULTI_DIRECTION_CIRCLE_GUN: &str = "multi_direction_circle_gun";